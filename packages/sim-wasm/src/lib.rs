@@ -1,25 +1,117 @@
 mod models;
 mod utils;
 mod types;
+mod simulator;
+mod qmc;
+mod multi_asset;
 
 use wasm_bindgen::prelude::*;
-use web_sys::console;
-use rand::prelude::*;
 
 pub use types::*;
 pub use models::*;
 pub use utils::*;
+pub use multi_asset::MultiAssetEngine;
+
+use simulator::{touch_levels, FirstTouch, Model, PathOutcome, QeCounts, ShockBuffer};
 
 const MAX_PATH_SAMPLES: usize = 50;
 const HISTOGRAM_BINS: usize = 40;
+const CONFIG_VERSION: u32 = 1;
+// Entropy seeds are kept below 2^53 so they survive a round trip through a JS number
+const JS_SAFE_SEED_MASK: u64 = (1 << 53) - 1;
+
+/// Number of running estimates recorded in `diagnostics.convergence`
+const CONVERGENCE_CHECKPOINTS: usize = 20;
+
+/// How many paths pass between checks of a run's stop condition
+const STOP_CHECK_INTERVAL: u32 = 256;
+
+/// How many paths pass between CI checks in `run_simulation_until`
+const CI_CHECK_INTERVAL: u32 = 1000;
 
-// Macro for logging to browser console
+// 10^9 scaled prices still fit comfortably in an i64
+const MAX_FIXED_POINT_DECIMALS: u32 = 9;
+
+/// Widest theta scale `implied_vol_scale` searches, either way from 1
+const MAX_VOL_SCALE: f64 = 64.0;
+
+// Macro for logging to browser console (no-op on native targets, where JS imports are unavailable)
 macro_rules! log {
     ( $( $t:tt )* ) => {
-        console::log_1(&format!( $( $t )* ).into());
+        #[cfg(target_arch = "wasm32")]
+        web_sys::console::log_1(&format!( $( $t )* ).into());
     };
 }
 
+fn terminal_stats(final_prices: &[f64]) -> Option<TerminalStats> {
+    if final_prices.is_empty() {
+        return None;
+    }
+    
+    let mut sorted = final_prices.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let at = |prob: f64| utils::quantile_sorted(&sorted, prob);
+    
+    Some(TerminalStats { p5: at(0.05), p25: at(0.25), p50: at(0.5), p75: at(0.75), p95: at(0.95) })
+}
+
+/// VaR and CVaR of ln(S_T / s0) at tail probability `level`, which the caller has checked
+fn tail_risk(final_prices: &[f64], s0: f64, level: f64) -> Option<RiskMetrics> {
+    if final_prices.is_empty() {
+        return None;
+    }
+    
+    let mut returns: Vec<f64> = final_prices.iter().map(|price| (price / s0).ln()).collect();
+    returns.sort_by(f64::total_cmp);
+    
+    let var = utils::quantile_sorted(&returns, level);
+    // Never empty: the interpolated quantile is at least the smallest return
+    let tail: Vec<f64> = returns.iter().copied().take_while(|r| *r <= var).collect();
+    let cvar = tail.iter().sum::<f64>() / tail.len() as f64;
+    
+    Some(RiskMetrics { level, var, cvar, n: final_prices.len() as u32 })
+}
+
+fn first_passage_stats(touch_times: &[f64]) -> Option<FirstPassageStats> {
+    if touch_times.is_empty() {
+        return None;
+    }
+    
+    let mut sorted = touch_times.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let at = |prob: f64| utils::quantile_sorted(&sorted, prob);
+    
+    Some(FirstPassageStats {
+        p5: at(0.05),
+        p25: at(0.25),
+        p50: at(0.5),
+        p75: at(0.75),
+        p95: at(0.95),
+        touched: sorted.len() as u32,
+    })
+}
+
+fn price_histogram(final_prices: &[f64], n_bins: u32) -> Option<PriceHistogram> {
+    if final_prices.is_empty() || n_bins == 0 {
+        return None;
+    }
+    
+    let n_bins = n_bins as usize;
+    let min_price = final_prices.iter().copied().fold(f64::INFINITY, f64::min);
+    let max_price = final_prices.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let bin_width = (max_price - min_price) / n_bins as f64;
+    
+    let mut counts = vec![0u32; n_bins];
+    for price in final_prices {
+        // Every price lands in bin 0 when all paths end at the same price
+        let idx = if bin_width > 0.0 { ((price - min_price) / bin_width).floor() as usize } else { 0 };
+        counts[idx.min(n_bins - 1)] += 1;
+    }
+    
+    let bin_edges = (0..=n_bins).map(|i| min_price + i as f64 * bin_width).collect();
+    Some(PriceHistogram { bin_edges, counts })
+}
+
 fn build_distribution(final_prices: &[f64], samples: Vec<PathSample>) -> Option<DistributionSummary> {
     if final_prices.is_empty() {
         return None;
@@ -61,7 +153,7 @@ fn build_distribution(final_prices: &[f64], samples: Vec<PathSample>) -> Option<
         }]
     } else {
         let bin_width = (max_price - min_price) / HISTOGRAM_BINS as f64;
-        let mut counts = vec![0u32; HISTOGRAM_BINS];
+        let mut counts = [0u32; HISTOGRAM_BINS];
         
         for price in final_prices {
             let mut idx = ((price - min_price) / bin_width).floor() as isize;
@@ -97,267 +189,5519 @@ fn build_distribution(final_prices: &[f64], samples: Vec<PathSample>) -> Option<
     })
 }
 
-#[wasm_bindgen]
-pub struct MonteCarloEngine {
-    sim_inputs: SimInputs,
-    rng: rand::rngs::StdRng,
+/// RNG for path `index` of a run: paths depend only on (seed, index), so any one can be replayed
+fn path_rng(replay_seed: u64, index: u32) -> rand::rngs::StdRng {
+    use rand::SeedableRng;
+    
+    rand::rngs::StdRng::seed_from_u64(utils::path_seed(replay_seed, index as u64))
 }
 
-#[wasm_bindgen]
-impl MonteCarloEngine {
-    #[wasm_bindgen(constructor)]
-    pub fn new(inputs_json: &str) -> Result<MonteCarloEngine, JsValue> {
-        // Set panic hook for better error messages
-        utils::set_panic_hook();
+/// Path `index` of the run seeded by `replay_seed`, with QMC price shocks when the inputs ask for them
+fn run_path(
+    model: &Model,
+    replay_seed: u64,
+    index: u32,
+    trace: Option<&mut Vec<PathPoint>>,
+    target: Option<&Target>,
+) -> PathOutcome {
+    let price_shocks = model.qmc_price_shocks(replay_seed, index);
+    simulate_drawn_path(model, &mut path_rng(replay_seed, index), price_shocks, false, trace, target)
+}
+
+/// `run_path` that records the path's draws into `shocks`, for `replay_path`
+fn run_recorded_path(
+    model: &Model,
+    replay_seed: u64,
+    index: u32,
+    shocks: &mut ShockBuffer,
+    trace: Option<&mut Vec<PathPoint>>,
+    target: Option<&Target>,
+) -> PathOutcome {
+    let price_shocks = model.qmc_price_shocks(replay_seed, index);
+    let mut rng = path_rng(replay_seed, index);
+    simulate_drawn_path(model, &mut shocks.record(&mut rng), price_shocks, false, trace, target)
+}
+
+/// Path `index` again from the draws `run_recorded_path` left in `shocks`, without regenerating
+/// them; `mirrored` negates every diffusive price shock, QMC ones included, for the antithetic twin
+fn replay_path(
+    model: &Model,
+    replay_seed: u64,
+    index: u32,
+    shocks: &ShockBuffer,
+    mirrored: bool,
+    trace: Option<&mut Vec<PathPoint>>,
+    target: Option<&Target>,
+) -> PathOutcome {
+    let price_shocks = model.qmc_price_shocks(replay_seed, index);
+    simulate_drawn_path(model, &mut shocks.replay(), price_shocks, mirrored, trace, target)
+}
+
+/// The path in `shocks` as `target` sees it, when the target watches levels (touch, first_touch):
+/// a path monitors one set of levels, so a run scoring several targets on shared paths replays the
+/// same draws with each target's levels. None means the shared outcome already serves the target.
+fn watched_outcome(
+    model: &Model,
+    replay_seed: u64,
+    index: u32,
+    shocks: &ShockBuffer,
+    mirrored: bool,
+    target: &Target,
+) -> Option<PathOutcome> {
+    touch_levels(target).map(|_| replay_path(model, replay_seed, index, shocks, mirrored, None, Some(target)))
+}
+
+fn simulate_drawn_path<R: rand::Rng + ?Sized>(
+    model: &Model,
+    rng: &mut R,
+    price_shocks: Option<Vec<f64>>,
+    mirrored: bool,
+    trace: Option<&mut Vec<PathPoint>>,
+    target: Option<&Target>,
+) -> PathOutcome {
+    if mirrored {
+        return model.simulate_mirrored_path(rng, trace, target, price_shocks.as_deref());
+    }
+    
+    match (price_shocks, target) {
+        (Some(shocks), _) => model.simulate_shocked_path(rng, trace, target, &shocks),
+        (None, Some(target)) => model.simulate_target_path(rng, trace, target),
+        (None, None) => model.simulate_path(rng, trace),
+    }
+}
+
+/// Running totals shared by `run_simulation` and `run_batch`
+///
+/// Every path carries a weight (1.0 unless a sampling scheme says otherwise) and the estimate
+/// is the self-normalized `sum(w * hit) / sum(w)`; with unit weights that is the hit rate.
+/// Likelihood-ratio weights have mean 1 by construction, so those runs divide by the path count
+/// instead and skip the noise of `sum(w)`.
+struct RunAccumulator {
+    hits: u32,
+    n: u32,
+    weight_sum: f64,
+    weighted_hits: f64,
+    weight_sq_sum: f64,
+    weight_sq_hits: f64,
+    weighted: bool,  // some path had a weight other than 1.0
+    likelihood_ratios: bool,  // weights are likelihood ratios: estimates average over paths, not weights
+    path_hits: Vec<(f64, bool)>,  // (weight, hit) per path in path order, for the convergence trace
+    final_prices: Vec<f64>,
+    touch_times: Vec<f64>,  // first-passage time of every path that touched a watched level
+    samples: Vec<PathSample>,
+    regime_switches: Vec<u32>,
+    regime_time: Vec<f64>,  // hours in each regime, summed over the paths
+    qe: QeCounts,
+}
+
+impl RunAccumulator {
+    fn new(capacity: usize) -> Self {
+        RunAccumulator {
+            hits: 0,
+            n: 0,
+            weight_sum: 0.0,
+            weighted_hits: 0.0,
+            weight_sq_sum: 0.0,
+            weight_sq_hits: 0.0,
+            weighted: false,
+            likelihood_ratios: false,
+            path_hits: Vec::with_capacity(capacity),
+            final_prices: Vec::with_capacity(capacity),
+            touch_times: Vec::new(),
+            samples: Vec::new(),
+            regime_switches: Vec::with_capacity(capacity),
+            regime_time: Vec::new(),
+            qe: QeCounts::default(),
+        }
+    }
+    
+    /// An accumulator for importance sampling, whose weights are likelihood ratios
+    fn with_likelihood_ratios(capacity: usize) -> Self {
+        RunAccumulator { likelihood_ratios: true, ..RunAccumulator::new(capacity) }
+    }
+    
+    fn wants_sample(&self) -> bool {
+        self.samples.len() < MAX_PATH_SAMPLES
+    }
+    
+    fn record(&mut self, id: u32, outcome: &PathOutcome, trace: Option<Vec<PathPoint>>, hit: bool) -> Result<(), String> {
+        self.record_weighted(id, outcome, trace, hit, 1.0)
+    }
+    
+    fn record_weighted(
+        &mut self,
+        id: u32,
+        outcome: &PathOutcome,
+        trace: Option<Vec<PathPoint>>,
+        hit: bool,
+        weight: f64,
+    ) -> Result<(), String> {
+        checked_final_price(id, outcome)?;
+        if !(weight >= 0.0 && weight.is_finite()) {
+            return Err(format!("Path {} has an invalid weight ({})", id, weight));
+        }
         
-        // Parse inputs
-        let sim_inputs: SimInputs = serde_json::from_str(inputs_json)
-            .map_err(|e| JsValue::from_str(&format!("Failed to parse inputs: {}", e)))?;
+        if let Some(points) = trace {
+            self.samples.push(PathSample { id, points });
+        }
         
-        // Initialize RNG with a seed for reproducibility
-        use rand::SeedableRng;
-        let rng = rand::rngs::StdRng::from_entropy();
+        self.final_prices.push(outcome.final_price);
+        self.touch_times.extend(outcome.touch_time);
+        self.regime_switches.push(outcome.regime_switches);
+        self.add_regime_time(&outcome.regime_time);
+        self.qe.add(&outcome.qe);
+        self.n += 1;
+        self.weight_sum += weight;
+        self.weight_sq_sum += weight * weight;
+        self.weighted |= weight != 1.0;
+        self.path_hits.push((weight, hit));
+        if hit {
+            self.hits += 1;
+            self.weighted_hits += weight;
+            self.weight_sq_hits += weight * weight;
+        }
         
-        Ok(MonteCarloEngine { sim_inputs, rng })
+        Ok(())
     }
     
-    #[wasm_bindgen]
-    pub fn run_simulation(&mut self, target_json: &str, n_paths: u32) -> Result<String, JsValue> {
-        let target: Target = serde_json::from_str(target_json)
-            .map_err(|e| JsValue::from_str(&format!("Failed to parse target: {}", e)))?;
+    /// Fold in the totals of an accumulator that covered a disjoint set of paths
+    #[cfg(not(target_arch = "wasm32"))]
+    fn merge(&mut self, other: RunAccumulator) {
+        self.hits += other.hits;
+        self.n += other.n;
+        self.weight_sum += other.weight_sum;
+        self.weighted_hits += other.weighted_hits;
+        self.weight_sq_sum += other.weight_sq_sum;
+        self.weight_sq_hits += other.weight_sq_hits;
+        self.weighted |= other.weighted;
+        self.likelihood_ratios |= other.likelihood_ratios;
+        self.path_hits.extend(other.path_hits);
+        self.final_prices.extend(other.final_prices);
+        self.touch_times.extend(other.touch_times);
+        self.regime_switches.extend(other.regime_switches);
+        self.add_regime_time(&other.regime_time);
+        self.qe.add(&other.qe);
         
-        log!("Starting simulation with {} paths for target: {:?}", n_paths, target.kind);
+        let room = MAX_PATH_SAMPLES.saturating_sub(self.samples.len());
+        self.samples.extend(other.samples.into_iter().take(room));
+    }
+    
+    fn add_regime_time(&mut self, regime_time: &[f64]) {
+        if self.regime_time.len() < regime_time.len() {
+            self.regime_time.resize(regime_time.len(), 0.0);
+        }
+        for (total, hours) in self.regime_time.iter_mut().zip(regime_time) {
+            *total += hours;
+        }
+    }
+    
+    /// Fraction of all simulated time spent in each regime; empty when no time was simulated
+    fn regime_occupancy(&self) -> Vec<f64> {
+        let total: f64 = self.regime_time.iter().sum();
+        if total > 0.0 {
+            self.regime_time.iter().map(|hours| hours / total).collect()
+        } else {
+            Vec::new()
+        }
+    }
+    
+    /// What the weighted hits are divided by: the weight sum, or the path count for likelihood ratios
+    fn mass(&self) -> f64 {
+        if self.likelihood_ratios {
+            self.n as f64
+        } else {
+            self.weight_sum
+        }
+    }
+    
+    fn p(&self) -> f64 {
+        self.weighted_hits / self.mass()
+    }
+    
+    fn saturated(&self) -> bool {
+        self.hits == 0 || self.hits == self.n
+    }
+    
+    /// Delta-method stderr of the ratio estimator: sqrt(sum(w^2 (hit - p)^2)) / sum(w), or with
+    /// likelihood ratios sqrt(sum((w hit - p)^2)) / n; both reduce to sqrt(p (1 - p) / n) with
+    /// unit weights
+    fn stderr(&self) -> f64 {
+        // A saturated estimate has zero sample variance; see `utils::hit_stderr`
+        if self.saturated() {
+            return utils::hit_stderr(self.hits, self.n);
+        }
         
-        let mut hits = 0u32;
-        let mut prices = Vec::with_capacity(n_paths as usize);
-        let mut sample_paths: Vec<PathSample> = Vec::new();
+        let p = self.p();
+        let spread = if self.likelihood_ratios {
+            self.weight_sq_hits - 2.0 * p * self.weighted_hits + p * p * self.n as f64
+        } else {
+            self.weight_sq_hits * (1.0 - 2.0 * p) + p * p * self.weight_sq_sum
+        };
+        spread.max(0.0).sqrt() / self.mass()
+    }
+    
+    fn ci(&self) -> [f64; 2] {
+        self.ci_at(0.95)
+    }
+    
+    /// Running estimate after each twentieth of the paths; the last entry is the final `p`
+    fn convergence(&self) -> Option<Vec<f64>> {
+        let n = self.path_hits.len();
+        if n == 0 {
+            return None;
+        }
         
-        // Run simulations
-        for i in 0..n_paths {
-            let mut trace = if sample_paths.len() < MAX_PATH_SAMPLES {
-                Some(Vec::new())
-            } else {
-                None
-            };
-            
-            let final_price = match trace.as_mut() {
-                Some(points) => self.simulate_path(Some(points))?,
-                None => self.simulate_path(None)?,
-            };
-            
-            if let Some(points) = trace {
-                sample_paths.push(PathSample { id: i, points });
-            }
-            
-            prices.push(final_price);
-            
-            let hit = match target.kind.as_str() {
-                "above" => {
-                    if let Some(k) = target.K {
-                        final_price > k
-                    } else {
-                        return Err(JsValue::from_str("Strike price K required for 'above' target"));
-                    }
-                },
-                "range" => {
-                    if let (Some(l), Some(u)) = (target.L, target.U) {
-                        final_price >= l && final_price <= u
-                    } else {
-                        return Err(JsValue::from_str("Range bounds L and U required for 'range' target"));
-                    }
-                },
-                _ => return Err(JsValue::from_str("Invalid target kind")),
-            };
-            
+        // Checkpoint k sits at ceil(k * n / 20) paths; short runs share checkpoints
+        let mut checkpoints: Vec<usize> = (1..=CONVERGENCE_CHECKPOINTS)
+            .map(|k| (k * n).div_ceil(CONVERGENCE_CHECKPOINTS))
+            .collect();
+        checkpoints.dedup();
+        
+        let mut trace = Vec::with_capacity(checkpoints.len());
+        let mut pending = checkpoints.into_iter().peekable();
+        let (mut weight_sum, mut weighted_hits) = (0.0, 0.0);
+        for (i, &(weight, hit)) in self.path_hits.iter().enumerate() {
+            weight_sum += if self.likelihood_ratios { 1.0 } else { weight };
             if hit {
-                hits += 1;
+                weighted_hits += weight;
             }
-            
-            // Log progress every 10%
-            if i > 0 && i % (n_paths / 10) == 0 {
-                log!("Progress: {}%", (i * 100) / n_paths);
+            if pending.next_if_eq(&(i + 1)).is_some() {
+                trace.push(weighted_hits / weight_sum);
             }
         }
         
-        // Calculate results
-        let p = hits as f64 / n_paths as f64;
-        let stderr = (p * (1.0 - p) / n_paths as f64).sqrt();
-        
-        // Wilson confidence interval
-        let ci = utils::wilson_ci(hits, n_paths, 0.95);
+        Some(trace)
+    }
+    
+    /// Wilson interval (rule of three when saturated); weighted runs use the normal interval
+    fn ci_at(&self, confidence: f64) -> [f64; 2] {
+        if self.weighted && !self.saturated() {
+            let (p, margin) = (self.p(), utils::two_sided_z(confidence) * self.stderr());
+            [(p - margin).max(0.0), (p + margin).min(1.0)]
+        } else {
+            utils::binomial_ci(self.hits, self.n, confidence)
+        }
+    }
+    
+    fn finish(self, target: Target, model: &Model, output: &OutputOptions) -> Result<SimResult, String> {
+        let inputs = &model.sim_inputs;
+        let p = self.p();
+        let saturated = self.saturated();
+        let stderr = self.stderr();
+        let ci = self.ci();
+        let ci_levels = output.ci_levels.iter().map(|&level| LevelCi { level, ci: self.ci_at(level) }).collect();
         
-        let distribution = build_distribution(&prices, sample_paths);
+        let convergence = self.convergence();
+        let regime_switches = summarize_regime_switches(&self.regime_switches);
+        let terminal_stats = terminal_stats(&self.final_prices);
+        let first_passage = first_passage_stats(&self.touch_times);
+        let price_histogram = output.histogram_bins.and_then(|n_bins| price_histogram(&self.final_prices, n_bins));
+        let risk = output.risk_level.and_then(|level| tail_risk(&self.final_prices, inputs.s0, level));
+        let regime_occupancy = self.regime_occupancy();
+        let payoff = payoff_estimate(&target, &self.final_prices, &self.path_hits, self.likelihood_ratios)?;
+        let distribution = build_distribution(&self.final_prices, self.samples);
         
         let result = SimResult {
             target,
             p,
             ci,
             fair: p * 100.0, // Convert to cents
+            hits: self.hits,
+            n: self.n,
             diagnostics: SimDiagnostics {
                 stderr,
-                n: n_paths,
-                convergence: None, // TODO: Add convergence tracking
+                relative_stderr: if p > 0.0 { Some(stderr / p) } else { None },
+                n: self.n,
+                saturated,
+                convergence,
+                regime_switches,
+                annualized_vol: annualized_vol(inputs, model.pi0()),
+                terminal_variance: terminal_variance_check(inputs, model.horizon(), &self.final_prices),
+                qe_branches: qe_branch_stats(&self.qe),
+                replay_seed: None,
+                scenario_switch_hour: model.scenario_switch_hour(),
+                feller_violations: feller_checks(inputs).iter().any(|check| !check.satisfied),
+                regime_occupancy,
             },
             distribution,
+            terminal_stats,
+            first_passage,
+            price_histogram,
+            risk,
+            control_variate: None,
+            payoff,
+            fixed_point: None,
+            ci_levels,
+            incomplete: false,
         };
         
-        // Return JSON result
-        serde_json::to_string(&result)
-            .map_err(|e| JsValue::from_str(&format!("Failed to serialize result: {}", e)))
+        ensure_finite(&result)?;
+        Ok(match output.fixed_point_decimals {
+            Some(decimals) => SimResult { fixed_point: Some(fixed_point_values(&result, decimals)), ..result },
+            None => result,
+        })
     }
+}
+
+fn fixed_point_values(result: &SimResult, decimals: u32) -> FixedPointValues {
+    let scale = 10f64.powi(decimals as i32);
+    let fixed = |value: f64| (value * scale).round() as i64;
     
-    #[wasm_bindgen]
-    pub fn run_batch(&mut self, target_json: &str, n_paths: u32, batch_size: u32) -> Result<js_sys::Array, JsValue> {
-        let target: Target = serde_json::from_str(target_json)
-            .map_err(|e| JsValue::from_str(&format!("Failed to parse target: {}", e)))?;
-        
-        let results = js_sys::Array::new();
-        let mut total_hits = 0u32;
-        let mut total_paths = 0u32;
-        let mut final_prices: Vec<f64> = Vec::with_capacity(n_paths as usize);
-        let mut sample_paths: Vec<PathSample> = Vec::new();
-        
-        let num_batches = (n_paths + batch_size - 1) / batch_size;
-        
-        for batch in 0..num_batches {
-            let batch_paths = if batch == num_batches - 1 {
-                n_paths - batch * batch_size
-            } else {
-                batch_size
-            };
-            
-            // Run batch
-            let mut batch_hits = 0u32;
-            for path_idx in 0..batch_paths {
-                let global_index = batch * batch_size + path_idx;
-                
-                let mut trace = if sample_paths.len() < MAX_PATH_SAMPLES {
-                    Some(Vec::new())
-                } else {
-                    None
-                };
-                
-                let final_price = match trace.as_mut() {
-                    Some(points) => self.simulate_path(Some(points))?,
-                    None => self.simulate_path(None)?,
-                };
-                
-                if let Some(points) = trace {
-                    sample_paths.push(PathSample { id: global_index, points });
-                }
-                
-                final_prices.push(final_price);
-                
-                let hit = match target.kind.as_str() {
-                    "above" => final_price > target.K.unwrap(),
-                    "range" => final_price >= target.L.unwrap() && final_price <= target.U.unwrap(),
-                    _ => return Err(JsValue::from_str("Invalid target kind")),
-                };
-                
-                if hit {
-                    batch_hits += 1;
-                }
-            }
-            
-            total_hits += batch_hits;
-            total_paths += batch_paths;
-            
-            // Calculate intermediate result
-            let p = total_hits as f64 / total_paths as f64;
-            let ci = utils::wilson_ci(total_hits, total_paths, 0.95);
-            
-            let intermediate = IntermediateResult {
-                batch: batch + 1,
-                total_paths,
-                p,
-                ci,
-                fair: p * 100.0,
-            };
-            
-            let result_json = serde_json::to_string(&intermediate)
-                .map_err(|e| JsValue::from_str(&format!("Failed to serialize: {}", e)))?;
-            
-            results.push(&JsValue::from_str(&result_json));
+    FixedPointValues {
+        decimals,
+        fair: fixed(result.fair),
+        ci: [fixed(result.ci[0] * 100.0), fixed(result.ci[1] * 100.0)],
+        price_min: result.distribution.as_ref().map(|dist| fixed(dist.min)),
+        price_mean: result.distribution.as_ref().map(|dist| fixed(dist.mean)),
+        price_max: result.distribution.as_ref().map(|dist| fixed(dist.max)),
+    }
+}
+
+/// A path's final price, or an error naming the path when it is not finite
+fn checked_final_price(id: u32, outcome: &PathOutcome) -> Result<f64, String> {
+    if outcome.final_price.is_finite() {
+        Ok(outcome.final_price)
+    } else {
+        Err(format!("Path {} produced a non-finite final price ({})", id, outcome.final_price))
+    }
+}
+
+/// serde_json writes NaN/Inf as `null`, which the JS side would silently misread; fail loudly instead
+fn ensure_finite(result: &SimResult) -> Result<(), String> {
+    let mut fields: Vec<(String, f64)> = vec![
+        ("p".to_string(), result.p),
+        ("fair".to_string(), result.fair),
+        ("ci[0]".to_string(), result.ci[0]),
+        ("ci[1]".to_string(), result.ci[1]),
+        ("diagnostics.stderr".to_string(), result.diagnostics.stderr),
+        ("diagnostics.annualized_vol.blended".to_string(), result.diagnostics.annualized_vol.blended),
+    ];
+    let mut push_all = |name: &str, values: &mut dyn Iterator<Item = f64>| {
+        fields.extend(values.enumerate().map(|(i, value)| (format!("{}[{}]", name, i), value)));
+    };
+    
+    let diagnostics = &result.diagnostics;
+    push_all("diagnostics.annualized_vol.by_regime", &mut diagnostics.annualized_vol.by_regime.iter().copied());
+    push_all("diagnostics.convergence", &mut diagnostics.convergence.iter().flatten().copied());
+    push_all("diagnostics.regime_occupancy", &mut diagnostics.regime_occupancy.iter().copied());
+    for (i, level) in result.ci_levels.iter().enumerate() {
+        push_all(&format!("ci_levels[{}].ci", i), &mut level.ci.iter().copied());
+    }
+    if let Some(dist) = &result.distribution {
+        for (i, sample) in dist.samples.iter().enumerate() {
+            push_all(&format!("distribution.samples[{}].points.price", i), &mut sample.points.iter().map(|point| point.price));
         }
-        
-        // Final summary with distribution and diagnostics
-        let total_p = total_hits as f64 / total_paths as f64;
-        let stderr = (total_p * (1.0 - total_p) / total_paths as f64).sqrt();
-        let ci = utils::wilson_ci(total_hits, total_paths, 0.95);
-        let distribution = build_distribution(&final_prices, sample_paths);
-        
-        let final_result = SimResult {
-            target,
-            p: total_p,
-            ci,
-            fair: total_p * 100.0,
-            diagnostics: SimDiagnostics {
-                stderr,
-                n: total_paths,
-                convergence: None,
-            },
-            distribution,
-        };
-        
-        let final_json = serde_json::to_string(&final_result)
-            .map_err(|e| JsValue::from_str(&format!("Failed to serialize final result: {}", e)))?;
-        
-        results.push(&JsValue::from_str(&final_json));
-        
-        Ok(results)
+    }
+    if let Some(histogram) = &result.price_histogram {
+        push_all("price_histogram.bin_edges", &mut histogram.bin_edges.iter().copied());
     }
     
-    fn simulate_path(&mut self, mut trace: Option<&mut Vec<PathPoint>>) -> Result<f64, JsValue> {
-        let dt = self.sim_inputs.dt;
-        let n_steps = (self.sim_inputs.t / dt).ceil() as usize;
-        
-        // Initialize state
-        let mut s = self.sim_inputs.s0;
-        let mut v = self.sim_inputs.regimes.BULL.heston.theta; // Start with long-term vol
-        let mut regime = if self.rng.gen::<f64>() < self.sim_inputs.hmm.pi0[0] {
-            Regime::Bull
-        } else {
-            Regime::Bear
-        };
-        let mut time = 0.0;
+    if let Some(relative) = diagnostics.relative_stderr {
+        fields.push(("diagnostics.relative_stderr".to_string(), relative));
+    }
+    if let Some(hour) = diagnostics.scenario_switch_hour {
+        fields.push(("diagnostics.scenario_switch_hour".to_string(), hour));
+    }
+    if let Some(check) = &diagnostics.terminal_variance {
+        fields.push(("diagnostics.terminal_variance.empirical".to_string(), check.empirical));
+        fields.push(("diagnostics.terminal_variance.theoretical".to_string(), check.theoretical));
+        fields.push(("diagnostics.terminal_variance.ratio".to_string(), check.ratio));
+    }
+    if let Some(qe) = &diagnostics.qe_branches {
+        fields.push(("diagnostics.qe_branches.low_psi".to_string(), qe.low_psi));
+        fields.push(("diagnostics.qe_branches.high_psi".to_string(), qe.high_psi));
+        fields.push(("diagnostics.qe_branches.high_psi_zero".to_string(), qe.high_psi_zero));
+    }
+    if let Some(stats) = &diagnostics.regime_switches {
+        fields.push(("diagnostics.regime_switches.mean".to_string(), stats.mean));
+        fields.push(("diagnostics.regime_switches.variance".to_string(), stats.variance));
+    }
+    if let Some(dist) = &result.distribution {
+        fields.push(("distribution.min".to_string(), dist.min));
+        fields.push(("distribution.max".to_string(), dist.max));
+        fields.push(("distribution.mean".to_string(), dist.mean));
+        fields.push(("distribution.stddev".to_string(), dist.stddev));
+        for (i, bin) in dist.histogram.iter().enumerate() {
+            fields.push((format!("distribution.histogram[{}].price", i), bin.price));
+            fields.push((format!("distribution.histogram[{}].probability", i), bin.probability));
+        }
+    }
+    for (i, level) in result.ci_levels.iter().enumerate() {
+        fields.push((format!("ci_levels[{}].level", i), level.level));
+    }
+    let percentiles = |name: &str, values: [f64; 5]| -> Vec<(String, f64)> {
+        ["p5", "p25", "p50", "p75", "p95"].into_iter().zip(values).map(|(q, value)| (format!("{}.{}", name, q), value)).collect()
+    };
+    if let Some(stats) = &result.terminal_stats {
+        fields.extend(percentiles("terminal_stats", [stats.p5, stats.p25, stats.p50, stats.p75, stats.p95]));
+    }
+    if let Some(stats) = &result.first_passage {
+        fields.extend(percentiles("first_passage", [stats.p5, stats.p25, stats.p50, stats.p75, stats.p95]));
+    }
+    if let Some(risk) = &result.risk {
+        fields.push(("risk.var".to_string(), risk.var));
+        fields.push(("risk.cvar".to_string(), risk.cvar));
+    }
+    if let Some(cv) = &result.control_variate {
+        fields.push(("control_variate.p_raw".to_string(), cv.p_raw));
+        fields.push(("control_variate.stderr_raw".to_string(), cv.stderr_raw));
+        fields.push(("control_variate.p".to_string(), cv.p));
+        fields.push(("control_variate.stderr".to_string(), cv.stderr));
+        fields.push(("control_variate.beta".to_string(), cv.beta));
+        fields.push(("control_variate.control_mean".to_string(), cv.control_mean));
+    }
+    if let Some(payoff) = &result.payoff {
+        fields.push(("payoff.expected".to_string(), payoff.expected));
+        fields.push(("payoff.stderr".to_string(), payoff.stderr));
+    }
+    
+    match fields.into_iter().find(|(_, value)| !value.is_finite()) {
+        Some((name, value)) => Err(format!("Result field {} is not finite ({})", name, value)),
+        None => Ok(()),
+    }
+}
+
+/// Hit indicators of the most recent paths for one target, oldest first
+struct RollingWindow {
+    capacity: usize,
+    target: Option<Target>,
+    hits: std::collections::VecDeque<bool>,
+    total_paths: u64,
+}
+
+impl RollingWindow {
+    fn new(capacity: usize) -> Self {
+        RollingWindow {
+            capacity,
+            target: None,
+            hits: std::collections::VecDeque::with_capacity(capacity),
+            total_paths: 0,
+        }
+    }
+    
+    fn clear(&mut self) {
+        self.hits.clear();
+        self.total_paths = 0;
+    }
+    
+    fn push(&mut self, hit: bool) {
+        if self.hits.len() == self.capacity {
+            self.hits.pop_front();
+        }
+        self.hits.push_back(hit);
+        self.total_paths += 1;
+    }
+    
+    fn estimate(&self) -> RollingEstimate {
+        let n = self.hits.len() as u32;
+        let hits = self.hits.iter().filter(|&&hit| hit).count() as u32;
+        let p = if n > 0 { hits as f64 / n as f64 } else { 0.0 };
         
-        if let Some(points) = trace.as_deref_mut() {
-            points.push(PathPoint { t: time, price: s });
+        RollingEstimate {
+            p,
+            ci: utils::binomial_ci(hits, n, 0.95),
+            fair: p * 100.0,
+            window_size: self.capacity as u32,
+            effective_n: n,
+            total_paths: self.total_paths,
+        }
+    }
+}
+
+/// Long-run hourly variance of a regime, summed over its variance factors
+fn long_run_variance(params: &RegimeParams) -> f64 {
+    params.heston.theta + params.heston2.as_ref().map_or(0.0, |heston| heston.theta)
+}
+
+/// Feller condition for every variance factor of every regime, in regime order
+fn feller_checks(inputs: &SimInputs) -> Vec<FellerCheck> {
+    let mut checks = Vec::new();
+    for (regime, params) in inputs.regimes.iter().enumerate() {
+        for (factor, heston) in std::iter::once(&params.heston).chain(params.heston2.as_ref()).enumerate() {
+            let drift = 2.0 * heston.kappa * heston.theta;
+            let spread = heston.xi * heston.xi;
+            checks.push(FellerCheck {
+                regime: regime_name(regime),
+                factor: factor as u32 + 1,
+                satisfied: drift >= spread,
+                ratio: if spread > 0.0 { Some(drift / spread) } else { None },
+            });
         }
+    }
+    checks
+}
+
+/// Per-regime Feller condition 2 kappa theta >= xi^2 for an inputs JSON, without building an engine
+#[wasm_bindgen]
+pub fn check_feller(inputs_json: &str) -> Result<String, JsValue> {
+    let inputs: SimInputs = serde_json::from_str(inputs_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse inputs: {}", e)))?;
+    
+    to_json(&feller_checks(&inputs))
+}
+
+/// Parameter combinations under which the model quietly reduces to something simpler
+fn degenerate_warnings(inputs: &SimInputs) -> Vec<ModelWarning> {
+    let mut warnings = Vec::new();
+    let mut warn = |code: &str, message: String| warnings.push(ModelWarning { code: code.to_string(), message });
+    
+    for (regime, params) in inputs.regimes.iter().enumerate() {
+        let name = regime_name(regime);
+        let heston = &params.heston;
         
-        // Simulate path
-        for _ in 0..n_steps {
-            // Update regime
-            regime = models::update_regime(&mut self.rng, regime, &self.sim_inputs.hmm, dt);
-            
-            // Get current parameters
-            let params = match regime {
-                Regime::Bull => &self.sim_inputs.regimes.BULL,
-                Regime::Bear => &self.sim_inputs.regimes.BEAR,
-            };
-            
-            // Simulate variance (Heston)
-            v = models::simulate_heston_variance(&mut self.rng, v, &params.heston, dt);
-            
-            // Simulate price with jumps
-            let (new_s, _jump_occurred) = models::simulate_price_with_jumps(
-                &mut self.rng,
-                s,
-                v,
-                params.mu,
-                &params.heston,
-                &self.sim_inputs.jumps,
-                dt,
+        // Stationary sd of the CIR variance is xi * sqrt(theta / (2 kappa))
+        let variance_sd = if heston.kappa > 0.0 { heston.xi * (heston.theta / (2.0 * heston.kappa)).sqrt() } else { f64::INFINITY };
+        if variance_sd < 0.01 * heston.theta {
+            warn(
+                "negligible_vol_of_vol",
+                format!("{} xi = {} barely moves the variance around theta = {}; the regime is effectively constant-vol", name, heston.xi, heston.theta),
             );
-            
-            s = new_s;
-            time += dt;
-            
-            if let Some(points) = trace.as_deref_mut() {
-                points.push(PathPoint { t: time, price: s });
-            }
         }
+        if heston.var_floor >= heston.theta {
+            warn(
+                "variance_pinned_by_floor",
+                format!("{} var_floor = {} is at or above theta = {}, so the variance stays on the floor", name, heston.var_floor, heston.theta),
+            );
+        }
+        
+        let diffusion_sd = (long_run_variance(params) * inputs.t).sqrt();
+        if (params.mu * inputs.t).abs() > 2.0 * diffusion_sd {
+            warn(
+                "drift_dominates_diffusion",
+                format!(
+                    "{} drift moves the log-price by {:.4} over the horizon, more than twice the diffusion's {:.4}",
+                    name,
+                    params.mu * inputs.t,
+                    diffusion_sd
+                ),
+            );
+        }
+    }
+    
+    let close = |a: f64, b: f64| (a - b).abs() <= 0.01 * a.abs().max(b.abs()).max(f64::MIN_POSITIVE);
+    let regimes = &inputs.regimes;
+    for i in 0..regimes.len() {
+        for j in i + 1..regimes.len() {
+            let (a, b) = (&regimes[i], &regimes[j]);
+            if close(a.mu, b.mu) && a.mu_schedule == b.mu_schedule && close(long_run_variance(a), long_run_variance(b)) && close(a.heston.xi, b.heston.xi) {
+                warn(
+                    "indistinguishable_regimes",
+                    format!(
+                        "{} and {} have (nearly) the same drift, long-run variance and vol of vol; the HMM cannot tell them apart",
+                        regime_name(i),
+                        regime_name(j)
+                    ),
+                );
+            }
+        }
+    }
+    
+    let jumps = &inputs.jumps;
+    // Hawkes clustering raises the long-run rate above lambda
+    let intensity = models::mean_jump_intensity(jumps);
+    if intensity > 1.0 {
+        warn(
+            "implausible_jump_intensity",
+            format!("a mean jump intensity of {} means more than one jump per hour on average", intensity),
+        );
+    }
+    let jump_variance = intensity * models::jump_second_moment(jumps);
+    let diffusive_variance = regimes.iter().map(long_run_variance).fold(f64::INFINITY, f64::min);
+    if jump_variance > 9.0 * diffusive_variance {
+        warn(
+            "jumps_dominate",
+            format!(
+                "jumps contribute {:.3e} of variance per hour against {:.3e} from diffusion; the price is essentially a jump process",
+                jump_variance, diffusive_variance
+            ),
+        );
+    }
+    
+    warnings
+}
+
+/// Long-run annualized volatility implied by each regime's Heston theta
+fn annualized_vol(inputs: &SimInputs, pi0: &[f64]) -> AnnualizedVol {
+    let thetas: Vec<f64> = inputs.regimes.iter().map(long_run_variance).collect();
+    let annualize = |variance: f64| (variance * utils::HOURS_PER_YEAR).sqrt();
+    
+    let blended_variance: f64 = thetas.iter().zip(pi0.iter()).map(|(theta, w)| theta * w).sum();
+    
+    AnnualizedVol {
+        by_regime: thetas.iter().map(|&theta| annualize(theta)).collect(),
+        blended: annualize(blended_variance),
+    }
+}
+
+/// Compare the sample variance of the terminal log-price with its closed form
+///
+/// Only pure Heston qualifies: one effective regime, no jumps, no stochastic drift or carry, no
+/// scheduled shocks or scenario switch and no basket. Then Var[ln S_T] is, to leading order, the expected
+/// integrated variance theta*T + (v0 - theta)(1 - e^(-kappa T)) / kappa, and since paths
+/// start at v0 = theta the transient term vanishes.
+fn terminal_variance_check(inputs: &SimInputs, horizon: f64, final_prices: &[f64]) -> Option<TerminalVarianceCheck> {
+    let pure_heston = inputs.regimes.iter().all(|params| *params == inputs.regimes[0])
+        && inputs.jumps.lambda == 0.0
+        && inputs.drift_ou.is_none()
+        && inputs.carry.is_none()
+        && inputs.scenario_switch.is_none()
+        && inputs.vol_shocks.is_empty()
+        && inputs.basket.is_none();
+    if !pure_heston || final_prices.len() < 2 {
+        return None;
+    }
+    
+    let theoretical = long_run_variance(&inputs.regimes[0]) * horizon;
+    
+    let log_returns: Vec<f64> = final_prices.iter().map(|s| (s / inputs.s0).ln()).collect();
+    let n = log_returns.len() as f64;
+    let mean = log_returns.iter().sum::<f64>() / n;
+    let empirical = log_returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    
+    Some(TerminalVarianceCheck {
+        empirical,
+        theoretical,
+        ratio: empirical / theoretical,
+    })
+}
+
+fn qe_branch_stats(counts: &QeCounts) -> Option<QeBranchStats> {
+    let steps = counts.total();
+    if steps == 0 {
+        return None;
+    }
+    
+    let fraction = |count: u64| count as f64 / steps as f64;
+    Some(QeBranchStats {
+        steps,
+        low_psi: fraction(counts.low_psi),
+        high_psi: fraction(counts.high_psi),
+        high_psi_zero: fraction(counts.high_psi_zero),
+    })
+}
+
+fn summarize_regime_switches(switches: &[u32]) -> Option<RegimeSwitchStats> {
+    if switches.is_empty() {
+        return None;
+    }
+    
+    let len = switches.len() as f64;
+    let max = switches.iter().copied().max().unwrap_or(0);
+    let mut counts = vec![0u32; max as usize + 1];
+    for &k in switches {
+        counts[k as usize] += 1;
+    }
+    
+    let mean = switches.iter().map(|&k| k as f64).sum::<f64>() / len;
+    let variance = if len > 1.0 {
+        switches
+            .iter()
+            .map(|&k| {
+                let diff = k as f64 - mean;
+                diff * diff
+            })
+            .sum::<f64>() / (len - 1.0)
+    } else {
+        0.0
+    };
+    
+    Some(RegimeSwitchStats {
+        mean,
+        variance,
+        max,
+        counts,
+    })
+}
+
+/// Bucket a path trace into hourly open/high/low/close candles
+///
+/// Hours without an observation (dt > 1) are carried flat at the last close.
+fn hourly_candles(points: &[PathPoint]) -> Vec<Candle> {
+    let mut candles: Vec<Candle> = Vec::new();
+    let Some(first) = points.first() else {
+        return candles;
+    };
+    
+    let flat = |hour: u32, price: f64| Candle { hour, open: price, high: price, low: price, close: price };
+    let mut last_close = first.price;
+    
+    for point in &points[1..] {
+        // Small tolerance so accumulated time like 0.1 * 10 still lands in hour 0
+        let hour = ((point.t - 1e-9).ceil() as u32).saturating_sub(1);
+        
+        while (candles.len() as u32) < hour {
+            candles.push(flat(candles.len() as u32, last_close));
+        }
+        if candles.len() as u32 == hour {
+            candles.push(flat(hour, last_close));
+        }
+        
+        let candle = candles.last_mut().expect("candle for current hour");
+        candle.high = candle.high.max(point.price);
+        candle.low = candle.low.min(point.price);
+        candle.close = point.price;
+        last_close = point.price;
+    }
+    
+    candles
+}
+
+fn parse_target(target_json: &str) -> Result<Target, JsValue> {
+    serde_json::from_str(target_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse target: {}", e)))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, JsValue> {
+    serde_json::to_string(value)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize result: {}", e)))
+}
+
+/// Echo a caller-supplied request ID: added as `request_id` to an object, or to each object in an array
+fn tag_request_id(mut value: serde_json::Value, request_id: Option<&str>) -> serde_json::Value {
+    let Some(id) = request_id else {
+        return value;
+    };
+    
+    let tag = |entry: &mut serde_json::Value| {
+        if let Some(object) = entry.as_object_mut() {
+            object.insert("request_id".to_string(), serde_json::Value::from(id));
+        }
+    };
+    match &mut value {
+        serde_json::Value::Array(entries) => entries.iter_mut().for_each(tag),
+        entry => tag(entry),
+    }
+    value
+}
+
+/// `to_json`, echoing `request_id` back in the result when one was passed
+fn to_json_tagged<T: serde::Serialize>(value: &T, request_id: Option<&str>) -> Result<String, JsValue> {
+    let value = serde_json::to_value(value)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize result: {}", e)))?;
+    
+    to_json(&tag_request_id(value, request_id))
+}
+
+/// Check a target's payoff: linear and capped payoffs need an above/below strike, and only capped
+/// takes a cap (above its floor, which defaults to 0)
+fn validate_payoff(target: &Target) -> Result<(), String> {
+    let payoff = target.payoff.as_deref().unwrap_or("binary");
+    match (payoff, target.cap, target.floor) {
+        ("binary" | "linear", None, None) => {},
+        ("binary" | "linear", _, _) => return Err(format!("cap and floor only apply to a capped payoff, not \"{}\"", payoff)),
+        ("capped", Some(cap), floor) => {
+            let floor = floor.unwrap_or(0.0);
+            if !(floor >= 0.0 && floor.is_finite() && cap > floor && cap.is_finite()) {
+                return Err(format!("capped payoff needs 0 <= floor < cap, got floor = {} and cap = {}", floor, cap));
+            }
+        },
+        ("capped", None, _) => return Err("capped payoff needs a cap".to_string()),
+        (other, _, _) => return Err(format!("payoff must be \"binary\", \"linear\" or \"capped\", got \"{}\"", other)),
+    }
+    
+    if payoff != "binary" && !matches!(target.kind.as_str(), "above" | "below") {
+        return Err(format!("A {} payoff needs an 'above' or 'below' target, got '{}'", payoff, target.kind));
+    }
+    Ok(())
+}
+
+/// Payoff of a path finishing at `final_price` under a validated linear or capped target
+fn target_payoff(target: &Target, final_price: f64) -> Result<f64, String> {
+    let k = target.K.ok_or("Strike price K required for a linear or capped payoff")?;
+    let intrinsic = if target.kind == "below" { (k - final_price).max(0.0) } else { (final_price - k).max(0.0) };
+    
+    Ok(match target.cap {
+        Some(cap) => intrinsic.clamp(target.floor.unwrap_or(0.0), cap),
+        None => intrinsic,
+    })
+}
+
+/// Weighted mean of the payoff with the stderr used for `p` (self-normalized, or averaged over
+/// paths for `likelihood_ratios`, see `RunAccumulator`); None for binary targets, whose
+/// expectation is `p` itself
+fn payoff_estimate(
+    target: &Target,
+    final_prices: &[f64],
+    path_hits: &[(f64, bool)],
+    likelihood_ratios: bool,
+) -> Result<Option<PayoffEstimate>, String> {
+    let payoff = match target.payoff.as_deref() {
+        None | Some("binary") => return Ok(None),
+        Some(payoff) => payoff.to_string(),
+    };
+    if final_prices.is_empty() {
+        return Ok(None);
+    }
+    
+    let values = final_prices.iter().map(|&price| target_payoff(target, price)).collect::<Result<Vec<f64>, String>>()?;
+    let weight_sum: f64 = if likelihood_ratios {
+        path_hits.len() as f64
+    } else {
+        path_hits.iter().map(|(weight, _)| weight).sum()
+    };
+    let expected = values.iter().zip(path_hits).map(|(value, (weight, _))| weight * value).sum::<f64>() / weight_sum;
+    let spread: f64 = values
+        .iter()
+        .zip(path_hits)
+        .map(|(value, (weight, _))| if likelihood_ratios { weight * value - expected } else { weight * (value - expected) }.powi(2))
+        .sum();
+    
+    Ok(Some(PayoffEstimate { payoff, expected, stderr: spread.sqrt() / weight_sum }))
+}
+
+/// Replace a result's stderr with the spread of its antithetic pair averages (needs two pairs),
+/// checking the result is still finite
+fn apply_pair_stderr(result: &mut SimResult, pair_means: &[f64]) -> Result<(), String> {
+    if pair_means.len() < 2 {
+        return Ok(());
+    }
+    
+    let pairs = pair_means.len() as f64;
+    let mean = pair_means.iter().sum::<f64>() / pairs;
+    let variance = pair_means.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (pairs - 1.0);
+    let stderr = (variance / pairs).sqrt();
+    
+    result.diagnostics.stderr = stderr;
+    result.diagnostics.relative_stderr = Some(stderr / result.p);
+    ensure_finite(result)
+}
+
+/// Whether a simulated path settles the target YES (for linear and capped payoffs: ends in the money)
+fn target_hit(target: &Target, outcome: &PathOutcome) -> Result<bool, String> {
+    if target.payoff.is_some() {
+        validate_payoff(target)?;
+    }
+    let final_price = outcome.final_price;
+    
+    match target.kind.as_str() {
+        "above" => {
+            if let Some(k) = target.K {
+                Ok(final_price > k)
+            } else {
+                Err("Strike price K required for 'above' target".to_string())
+            }
+        },
+        "below" => {
+            if let Some(k) = target.K {
+                Ok(final_price < k)
+            } else {
+                Err("Strike price K required for 'below' target".to_string())
+            }
+        },
+        "range" => {
+            if let (Some(l), Some(u)) = (target.L, target.U) {
+                let above_l = if target.l_inclusive.unwrap_or(true) { final_price >= l } else { final_price > l };
+                let below_u = if target.u_inclusive.unwrap_or(true) { final_price <= u } else { final_price < u };
+                Ok(above_l && below_u)
+            } else {
+                Err("Range bounds L and U required for 'range' target".to_string())
+            }
+        },
+        "return_above" => {
+            if let Some(pct) = target.pct {
+                Ok(final_price / outcome.initial_price - 1.0 > pct)
+            } else {
+                Err("Return threshold pct required for 'return_above' target".to_string())
+            }
+        },
+        "realized_vol_above" => {
+            if let Some(vol) = target.vol {
+                Ok(outcome.realized_vol > vol)
+            } else {
+                Err("Volatility threshold vol required for 'realized_vol_above' target".to_string())
+            }
+        },
+        "stayed_above" => {
+            if let Some(floor) = target.K {
+                Ok(outcome.min_price >= floor)
+            } else {
+                Err("Floor level K required for 'stayed_above' target".to_string())
+            }
+        },
+        // Monitored at t = 0 and every step end, like stayed_above; with bridge_correction a path
+        // simulated for the target may also have touched between steps
+        "touch" => {
+            let k = target.K.ok_or("Barrier level K required for 'touch' target")?;
+            match target.direction.as_deref().unwrap_or("up") {
+                "up" => Ok(outcome.max_price >= k || matches!(outcome.first_touch, FirstTouch::Upper)),
+                "down" => Ok(outcome.min_price <= k || matches!(outcome.first_touch, FirstTouch::Lower)),
+                other => Err(format!("touch direction must be \"up\" or \"down\", got \"{}\"", other)),
+            }
+        },
+        "first_touch" => match (target.L, target.U) {
+            (Some(lower), Some(upper)) if lower < upper => match outcome.first_touch {
+                FirstTouch::Upper => Ok(true),
+                FirstTouch::Lower | FirstTouch::Neither => Ok(false),
+                FirstTouch::NotMonitored => Err("first_touch paths must be simulated with simulate_target_path".to_string()),
+            },
+            (Some(lower), Some(upper)) => Err(format!("first_touch needs L < U, got L = {} and U = {}", lower, upper)),
+            _ => Err("Levels L and U required for 'first_touch' target".to_string()),
+        },
+        _ => Err("Invalid target kind".to_string()),
+    }
+}
+
+/// Black-Scholes digital probability P(S_T > K) = N(d2), with `vol` and `r` annualized and `t` in hours
+///
+/// The closed form for a single regime with constant variance theta and no jumps: vol is
+/// sqrt(theta * HOURS_PER_YEAR) and r the regime mu (per hour) times HOURS_PER_YEAR.
+#[wasm_bindgen]
+pub fn bs_digital_above(s0: f64, k: f64, vol: f64, t: f64, r: f64) -> f64 {
+    let years = t / utils::HOURS_PER_YEAR;
+    let d2 = ((s0 / k).ln() + (r - 0.5 * vol * vol) * years) / (vol * years.sqrt());
+    
+    utils::normal_cdf(d2)
+}
+
+/// Flat annualized vol at which the driftless Black-Scholes digital above `k` equals `p`
+///
+/// In the money (K <= S0) the digital falls monotonically in vol. Out of the money it first rises,
+/// peaking where vol * sqrt(T) = sqrt(2 ln(K / S0)), then falls; the root is taken on the rising
+/// branch, and prices above the peak have no implied vol.
+fn implied_digital_vol(s0: f64, k: f64, t: f64, p: f64) -> Result<f64, String> {
+    if !(p > 0.0 && p < 1.0) {
+        return Err(format!("Market probability must be strictly between 0 and 1, got {}", p));
+    }
+    if !(s0 > 0.0 && k > 0.0 && t > 0.0) {
+        return Err("Implied vol needs a positive spot, strike and horizon".to_string());
+    }
+    
+    let price = |vol: f64| bs_digital_above(s0, k, vol, t, 0.0);
+    let sqrt_years = (t / utils::HOURS_PER_YEAR).sqrt();
+    let moneyness = (k / s0).ln();
+    
+    let (mut lo, mut hi) = (1e-8, 1.0);
+    if moneyness > 0.0 {
+        hi = (2.0 * moneyness).sqrt() / sqrt_years;
+        if p > price(hi) {
+            return Err(format!(
+                "No implied vol: {:.4} exceeds the largest digital price {:.4} reachable at this strike",
+                p,
+                price(hi)
+            ));
+        }
+    } else {
+        while price(hi) > p {
+            hi *= 2.0;
+            if hi > 1e6 {
+                return Err(format!("No implied vol below {} reproduces p = {}", hi, p));
+            }
+        }
+        if price(lo) < p {
+            return Err(format!("No implied vol: {:.4} exceeds the zero-vol digital price", p));
+        }
+    }
+    
+    // On [lo, hi] the price is monotone; bisect towards the root
+    let rising = moneyness > 0.0;
+    for _ in 0..200 {
+        let mid = 0.5 * (lo + hi);
+        if (price(mid) < p) == rising {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    
+    Ok(0.5 * (lo + hi))
+}
+
+/// Expected P&L of trading `n_contracts` YES contracts at `market_price_cents`, net of fees
+fn position_economics(
+    result: &SimResult,
+    side: &str,
+    market_price_cents: f64,
+    n_contracts: u32,
+) -> Result<ExpectedProfit, String> {
+    if !(market_price_cents > 0.0 && market_price_cents < 100.0) {
+        return Err("Market price must be between 0 and 100 cents".to_string());
+    }
+    
+    // Per-contract edge in cents as a function of the win probability
+    let edge = |p: f64| match side {
+        "buy" => Ok(p * 100.0 - market_price_cents),
+        "sell" => Ok(market_price_cents - p * 100.0),
+        _ => Err(format!("Invalid side '{}', expected 'buy' or 'sell'", side)),
+    };
+    
+    let n = n_contracts as f64;
+    let fee_cents = utils::kalshi_fee_cents(market_price_cents, n_contracts);
+    let expected_profit_cents = n * edge(result.p)? - fee_cents;
+    
+    let a = n * edge(result.ci[0])? - fee_cents;
+    let b = n * edge(result.ci[1])? - fee_cents;
+    
+    Ok(ExpectedProfit {
+        side: side.to_string(),
+        market_price_cents,
+        n_contracts,
+        p: result.p,
+        fee_cents,
+        expected_profit_cents,
+        ci: [a.min(b), a.max(b)],
+    })
+}
+
+/// What moved between two results, and whether the move is outside the noise
+fn diff_sim_results(a: &SimResult, b: &SimResult) -> ResultDiff {
+    let dp = b.p - a.p;
+    let combined_stderr = (a.diagnostics.stderr.powi(2) + b.diagnostics.stderr.powi(2)).sqrt();
+    let z = if combined_stderr > 0.0 { dp / combined_stderr } else { 0.0 };
+    let overlap = a.ci[0] <= b.ci[1] && b.ci[0] <= a.ci[1];
+    
+    ResultDiff {
+        dp,
+        dfair: b.fair - a.fair,
+        dci: [b.ci[0] - a.ci[0], b.ci[1] - a.ci[1]],
+        z,
+        overlap,
+        significant: !overlap,
+    }
+}
+
+/// Fractional hours from `now_iso` to `expiry_iso` (RFC 3339, any offsets), ready for `SimInputs.t`
+fn hours_between(expiry_iso: &str, now_iso: &str) -> Result<f64, String> {
+    let expiry = utils::parse_rfc3339(expiry_iso).map_err(|e| format!("expiry: {}", e))?;
+    let now = utils::parse_rfc3339(now_iso).map_err(|e| format!("now: {}", e))?;
+    
+    Ok((expiry - now) / 3600.0)
+}
+
+/// Hours until a contract expiry, negative once it has passed
+#[wasm_bindgen]
+pub fn hours_until(expiry_iso: &str, now_iso: &str) -> Result<f64, JsValue> {
+    hours_between(expiry_iso, now_iso).map_err(|e| JsValue::from_str(&e))
+}
+
+/// One-sided Wilson bound on p from a run's `hits` out of `n`, e.g. the 95% upper confidence
+/// limit for a deep out-of-the-money contract
+#[wasm_bindgen]
+pub fn wilson_bound(hits: u32, n: u32, confidence: f64, upper: bool) -> Result<f64, JsValue> {
+    if n == 0 || hits > n {
+        return Err(JsValue::from_str(&format!("Need 0 <= hits <= n with n > 0, got {} of {}", hits, n)));
+    }
+    if !(confidence > 0.0 && confidence < 1.0) {
+        return Err(JsValue::from_str(&format!("Confidence must be in (0, 1), got {}", confidence)));
+    }
+    
+    Ok(utils::wilson_ci_one_sided(hits, n, confidence, upper))
+}
+
+/// Compare two serialized `SimResult`s, e.g. before and after a parameter or engine change
+#[wasm_bindgen]
+pub fn diff_results(a_json: &str, b_json: &str) -> Result<String, JsValue> {
+    let parse = |json: &str| -> Result<SimResult, JsValue> {
+        serde_json::from_str(json).map_err(|e| JsValue::from_str(&format!("Failed to parse result: {}", e)))
+    };
+    
+    to_json(&diff_sim_results(&parse(a_json)?, &parse(b_json)?))
+}
+
+fn build_info() -> HealthCheck {
+    HealthCheck {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        target: if cfg!(target_arch = "wasm32") { "wasm32" } else { "native" }.to_string(),
+        panic_hook: cfg!(feature = "console_error_panic_hook"),
+        parallel: cfg!(not(target_arch = "wasm32")),
+        rng: "StdRng (ChaCha12)".to_string(),
+        variance_reduction: ["antithetic", "control_variate", "qmc", "importance"].iter().map(|name| name.to_string()).collect(),
+    }
+}
+
+/// Which capabilities this build was compiled with, for support questions
+#[wasm_bindgen]
+pub fn health_check() -> Result<String, JsValue> {
+    to_json(&build_info())
+}
+
+/// `inputs` with every regime (basket constituents and a scenario switch's included) and the
+/// jumps stressed by `level`
+fn stressed_inputs(inputs: &SimInputs, level: f64) -> Result<SimInputs, String> {
+    if !(level > -1.0 && level.is_finite()) {
+        return Err(format!("Stress level must be greater than -1, got {}", level));
+    }
+    
+    let basket = inputs.basket.as_ref().map(|basket| Basket {
+        assets: basket
+            .assets
+            .iter()
+            .map(|asset| BasketAsset {
+                regimes: models::stress_regimes(&asset.regimes, level),
+                jumps: models::stress_jumps(&asset.jumps, level),
+                ..asset.clone()
+            })
+            .collect(),
+        ..basket.clone()
+    });
+    
+    let scenario_switch = inputs.scenario_switch.as_ref().map(|switch| ScenarioSwitch {
+        inputs: ScenarioInputs {
+            regimes: models::stress_regimes(&switch.inputs.regimes, level),
+            jumps: models::stress_jumps(&switch.inputs.jumps, level),
+            ..switch.inputs.clone()
+        },
+        ..switch.clone()
+    });
+    
+    Ok(SimInputs {
+        regimes: models::stress_regimes(&inputs.regimes, level),
+        jumps: models::stress_jumps(&inputs.jumps, level),
+        basket,
+        scenario_switch,
+        ..inputs.clone()
+    })
+}
+
+/// Scenario helper: returns the inputs JSON with both regimes moved together by `stress_level`
+/// (0.2 = 20% more volatile, -0.2 = 20% calmer), ready to price with a new engine
+#[wasm_bindgen]
+pub fn stress_inputs(inputs_json: &str, stress_level: f64) -> Result<String, JsValue> {
+    let inputs: SimInputs = serde_json::from_str(inputs_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse inputs: {}", e)))?;
+    
+    let stressed = stressed_inputs(&inputs, stress_level).map_err(|e| JsValue::from_str(&e))?;
+    
+    to_json(&stressed)
+}
+
+/// Price every variant with its own seed derived from the base seed and the variant name,
+/// so the whole experiment is reproducible from one number and each variant independently
+fn run_variants(target: &Target, base_seed: u64, variants: Vec<ExperimentVariant>, n_paths: u32) -> Result<Vec<VariantRun>, String> {
+    let mut names = std::collections::HashSet::new();
+    if let Some(variant) = variants.iter().find(|variant| !names.insert(variant.name.as_str())) {
+        return Err(format!("Duplicate variant name '{}'", variant.name));
+    }
+    
+    variants
+        .into_iter()
+        .map(|variant| {
+            let seed = utils::variant_seed(base_seed, &variant.name) & JS_SAFE_SEED_MASK;
+            let result = MonteCarloEngine::from_inputs(variant.inputs, seed)
+                .and_then(|mut engine| engine.simulate(target.clone(), n_paths))
+                .map_err(|e| format!("Variant '{}': {}", variant.name, e))?;
+            
+            Ok(VariantRun { name: variant.name, seed, result })
+        })
+        .collect()
+}
+
+/// A/B experiment over named input variants, reproducible from `base_seed`
+#[wasm_bindgen]
+pub fn run_experiment(target_json: &str, base_seed: u64, variants_json: &str, n_paths: u32, request_id: Option<String>) -> Result<String, JsValue> {
+    let target = parse_target(target_json)?;
+    let variants: Vec<ExperimentVariant> = serde_json::from_str(variants_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse variants: {}", e)))?;
+    
+    let runs = run_variants(&target, base_seed, variants, n_paths).map_err(|e| JsValue::from_str(&e))?;
+    
+    to_json_tagged(&runs, request_id.as_deref())
+}
+
+#[wasm_bindgen]
+pub struct MonteCarloEngine {
+    model: Model,
+    seed: u64,
+    rng: rand_chacha::ChaCha12Rng,  // the generator behind StdRng, named so its position can be checkpointed
+    window: Option<RollingWindow>,
+    output: OutputOptions,
+    last_diagnostics: Option<SimDiagnostics>,
+}
+
+#[wasm_bindgen]
+impl MonteCarloEngine {
+    #[wasm_bindgen(constructor)]
+    pub fn new(inputs_json: &str) -> Result<MonteCarloEngine, JsValue> {
+        // Draw a seed so the run can be reproduced later via export_config
+        use rand::{Rng, SeedableRng};
+        let seed = rand::rngs::StdRng::from_entropy().gen::<u64>() & JS_SAFE_SEED_MASK;
+        
+        MonteCarloEngine::new_seeded(inputs_json, seed)
+    }
+    
+    /// Engine whose RNG stream is fixed by `seed`: the same inputs and seed reproduce every run exactly
+    #[wasm_bindgen]
+    pub fn new_seeded(inputs_json: &str, seed: u64) -> Result<MonteCarloEngine, JsValue> {
+        // Set panic hook for better error messages
+        utils::set_panic_hook();
+        
+        // Parse inputs
+        let sim_inputs: SimInputs = serde_json::from_str(inputs_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse inputs: {}", e)))?;
+        
+        MonteCarloEngine::from_inputs(sim_inputs, seed).map_err(|e| JsValue::from_str(&e))
+    }
+    
+    /// Rebuild an engine from an `export_config` blob: same inputs, same seed
+    #[wasm_bindgen]
+    pub fn import_config(config_json: &str) -> Result<MonteCarloEngine, JsValue> {
+        utils::set_panic_hook();
+        
+        let config: EngineConfig = serde_json::from_str(config_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse config: {}", e)))?;
+        
+        MonteCarloEngine::from_config(config).map_err(|e| JsValue::from_str(&e))
+    }
+    
+    /// Engine on `inputs_json` whose RNG resumes from an `export_rng_state` checkpoint, so the
+    /// next runs continue the original engine's stream exactly
+    #[wasm_bindgen]
+    pub fn from_rng_state(inputs_json: &str, state_json: &str) -> Result<MonteCarloEngine, JsValue> {
+        utils::set_panic_hook();
+        
+        let sim_inputs: SimInputs = serde_json::from_str(inputs_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse inputs: {}", e)))?;
+        let state: RngState = serde_json::from_str(state_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse RNG state: {}", e)))?;
+        
+        MonteCarloEngine::from_state(sim_inputs, &state).map_err(|e| JsValue::from_str(&e))
+    }
+    
+    /// Current position of the engine's RNG stream, e.g. between `run_batch` calls
+    #[wasm_bindgen]
+    pub fn export_rng_state(&self) -> Result<String, JsValue> {
+        to_json(&self.rng_state())
+    }
+    
+    /// Also return fair value, CI and price stats as integers scaled by 10^decimals (None to disable)
+    #[wasm_bindgen]
+    pub fn set_fixed_point(&mut self, decimals: Option<u32>) -> Result<(), JsValue> {
+        let mut output = self.output.clone();
+        output.fixed_point_decimals = decimals;
+        
+        self.set_output(output).map_err(|e| JsValue::from_str(&e))
+    }
+    
+    /// Also report a CI at each of these confidence levels (JSON array, e.g. [0.5, 0.9, 0.99])
+    #[wasm_bindgen]
+    pub fn set_ci_levels(&mut self, levels_json: &str) -> Result<(), JsValue> {
+        let levels: Vec<f64> = serde_json::from_str(levels_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse confidence levels: {}", e)))?;
+        
+        let mut output = self.output.clone();
+        output.ci_levels = levels;
+        
+        self.set_output(output).map_err(|e| JsValue::from_str(&e))
+    }
+    
+    /// Keep only the most recent `size` paths for `run_rolling` estimates
+    #[wasm_bindgen]
+    pub fn set_rolling_window(&mut self, size: u32) -> Result<(), JsValue> {
+        self.set_window(size).map_err(|e| JsValue::from_str(&e))
+    }
+    
+    /// Add `n_paths` fresh paths to the rolling window and return the estimate over the window
+    #[wasm_bindgen]
+    pub fn run_rolling(&mut self, target_json: &str, n_paths: u32, request_id: Option<String>) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let estimate = self.simulate_rolling(target, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json_tagged(&estimate, request_id.as_deref())
+    }
+    
+    /// Change parameters on the fly; old paths age out of the rolling window as new ones arrive
+    #[wasm_bindgen]
+    pub fn update_inputs(&mut self, inputs_json: &str) -> Result<(), JsValue> {
+        let sim_inputs: SimInputs = serde_json::from_str(inputs_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse inputs: {}", e)))?;
+        
+        self.replace_inputs(sim_inputs).map_err(|e| JsValue::from_str(&e))
+    }
+    
+    /// Inputs, seed, options, rolling-window state and the last run's diagnostics in one blob
+    #[wasm_bindgen]
+    pub fn debug_snapshot(&self) -> Result<String, JsValue> {
+        to_json(&self.snapshot())
+    }
+    
+    /// Self-contained JSON of the inputs and seed; the imported engine restarts the seed's stream
+    #[wasm_bindgen]
+    pub fn export_config(&self) -> Result<String, JsValue> {
+        to_json(&self.config())
+    }
+    
+    /// The `run_*` methods take an optional trailing `request_id`, echoed back in their results
+    #[wasm_bindgen]
+    pub fn run_simulation(&mut self, target_json: &str, n_paths: u32, request_id: Option<String>) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let result = self.simulate(target, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        // Return JSON result
+        to_json_tagged(&result, request_id.as_deref())
+    }
+    
+    /// `run_simulation` that also returns an `n_bins`-bin histogram of terminal prices in `price_histogram`
+    #[wasm_bindgen]
+    pub fn run_simulation_with_histogram(
+        &mut self,
+        target_json: &str,
+        n_paths: u32,
+        n_bins: u32,
+        request_id: Option<String>,
+    ) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let result = self.simulate_with_histogram(target, n_paths, n_bins).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json_tagged(&result, request_id.as_deref())
+    }
+    
+    /// `run_simulation` for "above"/"below" targets that also reports a control-variate estimate
+    /// (`control_variate`) against the closed-form GBM digital
+    #[wasm_bindgen]
+    pub fn run_simulation_control_variate(&mut self, target_json: &str, n_paths: u32, request_id: Option<String>) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let result = self.simulate_control_variate(target, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json_tagged(&result, request_id.as_deref())
+    }
+    
+    /// `run_simulation` with antithetic pairs: every path is followed by a twin with negated price shocks
+    #[wasm_bindgen]
+    pub fn run_simulation_antithetic(&mut self, target_json: &str, n_paths: u32, request_id: Option<String>) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let result = self.simulate_antithetic(target, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json_tagged(&result, request_id.as_deref())
+    }
+    
+    /// `run_simulation` by importance sampling: the price shocks are tilted so the terminal shock
+    /// has mean `tilt`, and each path is weighted back by its likelihood ratio
+    #[wasm_bindgen]
+    pub fn run_simulation_importance(&mut self, target_json: &str, n_paths: u32, tilt: f64, request_id: Option<String>) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let result = self.simulate_importance(target, n_paths, tilt).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json_tagged(&result, request_id.as_deref())
+    }
+    
+    /// `run_simulation` that stops after `budget_ms` of wall-clock time, returning the paths done so far
+    #[wasm_bindgen]
+    pub fn run_simulation_with_budget(
+        &mut self,
+        target_json: &str,
+        n_paths: u32,
+        budget_ms: f64,
+        request_id: Option<String>,
+    ) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let deadline = utils::now_ms() + budget_ms;
+        let result = self
+            .simulate_interruptible(target, n_paths, |_| Ok(utils::now_ms() >= deadline))
+            .map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json_tagged(&result, request_id.as_deref())
+    }
+    
+    /// `run_simulation` that stops once the 95% CI half-width is at most `ci_tolerance`, or after `max_paths`
+    #[wasm_bindgen]
+    pub fn run_simulation_until(
+        &mut self,
+        target_json: &str,
+        max_paths: u32,
+        ci_tolerance: f64,
+        request_id: Option<String>,
+    ) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let result = self.simulate_until(target, max_paths, ci_tolerance).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json_tagged(&result, request_id.as_deref())
+    }
+    
+    /// `run_simulation` that polls `is_cancelled()` every few hundred paths and stops once it returns true
+    #[wasm_bindgen]
+    pub fn run_simulation_cancellable(
+        &mut self,
+        target_json: &str,
+        n_paths: u32,
+        is_cancelled: &js_sys::Function,
+        request_id: Option<String>,
+    ) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let result = self
+            .simulate_interruptible(target, n_paths, |_| {
+                is_cancelled
+                    .call0(&JsValue::NULL)
+                    .map(|flag| flag.is_truthy())
+                    .map_err(|e| format!("Cancel check failed: {:?}", e))
+            })
+            .map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json_tagged(&result, request_id.as_deref())
+    }
+    
+    #[wasm_bindgen]
+    pub fn run_batch(&mut self, target_json: &str, n_paths: u32, batch_size: u32, request_id: Option<String>) -> Result<js_sys::Array, JsValue> {
+        let target = parse_target(target_json)?;
+        let request_id = request_id.as_deref();
+        
+        let results = js_sys::Array::new();
+        
+        let final_result = self.simulate_batches(target, n_paths, batch_size, |intermediate| {
+            let value = serde_json::to_value(intermediate)
+                .map_err(|e| format!("Failed to serialize: {}", e))?;
+            
+            results.push(&JsValue::from_str(&tag_request_id(value, request_id).to_string()));
+            Ok(())
+        }).map_err(|e| JsValue::from_str(&e))?;
+        
+        let final_json = to_json_tagged(&final_result, request_id)?;
+        
+        results.push(&JsValue::from_str(&final_json));
+        
+        Ok(results)
+    }
+    
+    /// `run_batch` that hands each batch's `IntermediateResult` JSON to `on_batch` as soon as it finishes
+    /// and returns only the final `SimResult`; an exception thrown by `on_batch` aborts the run and is rethrown
+    #[wasm_bindgen]
+    pub fn run_batch_streaming(
+        &mut self,
+        target_json: &str,
+        n_paths: u32,
+        batch_size: u32,
+        on_batch: &js_sys::Function,
+        request_id: Option<String>,
+    ) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        let request_id = request_id.as_deref();
+        
+        let mut thrown = None;
+        let final_result = self.simulate_batches(target, n_paths, batch_size, |intermediate| {
+            let value = serde_json::to_value(intermediate)
+                .map_err(|e| format!("Failed to serialize: {}", e))?;
+            
+            on_batch
+                .call1(&JsValue::NULL, &JsValue::from_str(&tag_request_id(value, request_id).to_string()))
+                .map(|_| ())
+                .map_err(|e| {
+                    thrown = Some(e);
+                    "Batch callback threw".to_string()
+                })
+        });
+        
+        match (final_result, thrown) {
+            (_, Some(e)) => Err(e),
+            (Ok(result), None) => to_json_tagged(&result, request_id),
+            (Err(e), None) => Err(JsValue::from_str(&e)),
+        }
+    }
+    
+    /// Expected profit of buying or selling `n_contracts` at the market price, net of Kalshi fees
+    #[wasm_bindgen]
+    pub fn expected_profit(
+        &mut self,
+        target_json: &str,
+        side: &str,
+        market_price_cents: f64,
+        n_contracts: u32,
+        n_paths: u32,
+    ) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let result = self.simulate(target, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        let profit = position_economics(&result, side, market_price_cents, n_contracts)
+            .map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json(&profit)
+    }
+    
+    /// Price "above K" for every strike from a single set of paths, optionally smoothed:
+    /// `smoothing` is "logistic" or "isotonic", and leaving it out skips the fit. The shared
+    /// paths already make the raw curve monotone, so "isotonic" returns it unchanged.
+    /// With `covariance`, also the joint covariance matrix of the per-strike estimates
+    #[wasm_bindgen]
+    pub fn run_strike_ladder(
+        &mut self,
+        strikes_json: &str,
+        n_paths: u32,
+        smoothing: Option<String>,
+        covariance: bool,
+        request_id: Option<String>,
+    ) -> Result<String, JsValue> {
+        let strikes: Vec<f64> = serde_json::from_str(strikes_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse strikes: {}", e)))?;
+        
+        let ladder = self.strike_ladder(&strikes, n_paths, smoothing.as_deref(), covariance).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json_tagged(&ladder, request_id.as_deref())
+    }
+    
+    /// One `SimResult` per target (JSON array), every target scored on the same paths
+    #[wasm_bindgen]
+    pub fn run_multi_target(&mut self, targets_json: &str, n_paths: u32, request_id: Option<String>) -> Result<String, JsValue> {
+        let targets: Vec<Target> = serde_json::from_str(targets_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse targets: {}", e)))?;
+        
+        let results = self.simulate_multi_target(targets, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json_tagged(&results, request_id.as_deref())
+    }
+    
+    /// `run_multi_target` with antithetic pairs, for a lower stderr on every target at the same path count
+    #[wasm_bindgen]
+    pub fn run_multi_target_antithetic(&mut self, targets_json: &str, n_paths: u32, request_id: Option<String>) -> Result<String, JsValue> {
+        let targets: Vec<Target> = serde_json::from_str(targets_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse targets: {}", e)))?;
+        
+        let results = self.simulate_multi_target_antithetic(targets, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json_tagged(&results, request_id.as_deref())
+    }
+    
+    /// P(target | realized vol over the path exceeds `min_vol`), plus how often the condition holds
+    #[wasm_bindgen]
+    pub fn run_conditional_on_vol(&mut self, target_json: &str, min_vol: f64, n_paths: u32, request_id: Option<String>) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let result = self.conditional_on_vol(target, min_vol, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json_tagged(&result, request_id.as_deref())
+    }
+    
+    /// Long "above lower", short "above upper", priced on one set of paths
+    #[wasm_bindgen]
+    pub fn price_spread(&mut self, lower: f64, upper: f64, n_paths: u32) -> Result<String, JsValue> {
+        let spread = self.digital_spread(lower, upper, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json(&spread)
+    }
+    
+    /// Expected fraction of the horizon an "above K" target spends in the money, for accrual products
+    #[wasm_bindgen]
+    pub fn expected_time_in_the_money(&mut self, target_json: &str, n_paths: u32) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let time = self.time_in_the_money(target, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json(&time)
+    }
+    
+    /// Which of the first_touch target's levels each path reaches first, e.g. "72k or 68k first?"
+    #[wasm_bindgen]
+    pub fn first_touch_race(&mut self, target_json: &str, n_paths: u32) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let race = self.simulate_first_touch(target, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json(&race)
+    }
+    
+    /// Full (s, v, regime, jump) trajectory of one path of an earlier `run_simulation`
+    ///
+    /// `seed` is that run's `diagnostics.replay_seed` and `path_index` the path's position in it.
+    #[wasm_bindgen]
+    pub fn replay_path(&self, seed: u64, path_index: u32) -> Result<String, JsValue> {
+        let steps = self.replay(seed, path_index).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json(&steps)
+    }
+    
+    /// Deterministic path with no diffusion noise and no jumps, to see the pure drift trajectory
+    #[wasm_bindgen]
+    pub fn simulate_expected_path(&self) -> Result<String, JsValue> {
+        let path = self.expected_path().map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json(&path)
+    }
+    
+    /// Sample quantile function of the terminal price at each probability in `probs_json`
+    #[wasm_bindgen]
+    pub fn terminal_quantiles(&mut self, probs_json: &str, n_paths: u32) -> Result<String, JsValue> {
+        let probs: Vec<f64> = serde_json::from_str(probs_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse probabilities: {}", e)))?;
+        
+        let quantiles = self.terminal_quantile_function(&probs, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json(&quantiles)
+    }
+    
+    /// Value-at-risk and expected shortfall of the terminal log-return at tail probability `level`,
+    /// over `n_paths` freshly drawn paths. For the figures of a particular run, set
+    /// `OutputOptions.risk_level` and read `SimResult.risk` instead
+    #[wasm_bindgen]
+    pub fn risk_metrics(&mut self, n_paths: u32, level: f64) -> Result<String, JsValue> {
+        let metrics = self.terminal_risk(n_paths, level).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json(&metrics)
+    }
+    
+    /// Strike at which the "above K" fair value equals `target_cents` (e.g. the 50c line)
+    #[wasm_bindgen]
+    pub fn strike_for_fair(&mut self, target_cents: f64, n_paths: u32) -> Result<String, JsValue> {
+        let crossover = self.crossover_strike(target_cents, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json(&crossover)
+    }
+    
+    /// Flat volatility at which a Black-Scholes digital reproduces the market price of the target
+    #[wasm_bindgen]
+    pub fn implied_vol_from_price(&self, target_json: &str, market_price_cents: f64) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let implied = self.implied_vol(target, market_price_cents).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json(&implied)
+    }
+    
+    /// Factor on every regime's theta that makes the model's p for an above/below target match
+    /// `market_prob` (e.g. 0.43 for a 43 cent quote)
+    #[wasm_bindgen]
+    pub fn implied_vol_scale(&mut self, target_json: &str, market_prob: f64, n_paths: u32) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let fit = self.fit_vol_scale(target, market_prob, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json(&fit)
+    }
+    
+    /// Finite-difference sensitivity of the fair value to the jump intensity, `bump` in jumps per hour
+    #[wasm_bindgen]
+    pub fn jump_intensity_sensitivity(&mut self, target_json: &str, n_paths: u32, bump: f64) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let sensitivity = self.jump_sensitivity(target, n_paths, bump).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json(&sensitivity)
+    }
+    
+    /// Delta (s0 bumped by `ds`) and vega (long-run vol bumped by `dvol`) of the fair value, by central
+    /// differences over common random numbers
+    #[wasm_bindgen]
+    pub fn compute_greeks(&mut self, target_json: &str, n_paths: u32, ds: f64, dvol: f64) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let greeks = self.greeks(target, n_paths, ds, dvol).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json(&greeks)
+    }
+    
+    /// Pilot run to pick a variance-reduction strategy, then the full run with it
+    #[wasm_bindgen]
+    pub fn smart_run(&mut self, target_json: &str, n_paths: u32) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let result = self.smart_simulate(target, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json(&result)
+    }
+    
+    /// Re-run the target at each `dt` to check the discretization has converged
+    #[wasm_bindgen]
+    pub fn dt_convergence_study(&mut self, target_json: &str, dt_values_json: &str, n_paths: u32) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        let dt_values: Vec<f64> = serde_json::from_str(dt_values_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse dt values: {}", e)))?;
+        
+        let study = self.dt_study(target, &dt_values, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json(&study)
+    }
+    
+    /// Fair value of the target at each remaining time to expiry, spot and vol held fixed
+    #[wasm_bindgen]
+    pub fn theta_decay_curve(&mut self, target_json: &str, time_points_json: &str, n_paths: u32) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        let time_points: Vec<f64> = serde_json::from_str(time_points_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse time points: {}", e)))?;
+        
+        let curve = self.theta_decay(target, &time_points, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json(&curve)
+    }
+    
+    /// Binomial stderr at 100, 300, 1k, 3k, 10k, ... paths and at `n_paths`, as [n, stderr] pairs,
+    /// to check the 1/sqrt(N) decay and pick a path budget; saturated checkpoints report the same
+    /// rule-of-three stderr a run's diagnostics do
+    #[wasm_bindgen]
+    pub fn stderr_curve(&mut self, target_json: &str, n_paths: u32) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let curve = self.stderr_by_path_count(target, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json(&curve)
+    }
+    
+    /// `run_simulation` under the real-world (regime mu) and risk-neutral (r) drifts, on the same paths
+    #[wasm_bindgen]
+    pub fn run_simulation_dual(&mut self, target_json: &str, n_paths: u32, request_id: Option<String>) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let result = self.simulate_dual(target, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json_tagged(&result, request_id.as_deref())
+    }
+    
+    /// A few full trajectories that make the probability concrete: the lowest, median and highest
+    /// terminal hitters (for "above K": barely made it through blew past) and the median misser
+    #[wasm_bindgen]
+    pub fn representative_paths(&mut self, target_json: &str, n_paths: u32) -> Result<String, JsValue> {
+        let target = parse_target(target_json)?;
+        
+        let paths = self.pick_representative_paths(&target, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json(&paths)
+    }
+    
+    /// Human-readable warnings for degenerate or suspicious parameter combinations (empty if none)
+    #[wasm_bindgen]
+    pub fn diagnose_model(&self) -> Result<String, JsValue> {
+        to_json(&degenerate_warnings(&self.model.sim_inputs))
+    }
+    
+    /// Stationary distribution of the HMM transition matrix (regime order), computed once per model
+    #[wasm_bindgen]
+    pub fn stationary_distribution(&self) -> Result<String, JsValue> {
+        let pi = self.model.stationary_distribution()
+            .ok_or_else(|| JsValue::from_str("Transition matrix has no unique stationary distribution"))?;
+        
+        to_json(&pi)
+    }
+    
+    /// Share of paths in each regime over the horizon, for a stacked regime chart
+    #[wasm_bindgen]
+    pub fn regime_occupancy(&mut self, n_paths: u32) -> Result<String, JsValue> {
+        let occupancy = self.simulate_regime_occupancy(n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json(&occupancy)
+    }
+    
+    /// Hourly OHLC candles of one path drawn from `seed`, for an example-scenario chart
+    ///
+    /// Uses its own RNG, so the same seed always draws the same path and the engine's stream is untouched.
+    #[wasm_bindgen]
+    pub fn hourly_ohlc(&self, seed: u64) -> Result<String, JsValue> {
+        to_json(&self.seeded_candles(seed))
+    }
+}
+
+impl MonteCarloEngine {
+    fn from_inputs(sim_inputs: SimInputs, seed: u64) -> Result<MonteCarloEngine, String> {
+        use rand::SeedableRng;
+        let model = Model::new(sim_inputs)?;
+        let rng = rand_chacha::ChaCha12Rng::seed_from_u64(seed);
+        
+        Ok(MonteCarloEngine {
+            model,
+            seed,
+            rng,
+            window: None,
+            output: OutputOptions::default(),
+            last_diagnostics: None,
+        })
+    }
+    
+    fn from_state(sim_inputs: SimInputs, state: &RngState) -> Result<MonteCarloEngine, String> {
+        let word_pos: u128 = state.word_pos.parse()
+            .map_err(|e| format!("Invalid RNG word position \"{}\": {}", state.word_pos, e))?;
+        
+        let mut engine = MonteCarloEngine::from_inputs(sim_inputs, state.seed)?;
+        engine.rng.set_word_pos(word_pos);
+        Ok(engine)
+    }
+    
+    fn rng_state(&self) -> RngState {
+        RngState { seed: self.seed, word_pos: self.rng.get_word_pos().to_string() }
+    }
+    
+    fn from_config(config: EngineConfig) -> Result<MonteCarloEngine, String> {
+        if config.version != CONFIG_VERSION {
+            return Err(format!("Unsupported config version {}, expected {}", config.version, CONFIG_VERSION));
+        }
+        
+        let mut engine = MonteCarloEngine::from_inputs(config.inputs, config.seed)?;
+        if let Some(size) = config.rolling_window {
+            engine.set_window(size)?;
+        }
+        engine.set_output(config.output)?;
+        
+        Ok(engine)
+    }
+    
+    fn config(&self) -> EngineConfig {
+        EngineConfig {
+            version: CONFIG_VERSION,
+            seed: self.seed,
+            inputs: self.model.sim_inputs.clone(),
+            rolling_window: self.window.as_ref().map(|window| window.capacity as u32),
+            output: self.output.clone(),
+        }
+    }
+    
+    /// Summarize a run and keep its diagnostics for `debug_snapshot`
+    fn finish_run(&mut self, acc: RunAccumulator, target: Target) -> Result<SimResult, String> {
+        let result = acc.finish(target, &self.model, &self.output)?;
+        self.last_diagnostics = Some(result.diagnostics.clone());
+        
+        Ok(result)
+    }
+    
+    fn snapshot(&self) -> DebugSnapshot {
+        DebugSnapshot {
+            build: build_info(),
+            config: self.config(),
+            rolling_window: self.window.as_ref().map(|window| RollingWindowState {
+                target: window.target.clone(),
+                estimate: window.estimate(),
+            }),
+            last_diagnostics: self.last_diagnostics.clone(),
+        }
+    }
+    
+    fn set_output(&mut self, output: OutputOptions) -> Result<(), String> {
+        if let Some(decimals) = output.fixed_point_decimals {
+            if decimals > MAX_FIXED_POINT_DECIMALS {
+                return Err(format!("fixed_point_decimals must be at most {}, got {}", MAX_FIXED_POINT_DECIMALS, decimals));
+            }
+        }
+        if let Some(level) = output.ci_levels.iter().find(|level| !(**level > 0.0 && **level < 1.0)) {
+            return Err(format!("Confidence levels must be in (0, 1), got {}", level));
+        }
+        if output.histogram_bins == Some(0) {
+            return Err("histogram_bins must be positive".to_string());
+        }
+        if let Some(level) = output.risk_level.filter(|level| !(*level > 0.0 && *level < 1.0)) {
+            return Err(format!("Risk level must be in (0, 1), got {}", level));
+        }
+        
+        self.output = output;
+        Ok(())
+    }
+    
+    fn set_window(&mut self, size: u32) -> Result<(), String> {
+        if size == 0 {
+            return Err("Rolling window size must be positive".to_string());
+        }
+        
+        self.window = Some(RollingWindow::new(size as usize));
+        Ok(())
+    }
+    
+    /// Swap in new inputs, keeping the RNG stream and any rolling window
+    fn replace_inputs(&mut self, sim_inputs: SimInputs) -> Result<(), String> {
+        self.model = Model::new(sim_inputs)?;
+        Ok(())
+    }
+    
+    /// Simulate `n_paths` more paths into the rolling window and report the estimate over it
+    ///
+    /// Paths from a different target cannot be mixed, so switching targets restarts the window.
+    fn simulate_rolling(&mut self, target: Target, n_paths: u32) -> Result<RollingEstimate, String> {
+        // Window paths come straight off the engine stream, with no path index to place a Sobol point
+        if self.model.sim_inputs.qmc {
+            return Err("qmc is not supported with a rolling window".to_string());
+        }
+        let window = self.window.as_mut().ok_or("Rolling window is not enabled")?;
+        
+        if window.target.as_ref() != Some(&target) {
+            window.clear();
+            window.target = Some(target.clone());
+        }
+        
+        for i in 0..n_paths {
+            let outcome = self.model.simulate_target_path(&mut self.rng, None, &target);
+            if !outcome.final_price.is_finite() {
+                return Err(format!("Path {} produced a non-finite final price ({})", i, outcome.final_price));
+            }
+            window.push(target_hit(&target, &outcome)?);
+        }
+        
+        Ok(window.estimate())
+    }
+    
+    fn simulate(&mut self, target: Target, n_paths: u32) -> Result<SimResult, String> {
+        self.simulate_interruptible(target, n_paths, |_| Ok(false))
+    }
+    
+    /// `simulate` with a terminal price histogram, leaving the engine's output options untouched
+    fn simulate_with_histogram(&mut self, target: Target, n_paths: u32, n_bins: u32) -> Result<SimResult, String> {
+        let output = self.output.clone();
+        self.set_output(OutputOptions { histogram_bins: Some(n_bins), ..output.clone() })?;
+        let result = self.simulate(target, n_paths);
+        self.output = output;
+        
+        result
+    }
+    
+    /// `simulate`, asking `should_stop(paths_done)` every `STOP_CHECK_INTERVAL` paths whether to
+    /// abort; an aborted run still returns the estimate over the completed paths, flagged `incomplete`
+    fn simulate_interruptible(
+        &mut self,
+        target: Target,
+        n_paths: u32,
+        mut should_stop: impl FnMut(u32) -> Result<bool, String>,
+    ) -> Result<SimResult, String> {
+        if n_paths == 0 {
+            return Err("n_paths must be positive".to_string());
+        }
+        
+        log!("Starting simulation with {} paths for target: {:?}", n_paths, target.kind);
+        
+        let mut acc = RunAccumulator::new(n_paths as usize);
+        let progress_step = (n_paths / 10).max(1);
+        let mut incomplete = false;
+        
+        let replay_seed = self.replay_seed();
+        
+        // Run simulations
+        for i in 0..n_paths {
+            // At least one path always runs, so there is an estimate to return
+            if i > 0 && i % STOP_CHECK_INTERVAL == 0 && should_stop(i)? {
+                log!("Stopped early after {} of {} paths", i, n_paths);
+                incomplete = true;
+                break;
+            }
+            
+            let mut trace = if acc.wants_sample() {
+                Some(Vec::new())
+            } else {
+                None
+            };
+            
+            let outcome = run_path(&self.model, replay_seed, i, trace.as_mut(), Some(&target));
+            let hit = target_hit(&target, &outcome)?;
+            
+            acc.record(i, &outcome, trace, hit)
+                .map_err(|e| format!("{} (replay with seed {})", e, replay_seed))?;
+            
+            // Log progress every 10%
+            if i > 0 && i % progress_step == 0 {
+                log!("Progress: {}%", (i * 100) / n_paths);
+            }
+        }
+        
+        let mut result = self.finish_run(acc, target)?;
+        result.diagnostics.replay_seed = Some(replay_seed);
+        Ok(SimResult { incomplete, ..result })
+    }
+    
+    /// `simulate`, pairing each path's indicator with the same event for a driftless GBM at the blended
+    /// long-run vol, whose terminal shock is the path's own z1 shocks, each weighted by
+    /// sqrt(dt / horizon) so the sum is N(0, 1) on any step grid
+    fn simulate_control_variate(&mut self, target: Target, n_paths: u32) -> Result<SimResult, String> {
+        if n_paths < 2 {
+            return Err("Control variates need at least 2 paths".to_string());
+        }
+        if self.model.sim_inputs.basket.is_some() {
+            return Err("Control variates are not supported in basket mode".to_string());
+        }
+        // The analytic control mean assumes a normal terminal shock
+        if self.model.sim_inputs.innovation == "student_t" {
+            return Err("Control variates need normal innovations".to_string());
+        }
+        let k = match (target.kind.as_str(), target.K) {
+            ("above" | "below", Some(k)) => k,
+            _ => return Err("Control variates need an 'above' or 'below' target with K".to_string()),
+        };
+        
+        let inputs = &self.model.sim_inputs;
+        let horizon = self.model.horizon();
+        let vol = annualized_vol(inputs, self.model.pi0()).blended;
+        let sigma_sqrt_t = vol * (horizon / utils::HOURS_PER_YEAR).sqrt();
+        let s0 = inputs.s0;
+        let above = target.kind == "above";
+        
+        let p_above = bs_digital_above(s0, k, vol, horizon, 0.0);
+        let control_mean = if above { p_above } else { 1.0 - p_above };
+        
+        let mut acc = RunAccumulator::new(n_paths as usize);
+        let replay_seed = self.replay_seed();
+        let mut pairs = Vec::with_capacity(n_paths as usize);
+        
+        for i in 0..n_paths {
+            let mut trace = if acc.wants_sample() {
+                Some(Vec::new())
+            } else {
+                None
+            };
+            
+            let outcome = run_path(&self.model, replay_seed, i, trace.as_mut(), Some(&target));
+            let hit = target_hit(&target, &outcome)?;
+            
+            let gbm_price = s0 * (-0.5 * sigma_sqrt_t * sigma_sqrt_t + sigma_sqrt_t * outcome.terminal_shock).exp();
+            let control = if above { gbm_price > k } else { gbm_price < k };
+            pairs.push((hit as u8 as f64, control as u8 as f64));
+            
+            acc.record(i, &outcome, trace, hit)
+                .map_err(|e| format!("{} (replay with seed {})", e, replay_seed))?;
+        }
+        
+        let n = pairs.len() as f64;
+        let mean_y = pairs.iter().map(|(y, _)| y).sum::<f64>() / n;
+        let mean_c = pairs.iter().map(|(_, c)| c).sum::<f64>() / n;
+        let cov = pairs.iter().map(|(y, c)| (y - mean_y) * (c - mean_c)).sum::<f64>() / (n - 1.0);
+        let var_y = pairs.iter().map(|(y, _)| (y - mean_y).powi(2)).sum::<f64>() / (n - 1.0);
+        let var_c = pairs.iter().map(|(_, c)| (c - mean_c).powi(2)).sum::<f64>() / (n - 1.0);
+        
+        // A constant control carries no information
+        let beta = if var_c > 0.0 { cov / var_c } else { 0.0 };
+        let var_adjusted = (var_y - 2.0 * beta * cov + beta * beta * var_c).max(0.0);
+        
+        let estimate = ControlVariateEstimate {
+            p_raw: mean_y,
+            stderr_raw: (var_y / n).sqrt(),
+            p: (mean_y - beta * (mean_c - control_mean)).clamp(0.0, 1.0),
+            stderr: (var_adjusted / n).sqrt(),
+            beta,
+            control_mean,
+        };
+        
+        let mut result = self.finish_run(acc, target)?;
+        result.diagnostics.replay_seed = Some(replay_seed);
+        let result = SimResult { control_variate: Some(estimate), ..result };
+        ensure_finite(&result)?;
+        Ok(result)
+    }
+    
+    /// `simulate` for several targets at once: each path is generated once and scored against every
+    /// target, so nested targets (a ladder of "above" strikes) get nested hit sets. Targets watching
+    /// levels see the same path through their own monitor (see `watched_outcome`), so every target
+    /// gets the answer its single-target run would.
+    fn simulate_multi_target(&mut self, targets: Vec<Target>, n_paths: u32) -> Result<Vec<SimResult>, String> {
+        if n_paths == 0 {
+            return Err("n_paths must be positive".to_string());
+        }
+        if targets.is_empty() {
+            return Err("At least one target is required".to_string());
+        }
+        
+        let mut accs: Vec<RunAccumulator> = targets.iter().map(|_| RunAccumulator::new(n_paths as usize)).collect();
+        let mut shocks = ShockBuffer::new();
+        let replay_seed = self.replay_seed();
+        
+        for i in 0..n_paths {
+            let mut trace = if accs[0].wants_sample() {
+                Some(Vec::new())
+            } else {
+                None
+            };
+            
+            let shared = run_recorded_path(&self.model, replay_seed, i, &mut shocks, trace.as_mut(), None);
+            for (acc, target) in accs.iter_mut().zip(&targets) {
+                let watched = watched_outcome(&self.model, replay_seed, i, &shocks, false, target);
+                let outcome = watched.as_ref().unwrap_or(&shared);
+                let hit = target_hit(target, outcome)?;
+                acc.record(i, outcome, trace.clone(), hit)
+                    .map_err(|e| format!("{} (replay with seed {})", e, replay_seed))?;
+            }
+        }
+        
+        accs.into_iter()
+            .zip(targets)
+            .map(|(acc, target)| {
+                let mut result = self.finish_run(acc, target)?;
+                result.diagnostics.replay_seed = Some(replay_seed);
+                Ok(result)
+            })
+            .collect()
+    }
+    
+    /// `simulate`, checking the CI every `CI_CHECK_INTERVAL` paths and stopping once its half-width is
+    /// within `ci_tolerance`; `diagnostics.n` reports the paths actually used
+    fn simulate_until(&mut self, target: Target, max_paths: u32, ci_tolerance: f64) -> Result<SimResult, String> {
+        if max_paths == 0 {
+            return Err("max_paths must be positive".to_string());
+        }
+        if ci_tolerance.is_nan() || ci_tolerance <= 0.0 {
+            return Err(format!("ci_tolerance must be positive, got {}", ci_tolerance));
+        }
+        
+        let mut acc = RunAccumulator::new(max_paths.min(CI_CHECK_INTERVAL * 10) as usize);
+        let replay_seed = self.replay_seed();
+        
+        for i in 0..max_paths {
+            if i > 0 && i % CI_CHECK_INTERVAL == 0 {
+                let [lo, hi] = acc.ci();
+                if (hi - lo) / 2.0 <= ci_tolerance {
+                    log!("CI within {} after {} of {} paths", ci_tolerance, i, max_paths);
+                    break;
+                }
+            }
+            
+            let mut trace = if acc.wants_sample() {
+                Some(Vec::new())
+            } else {
+                None
+            };
+            
+            let outcome = run_path(&self.model, replay_seed, i, trace.as_mut(), Some(&target));
+            let hit = target_hit(&target, &outcome)?;
+            
+            acc.record(i, &outcome, trace, hit)
+                .map_err(|e| format!("{} (replay with seed {})", e, replay_seed))?;
+        }
+        
+        let mut result = self.finish_run(acc, target)?;
+        result.diagnostics.replay_seed = Some(replay_seed);
+        Ok(result)
+    }
+    
+    /// Every step of path `path_index` from a run whose diagnostics reported `replay_seed`
+    ///
+    /// Paths depend only on (seed, index), so this reproduces the run's path exactly as long as
+    /// the engine's inputs are unchanged.
+    fn replay(&self, replay_seed: u64, path_index: u32) -> Result<Vec<PathStep>, String> {
+        let mut steps = Vec::new();
+        let price_shocks = self.model.qmc_price_shocks(replay_seed, path_index);
+        self.model.simulate_path_steps(&mut path_rng(replay_seed, path_index), &mut steps, price_shocks.as_deref())?;
+        Ok(steps)
+    }
+    
+    fn expected_path(&self) -> Result<ExpectedPath, String> {
+        let points = self.model.expected_path()?;
+        let final_price = points[points.len() - 1].price;
+        
+        Ok(ExpectedPath { final_price, points })
+    }
+    
+    /// Base seed for a run's per-path RNGs (see `path_rng`)
+    fn replay_seed(&mut self) -> u64 {
+        use rand::Rng;
+        
+        self.rng.gen::<u64>() & JS_SAFE_SEED_MASK
+    }
+    
+    /// `simulate` with antithetic pairs: each path is followed by its mirrored twin
+    ///
+    /// The twin replays the first path's recorded draws and negates only the diffusive price
+    /// shocks, so regimes, variance and jumps are common to both. The stderr is taken from the spread of
+    /// the pair averages; the CI stays the (conservative) Wilson interval over all paths.
+    fn simulate_antithetic(&mut self, target: Target, n_paths: u32) -> Result<SimResult, String> {
+        if n_paths == 0 {
+            return Err("n_paths must be positive".to_string());
+        }
+        
+        let mut acc = RunAccumulator::new(n_paths as usize);
+        let mut pair_means = Vec::with_capacity(n_paths.div_ceil(2) as usize);
+        let mut shocks = ShockBuffer::new();
+        let replay_seed = self.replay_seed();
+        
+        // Path i draws from its own seed; its twin replays those draws and is reported as path i + 1
+        let mut i = 0;
+        while i < n_paths {
+            let seed_index = i;
+            let mut trace = if acc.wants_sample() { Some(Vec::new()) } else { None };
+            let outcome = run_recorded_path(&self.model, replay_seed, seed_index, &mut shocks, trace.as_mut(), Some(&target));
+            let hit = target_hit(&target, &outcome)?;
+            acc.record(i, &outcome, trace, hit)?;
+            i += 1;
+            
+            let mut pair = f64::from(u8::from(hit));
+            // An odd final path stays unpaired
+            if i < n_paths {
+                let mut trace = if acc.wants_sample() { Some(Vec::new()) } else { None };
+                let twin = replay_path(&self.model, replay_seed, seed_index, &shocks, true, trace.as_mut(), Some(&target));
+                let twin_hit = target_hit(&target, &twin)?;
+                acc.record(i, &twin, trace, twin_hit)?;
+                i += 1;
+                
+                pair = (pair + f64::from(u8::from(twin_hit))) / 2.0;
+            }
+            pair_means.push(pair);
+        }
+        
+        let saturated = acc.saturated();
+        let mut result = acc.finish(target, &self.model, &self.output)?;
+        
+        if !saturated {
+            apply_pair_stderr(&mut result, &pair_means)?;
+        }
+        result.diagnostics.replay_seed = Some(replay_seed);
+        
+        self.last_diagnostics = Some(result.diagnostics.clone());
+        Ok(result)
+    }
+    
+    /// `simulate_multi_target` with antithetic pairs: every path is followed by its mirrored twin,
+    /// and each target's stderr comes from the spread of its pair averages
+    fn simulate_multi_target_antithetic(&mut self, targets: Vec<Target>, n_paths: u32) -> Result<Vec<SimResult>, String> {
+        if n_paths == 0 {
+            return Err("n_paths must be positive".to_string());
+        }
+        if targets.is_empty() {
+            return Err("At least one target is required".to_string());
+        }
+        
+        let mut accs: Vec<RunAccumulator> = targets.iter().map(|_| RunAccumulator::new(n_paths as usize)).collect();
+        let mut pair_means: Vec<Vec<f64>> = targets.iter().map(|_| Vec::with_capacity(n_paths.div_ceil(2) as usize)).collect();
+        let mut shocks = ShockBuffer::new();
+        let replay_seed = self.replay_seed();
+        
+        let mut i = 0;
+        while i < n_paths {
+            let seed_index = i;
+            let mut trace = if accs[0].wants_sample() { Some(Vec::new()) } else { None };
+            let shared = run_recorded_path(&self.model, replay_seed, seed_index, &mut shocks, trace.as_mut(), None);
+            let mut hits = Vec::with_capacity(targets.len());
+            for (acc, target) in accs.iter_mut().zip(&targets) {
+                let watched = watched_outcome(&self.model, replay_seed, seed_index, &shocks, false, target);
+                let outcome = watched.as_ref().unwrap_or(&shared);
+                let hit = target_hit(target, outcome)?;
+                acc.record(i, outcome, trace.clone(), hit)?;
+                hits.push(f64::from(u8::from(hit)));
+            }
+            i += 1;
+            
+            // An odd final path stays unpaired
+            if i < n_paths {
+                let mut trace = if accs[0].wants_sample() { Some(Vec::new()) } else { None };
+                let shared_twin = replay_path(&self.model, replay_seed, seed_index, &shocks, true, trace.as_mut(), None);
+                for ((acc, target), pair) in accs.iter_mut().zip(&targets).zip(hits.iter_mut()) {
+                    let watched = watched_outcome(&self.model, replay_seed, seed_index, &shocks, true, target);
+                    let twin = watched.as_ref().unwrap_or(&shared_twin);
+                    let hit = target_hit(target, twin)?;
+                    acc.record(i, twin, trace.clone(), hit)?;
+                    *pair = (*pair + f64::from(u8::from(hit))) / 2.0;
+                }
+                i += 1;
+            }
+            for (means, pair) in pair_means.iter_mut().zip(hits) {
+                means.push(pair);
+            }
+        }
+        
+        accs.into_iter()
+            .zip(targets)
+            .zip(pair_means)
+            .map(|((acc, target), means)| {
+                let saturated = acc.saturated();
+                let mut result = self.finish_run(acc, target)?;
+                if !saturated {
+                    apply_pair_stderr(&mut result, &means)?;
+                }
+                self.last_diagnostics = Some(result.diagnostics.clone());
+                Ok(result)
+            })
+            .collect()
+    }
+    
+    /// Drift-shifted importance sampling: paths come from `Model::with_price_tilt` and p is the mean
+    /// over paths of the hits weighted by exp(-tilt * terminal_shock + tilt^2 / 2), their likelihood
+    /// ratio. Price-distribution diagnostics describe the tilted paths
+    fn simulate_importance(&mut self, target: Target, n_paths: u32, tilt: f64) -> Result<SimResult, String> {
+        if n_paths == 0 {
+            return Err("n_paths must be positive".to_string());
+        }
+        if !tilt.is_finite() {
+            return Err(format!("tilt must be finite, got {}", tilt));
+        }
+        if self.model.sim_inputs.basket.is_some() {
+            return Err("importance sampling is not supported in basket mode".to_string());
+        }
+        if self.model.sim_inputs.innovation == "student_t" {
+            return Err("importance sampling needs normal innovations".to_string());
+        }
+        
+        let model = Model::new(self.model.sim_inputs.clone())?.with_price_tilt(tilt);
+        let mut acc = RunAccumulator::with_likelihood_ratios(n_paths as usize);
+        let replay_seed = self.replay_seed();
+        
+        for i in 0..n_paths {
+            let mut trace = if acc.wants_sample() { Some(Vec::new()) } else { None };
+            let outcome = run_path(&model, replay_seed, i, trace.as_mut(), Some(&target));
+            let hit = target_hit(&target, &outcome)?;
+            let weight = (-tilt * outcome.terminal_shock + 0.5 * tilt * tilt).exp();
+            acc.record_weighted(i, &outcome, trace, hit, weight)
+                .map_err(|e| format!("{} (replay with seed {})", e, replay_seed))?;
+        }
+        
+        let mut result = self.finish_run(acc, target)?;
+        result.diagnostics.replay_seed = Some(replay_seed);
+        Ok(result)
+    }
+    
+    fn smart_simulate(&mut self, target: Target, n_paths: u32) -> Result<SmartRunResult, String> {
+        if n_paths == 0 {
+            return Err("n_paths must be positive".to_string());
+        }
+        
+        let pilot_n = (n_paths / 10).clamp(100, 5000);
+        let pilot = self.simulate(target.clone(), pilot_n)?;
+        let pilot_p = pilot.p;
+        
+        // Antithetic pairing only helps when the payoff is monotone in the terminal price
+        let monotone = matches!(target.kind.as_str(), "above" | "below" | "return_above");
+        let tail = !(0.05..=0.95).contains(&pilot_p);
+        let inputs = &self.model.sim_inputs;
+        let tiltable = inputs.basket.is_none() && inputs.innovation != "student_t";
+        
+        // Tilt toward the rare side until it is about as likely as not: the rarer outcome's pilot
+        // share (half a path when none turned up) gives the shift, a monotone payoff its sign
+        let rare = pilot_p.min(1.0 - pilot_p).max(0.5 / pilot_n as f64);
+        let toward_hits = if target.kind == "below" { -1.0 } else { 1.0 };
+        let tilt = toward_hits * utils::normal_quantile(1.0 - rare).copysign(0.5 - pilot_p);
+        
+        let (strategy, reason) = if tail && monotone && tiltable {
+            (
+                "importance",
+                format!("pilot p = {:.4} is in the tail, so price shocks are tilted by {:.2} toward the rare outcome", pilot_p, tilt),
+            )
+        } else if tail {
+            (
+                "plain",
+                format!(
+                    "pilot p = {:.4} is in the tail, but importance sampling needs a monotone payoff with normal, single-asset shocks",
+                    pilot_p
+                ),
+            )
+        } else if monotone {
+            (
+                "antithetic",
+                format!("pilot p = {:.4} is near the money and the '{}' payoff is monotone", pilot_p, target.kind),
+            )
+        } else {
+            (
+                "plain",
+                format!("the '{}' payoff is not monotone in the terminal price, so antithetic pairs would not help", target.kind),
+            )
+        };
+        
+        let result = match strategy {
+            "antithetic" => self.simulate_antithetic(target, n_paths)?,
+            "importance" => self.simulate_importance(target, n_paths, tilt)?,
+            _ => self.simulate(target, n_paths)?,
+        };
+        
+        Ok(SmartRunResult {
+            strategy: strategy.to_string(),
+            reason,
+            pilot_p,
+            pilot_n,
+            result,
+        })
+    }
+    
+    /// Native-only multi-threaded `simulate`
+    ///
+    /// Workers share the immutable `Model` by reference and every path draws from its own seed
+    /// (see `path_rng`), so no state is shared mutably across threads and the paths do not depend
+    /// on how they are split between workers.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn run_simulation_parallel(&mut self, target: Target, n_paths: u32, n_threads: usize) -> Result<SimResult, String> {
+        if n_paths == 0 {
+            return Err("n_paths must be positive".to_string());
+        }
+        
+        let n_threads = n_threads.clamp(1, n_paths as usize) as u32;
+        let replay_seed = self.replay_seed();
+        let per_worker = n_paths / n_threads;
+        let remainder = n_paths % n_threads;
+        
+        let model = &self.model;
+        let target_ref = &target;
+        
+        let accumulators = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..n_threads)
+                .map(|worker| {
+                    let start = worker * per_worker + worker.min(remainder);
+                    let count = per_worker + u32::from(worker < remainder);
+                    
+                    scope.spawn(move || -> Result<RunAccumulator, String> {
+                        let mut acc = RunAccumulator::new(count as usize);
+                        
+                        for i in start..start + count {
+                            let mut trace = if acc.wants_sample() {
+                                Some(Vec::new())
+                            } else {
+                                None
+                            };
+                            
+                            let outcome = run_path(model, replay_seed, i, trace.as_mut(), Some(target_ref));
+                            let hit = target_hit(target_ref, &outcome)?;
+                            acc.record(i, &outcome, trace, hit)?;
+                        }
+                        
+                        Ok(acc)
+                    })
+                })
+                .collect();
+            
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("simulation worker panicked"))
+                .collect::<Result<Vec<_>, String>>()
+        })?;
+        
+        let mut merged = RunAccumulator::new(n_paths as usize);
+        for acc in accumulators {
+            merged.merge(acc);
+        }
+        
+        let mut result = self.finish_run(merged, target)?;
+        result.diagnostics.replay_seed = Some(replay_seed);
+        Ok(result)
+    }
+    
+    /// Every dt starts from the same seed, so the estimates share random numbers as far as
+    /// the differing step counts allow and their differences are mostly discretization
+    fn dt_study(&mut self, target: Target, dt_values: &[f64], n_paths: u32) -> Result<DtStudy, String> {
+        use rand::Rng;
+        
+        if dt_values.is_empty() {
+            return Err("dt study needs at least one dt value".to_string());
+        }
+        if let Some(dt) = dt_values.iter().find(|dt| !(**dt > 0.0 && dt.is_finite())) {
+            return Err(format!("dt values must be positive, got {}", dt));
+        }
+        
+        let seed = self.rng.gen::<u64>() & JS_SAFE_SEED_MASK;
+        let mut rows = Vec::with_capacity(dt_values.len());
+        
+        for &dt in dt_values {
+            // A schedule would override the dt under study
+            let inputs = SimInputs { dt, dt_schedule: Vec::new(), ..self.model.sim_inputs.clone() };
+            let result = MonteCarloEngine::from_inputs(inputs, seed)?.simulate(target.clone(), n_paths)?;
+            
+            rows.push(DtStudyRow {
+                dt,
+                p: result.p,
+                ci: result.ci,
+                stderr: result.diagnostics.stderr,
+            });
+        }
+        
+        Ok(DtStudy { rows, n: n_paths, seed })
+    }
+    
+    /// Like `dt_study` over the horizon: every time point starts from the same seed, so the
+    /// shorter horizons reuse the leading random numbers and the curve is smooth in `t`
+    fn theta_decay(&mut self, target: Target, time_points: &[f64], n_paths: u32) -> Result<ThetaDecayCurve, String> {
+        use rand::Rng;
+        
+        if time_points.is_empty() {
+            return Err("Theta decay curve needs at least one time point".to_string());
+        }
+        if let Some(t) = time_points.iter().find(|t| !(**t > 0.0 && t.is_finite())) {
+            return Err(format!("Time points must be positive, got {}", t));
+        }
+        
+        let seed = self.rng.gen::<u64>() & JS_SAFE_SEED_MASK;
+        let mut points = Vec::with_capacity(time_points.len());
+        
+        for &t in time_points {
+            let inputs = SimInputs { t, ..self.model.sim_inputs.clone() };
+            let result = MonteCarloEngine::from_inputs(inputs, seed)?.simulate(target.clone(), n_paths)?;
+            
+            points.push(ThetaDecayPoint {
+                t,
+                fair: result.fair,
+                p: result.p,
+                ci: result.ci,
+            });
+        }
+        
+        Ok(ThetaDecayCurve { points, n: n_paths, seed })
+    }
+    
+    /// One pass over the paths, reading the running hit count off at each checkpoint
+    fn stderr_by_path_count(&mut self, target: Target, n_paths: u32) -> Result<Vec<(u32, f64)>, String> {
+        if n_paths == 0 {
+            return Err("n_paths must be positive".to_string());
+        }
+        
+        let mut checkpoints: Vec<u32> = (2..10)
+            .flat_map(|power| [10u32.pow(power), 3 * 10u32.pow(power)])
+            .filter(|&n| n < n_paths)
+            .collect();
+        checkpoints.push(n_paths);
+        
+        let replay_seed = self.replay_seed();
+        let mut curve = Vec::with_capacity(checkpoints.len());
+        let mut next = checkpoints.iter().peekable();
+        let mut hits = 0u32;
+        for i in 0..n_paths {
+            hits += u32::from(target_hit(&target, &run_path(&self.model, replay_seed, i, None, Some(&target)))?);
+            
+            if next.peek() == Some(&&(i + 1)) {
+                let n = i + 1;
+                curve.push((n, utils::hit_stderr(hits, n)));
+                next.next();
+            }
+        }
+        
+        Ok(curve)
+    }
+    
+    /// Both measures start from one seed, and the drift is the only input they differ in, so every
+    /// path makes the same draws (see `Model::measure_drift`) and the gap between the two is the drift's
+    fn simulate_dual(&mut self, target: Target, n_paths: u32) -> Result<DualMeasureResult, String> {
+        use rand::Rng;
+        
+        let seed = self.rng.gen::<u64>() & JS_SAFE_SEED_MASK;
+        let run = |pricing_measure: &str| -> Result<SimResult, String> {
+            let inputs = SimInputs { pricing_measure: pricing_measure.to_string(), ..self.model.sim_inputs.clone() };
+            let mut engine = MonteCarloEngine::from_inputs(inputs, seed)?;
+            engine.set_output(self.output.clone())?;
+            engine.simulate(target.clone(), n_paths)
+        };
+        
+        Ok(DualMeasureResult {
+            real_world: run("real_world")?,
+            risk_neutral: run("risk_neutral")?,
+            seed,
+        })
+    }
+    
+    /// Only terminal prices are kept during the run; each path has its own seed, so the chosen
+    /// ones are regenerated with a trace afterwards instead of storing every trajectory
+    fn pick_representative_paths(&mut self, target: &Target, n_paths: u32) -> Result<Vec<RepresentativePath>, String> {
+        if n_paths == 0 {
+            return Err("n_paths must be positive".to_string());
+        }
+        
+        let replay_seed = self.replay_seed();
+        
+        let mut hits = Vec::new();
+        let mut misses = Vec::new();
+        for id in 0..n_paths {
+            let outcome = run_path(&self.model, replay_seed, id, None, Some(target));
+            if target_hit(target, &outcome)? {
+                hits.push((outcome.final_price, id));
+            } else {
+                misses.push((outcome.final_price, id));
+            }
+        }
+        hits.sort_by(|a, b| a.0.total_cmp(&b.0));
+        misses.sort_by(|a, b| a.0.total_cmp(&b.0));
+        
+        let mut picks = Vec::new();
+        if !hits.is_empty() {
+            picks.push(("lowest_hit", true, hits[0]));
+            picks.push(("median_hit", true, hits[hits.len() / 2]));
+            picks.push(("highest_hit", true, hits[hits.len() - 1]));
+        }
+        if !misses.is_empty() {
+            picks.push(("median_miss", false, misses[misses.len() / 2]));
+        }
+        
+        Ok(picks
+            .into_iter()
+            .map(|(label, hit, (final_price, id))| {
+                let mut points = Vec::new();
+                run_path(&self.model, replay_seed, id, Some(&mut points), Some(target));
+                
+                RepresentativePath {
+                    label: label.to_string(),
+                    id,
+                    hit,
+                    final_price,
+                    points,
+                }
+            })
+            .collect())
+    }
+    
+    /// Central difference in lambda with common random numbers
+    ///
+    /// Every path gets its own seed and is replayed under each intensity, so a differing jump
+    /// count only desynchronizes that one path rather than the rest of the stream. Near
+    /// lambda = 0 the down leg is dropped for a forward difference.
+    fn jump_sensitivity(&mut self, target: Target, n_paths: u32, bump: f64) -> Result<JumpSensitivity, String> {
+        if n_paths < 2 {
+            return Err("Jump sensitivity needs at least 2 paths".to_string());
+        }
+        if !(bump > 0.0 && bump.is_finite()) {
+            return Err(format!("Jump intensity bump must be positive, got {}", bump));
+        }
+        
+        let lambda = self.model.sim_inputs.jumps.lambda;
+        let lambda_down = if lambda >= bump { lambda - bump } else { lambda };
+        let lambda_up = lambda + bump;
+        let bumped = |lambda: f64| {
+            let mut inputs = self.model.sim_inputs.clone();
+            inputs.jumps.lambda = lambda;
+            Model::new(inputs)
+        };
+        let (down, up) = (bumped(lambda_down)?, bumped(lambda_up)?);
+        
+        let replay_seed = self.replay_seed();
+        let hit = |model: &Model, id: u32| -> Result<f64, String> {
+            Ok(f64::from(u8::from(target_hit(&target, &run_path(model, replay_seed, id, None, Some(&target)))?)))
+        };
+        
+        let mut base_hits = 0.0;
+        let mut diffs = Vec::with_capacity(n_paths as usize);
+        for id in 0..n_paths {
+            base_hits += hit(&self.model, id)?;
+            diffs.push((hit(&up, id)? - hit(&down, id)?) * 100.0 / (lambda_up - lambda_down));
+        }
+        
+        let n = n_paths as f64;
+        let mean = diffs.iter().sum::<f64>() / n;
+        let variance = diffs.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        
+        Ok(JumpSensitivity {
+            target,
+            lambda,
+            lambda_down,
+            lambda_up,
+            fair: base_hits * 100.0 / n,
+            dfair_dlambda: mean,
+            stderr: (variance / n).sqrt(),
+            n: n_paths,
+        })
+    }
+    
+    /// Central differences in s0 and in every regime's long-run vol, each path replayed from its own
+    /// seed under all four bumped models so the differences are not swamped by sampling noise
+    fn greeks(&mut self, target: Target, n_paths: u32, ds: f64, dvol: f64) -> Result<Greeks, String> {
+        if n_paths < 2 {
+            return Err("Greeks need at least 2 paths".to_string());
+        }
+        if self.model.sim_inputs.basket.is_some() {
+            return Err("Greeks are not supported in basket mode".to_string());
+        }
+        let s0 = self.model.sim_inputs.s0;
+        if !(ds > 0.0 && ds < s0) {
+            return Err(format!("Spot bump must be in (0, s0), got {}", ds));
+        }
+        let min_vol = annualized_vol(&self.model.sim_inputs, self.model.pi0()).by_regime.into_iter().fold(f64::INFINITY, f64::min);
+        if !(dvol > 0.0 && dvol < min_vol) {
+            return Err(format!("Vol bump must be in (0, {}), the lowest regime vol, got {}", min_vol, dvol));
+        }
+        
+        let with_spot = |s0: f64| Model::new(SimInputs { s0, ..self.model.sim_inputs.clone() });
+        let with_vol = |bump: f64| {
+            let mut inputs = self.model.sim_inputs.clone();
+            // The bump is in total regime vol, so every variance factor scales by the same ratio;
+            // the variance floor scales with theta, so a floored (constant-vol) regime still moves
+            for params in inputs.regimes.iter_mut() {
+                let variance = long_run_variance(params);
+                let vol = (variance * utils::HOURS_PER_YEAR).sqrt() + bump;
+                let scale = vol * vol / utils::HOURS_PER_YEAR / variance;
+                for heston in std::iter::once(&mut params.heston).chain(params.heston2.as_mut()) {
+                    heston.theta *= scale;
+                    heston.var_floor *= scale;
+                }
+            }
+            Model::new(inputs)
+        };
+        let (spot_down, spot_up) = (with_spot(s0 - ds)?, with_spot(s0 + ds)?);
+        let (vol_down, vol_up) = (with_vol(-dvol)?, with_vol(dvol)?);
+        
+        let replay_seed = self.replay_seed();
+        let hit = |model: &Model, id: u32| -> Result<f64, String> {
+            Ok(f64::from(u8::from(target_hit(&target, &run_path(model, replay_seed, id, None, Some(&target)))?)))
+        };
+        
+        let mut base_hits = 0.0;
+        let mut delta_diffs = Vec::with_capacity(n_paths as usize);
+        let mut vega_diffs = Vec::with_capacity(n_paths as usize);
+        for id in 0..n_paths {
+            base_hits += hit(&self.model, id)?;
+            delta_diffs.push((hit(&spot_up, id)? - hit(&spot_down, id)?) * 100.0 / (2.0 * ds));
+            vega_diffs.push((hit(&vol_up, id)? - hit(&vol_down, id)?) * 100.0 / (2.0 * dvol));
+        }
+        
+        let n = n_paths as f64;
+        let mean_and_stderr = |diffs: &[f64]| {
+            let mean = diffs.iter().sum::<f64>() / n;
+            let variance = diffs.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / (n - 1.0);
+            (mean, (variance / n).sqrt())
+        };
+        let (delta, delta_stderr) = mean_and_stderr(&delta_diffs);
+        let (vega, vega_stderr) = mean_and_stderr(&vega_diffs);
+        
+        Ok(Greeks {
+            target,
+            fair: base_hits * 100.0 / n,
+            ds,
+            dvol,
+            delta,
+            delta_stderr,
+            vega,
+            vega_stderr,
+            n: n_paths,
+        })
+    }
+    
+    /// Bracket and bisect the factor, every step scored on the same per-path seeds so p moves
+    /// smoothly with it; stops once p is within half a path of the market
+    fn fit_vol_scale(&mut self, target: Target, market_prob: f64, n_paths: u32) -> Result<VolScaleFit, String> {
+        if !(market_prob > 0.0 && market_prob < 1.0) {
+            return Err(format!("Market probability must be strictly between 0 and 1, got {}", market_prob));
+        }
+        if !matches!(target.kind.as_str(), "above" | "below") {
+            return Err(format!("Vol scale fitting needs an 'above' or 'below' target, got '{}'", target.kind));
+        }
+        if n_paths == 0 {
+            return Err("n_paths must be positive".to_string());
+        }
+        if self.model.sim_inputs.basket.is_some() {
+            return Err("Vol scale fitting is not supported in basket mode".to_string());
+        }
+        
+        let replay_seed = self.replay_seed();
+        let p_at = |factor: f64| -> Result<f64, String> {
+            let mut inputs = self.model.sim_inputs.clone();
+            // A scenario switch's regimes scale too, so the whole path moves with the factor
+            let switched = inputs.scenario_switch.as_mut().map(|switch| &mut switch.inputs.regimes);
+            for params in inputs.regimes.iter_mut().chain(switched.into_iter().flat_map(|regimes| regimes.iter_mut())) {
+                for heston in std::iter::once(&mut params.heston).chain(params.heston2.as_mut()) {
+                    heston.theta *= factor;
+                    heston.var_floor *= factor;
+                }
+            }
+            let model = Model::new(inputs)?;
+            let mut hits = 0u32;
+            for i in 0..n_paths {
+                hits += u32::from(target_hit(&target, &run_path(&model, replay_seed, i, None, Some(&target)))?);
+            }
+            Ok(hits as f64 / n_paths as f64)
+        };
+        
+        let tolerance = 0.5 / n_paths as f64;
+        let crosses = |a: f64, b: f64| (market_prob - a) * (market_prob - b) <= 0.0;
+        let (mut factor, mut p) = (1.0, p_at(1.0)?);
+        let mut iterations = 0;
+        
+        // p need not be monotone over all scales (the -v/2 drift eventually drags an above
+        // target back down), so double outwards from 1 to the nearest bracket of the market
+        let (mut down, mut up) = (1.0, 1.0);
+        let (mut p_down, mut p_up) = (p, p);
+        let bracket = loop {
+            if (p - market_prob).abs() <= tolerance {
+                break None;
+            }
+            if up >= MAX_VOL_SCALE {
+                return Err(format!(
+                    "No theta scale in [1/{}, {}] reaches p = {} (p = {} at scale 1)",
+                    MAX_VOL_SCALE, MAX_VOL_SCALE, market_prob, p
+                ));
+            }
+            let (q_down, q_up) = (p_at(down / 2.0)?, p_at(up * 2.0)?);
+            iterations += 1;
+            if crosses(p_up, q_up) {
+                break Some((up, p_up, up * 2.0));
+            }
+            if crosses(p_down, q_down) {
+                break Some((down / 2.0, q_down, down));
+            }
+            (down, up, p_down, p_up) = (down / 2.0, up * 2.0, q_down, q_up);
+        };
+        
+        // Bisect on log(factor), keeping the market probability between p(lo) and p(hi)
+        if let Some((mut lo, mut p_lo, mut hi)) = bracket {
+            while (p - market_prob).abs() > tolerance && hi / lo > 1.0 + 1e-6 {
+                factor = (lo * hi).sqrt();
+                p = p_at(factor)?;
+                iterations += 1;
+                if crosses(p, p_lo) {
+                    hi = factor;
+                } else {
+                    (lo, p_lo) = (factor, p);
+                }
+            }
+        }
+        
+        Ok(VolScaleFit {
+            target,
+            market_prob,
+            factor,
+            p,
+            stderr: utils::binomial_stderr(p, n_paths),
+            iterations,
+            n: n_paths,
+        })
+    }
+    
+    fn simulate_regime_occupancy(&mut self, n_paths: u32) -> Result<RegimeOccupancy, String> {
+        if n_paths == 0 {
+            return Err("n_paths must be positive".to_string());
+        }
+        
+        let n_regimes = self.model.sim_inputs.regimes.len();
+        let mut counts: Vec<Vec<u32>> = Vec::new();
+        for _ in 0..n_paths {
+            let regimes = self.model.simulate_regimes(&mut self.rng);
+            counts.resize(regimes.len(), vec![0; n_regimes]);
+            for (row, &regime) in counts.iter_mut().zip(&regimes) {
+                row[regime] += 1;
+            }
+        }
+        
+        let mut times = vec![0.0];
+        for dt in self.model.step_sizes() {
+            times.push(times[times.len() - 1] + dt);
+        }
+        let occupancy = counts
+            .iter()
+            .map(|row| row.iter().map(|&count| count as f64 / n_paths as f64).collect())
+            .collect();
+        
+        Ok(RegimeOccupancy {
+            times,
+            occupancy,
+            stationary: self.model.stationary_distribution(),
+            n: n_paths,
+        })
+    }
+    
+    fn seeded_candles(&self, seed: u64) -> Vec<Candle> {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let mut points = Vec::new();
+        self.model.simulate_path(&mut rng, Some(&mut points));
+        
+        hourly_candles(&points)
+    }
+    
+    fn simulate_terminal_prices(&mut self, n_paths: u32) -> Result<Vec<f64>, String> {
+        if n_paths == 0 {
+            return Err("n_paths must be positive".to_string());
+        }
+        
+        let replay_seed = self.replay_seed();
+        (0..n_paths)
+            .map(|i| {
+                checked_final_price(i, &run_path(&self.model, replay_seed, i, None, None))
+                    .map_err(|e| format!("{} (replay with seed {})", e, replay_seed))
+            })
+            .collect()
+    }
+    
+    /// With `covariance`, also Cov(p_i, p_j) = (p_ij - p_i p_j) / n, where p_ij is the share of
+    /// paths hitting both strikes: for "above" that is the share above the higher one
+    fn strike_ladder(&mut self, strikes: &[f64], n_paths: u32, smoothing: Option<&str>, covariance: bool) -> Result<StrikeLadderResult, String> {
+        if strikes.is_empty() {
+            return Err("Strike ladder must contain at least one strike".to_string());
+        }
+        if strikes.iter().any(|k| !(*k > 0.0 && k.is_finite())) {
+            return Err("Strikes must be positive".to_string());
+        }
+        if let Some(other) = smoothing.filter(|s| !matches!(*s, "isotonic" | "logistic")) {
+            return Err(format!("Unknown smoothing: {} (expected isotonic or logistic)", other));
+        }
+        
+        let prices = self.simulate_terminal_prices(n_paths)?;
+        
+        let hits: Vec<u32> = strikes
+            .iter()
+            .map(|&k| prices.iter().filter(|&&price| price > k).count() as u32)
+            .collect();
+        let p: Vec<f64> = hits.iter().map(|&h| h as f64 / n_paths as f64).collect();
+        let ci = hits.iter().map(|&h| utils::binomial_ci(h, n_paths, 0.95)).collect();
+        
+        let smoothed = match smoothing {
+            Some("logistic") => {
+                let log_strikes: Vec<f64> = strikes.iter().map(|k| k.ln()).collect();
+                Some(match utils::logistic_fit(&log_strikes, &p, n_paths) {
+                    Some((a, b)) => log_strikes.iter().map(|x| utils::logistic(a + b * x)).collect(),
+                    // Too few (or coincident) strikes to fit: keep the raw curve
+                    None => p.clone(),
+                })
+            }
+            Some(_) => {
+                // The shared paths never break monotonicity, so this returns p as is.
+                // Fit in ascending strike order, every strike weighted by its path count
+                let mut order: Vec<usize> = (0..strikes.len()).collect();
+                order.sort_by(|&a, &b| strikes[a].total_cmp(&strikes[b]));
+                let sorted_p: Vec<f64> = order.iter().map(|&i| p[i]).collect();
+                let fit = utils::isotonic_decreasing(&sorted_p, &vec![n_paths as f64; strikes.len()]);
+                
+                let mut smoothed = vec![0.0; strikes.len()];
+                for (&i, &value) in order.iter().zip(&fit) {
+                    smoothed[i] = value;
+                }
+                Some(smoothed)
+            }
+            None => None,
+        };
+        
+        let covariance = covariance.then(|| {
+            let n = n_paths as f64;
+            (0..strikes.len())
+                .map(|i| {
+                    (0..strikes.len())
+                        .map(|j| {
+                            let both = if strikes[i] >= strikes[j] { p[i] } else { p[j] };
+                            (both - p[i] * p[j]) / n
+                        })
+                        .collect()
+                })
+                .collect()
+        });
+        
+        Ok(StrikeLadderResult {
+            strikes: strikes.to_vec(),
+            p,
+            ci,
+            smoothed,
+            covariance,
+            n: n_paths,
+        })
+    }
+    
+    fn conditional_on_vol(&mut self, target: Target, min_vol: f64, n_paths: u32) -> Result<ConditionalResult, String> {
+        if n_paths == 0 {
+            return Err("n_paths must be positive".to_string());
+        }
+        if !(min_vol >= 0.0 && min_vol.is_finite()) {
+            return Err(format!("min_vol must be non-negative, got {}", min_vol));
+        }
+        
+        let replay_seed = self.replay_seed();
+        let mut n_conditioned = 0u32;
+        let mut hits = 0u32;
+        for i in 0..n_paths {
+            let outcome = run_path(&self.model, replay_seed, i, None, Some(&target));
+            // Evaluate every path so a bad target errors even when nothing qualifies
+            let hit = target_hit(&target, &outcome)?;
+            if outcome.realized_vol > min_vol {
+                n_conditioned += 1;
+                if hit {
+                    hits += 1;
+                }
+            }
+        }
+        
+        let p = if n_conditioned > 0 { hits as f64 / n_conditioned as f64 } else { 0.0 };
+        
+        Ok(ConditionalResult {
+            target,
+            min_vol,
+            p,
+            ci: utils::binomial_ci(hits, n_conditioned, 0.95),
+            condition_rate: n_conditioned as f64 / n_paths as f64,
+            condition_ci: utils::binomial_ci(n_conditioned, n_paths, 0.95),
+            n_conditioned,
+            n: n_paths,
+        })
+    }
+    
+    /// On shared paths the per-path difference of the two indicators is itself a 0/1 variable,
+    /// so the spread is binomial and its variance already nets out the legs' covariance
+    fn digital_spread(&mut self, lower: f64, upper: f64, n_paths: u32) -> Result<SpreadResult, String> {
+        if !(lower < upper && lower.is_finite() && upper.is_finite()) {
+            return Err("Spread needs finite strikes with lower < upper".to_string());
+        }
+        
+        let prices = self.simulate_terminal_prices(n_paths)?;
+        
+        let count_above = |k: f64| prices.iter().filter(|&&price| price > k).count() as u32;
+        let hits_lower = count_above(lower);
+        let hits_upper = count_above(upper);
+        let hits = hits_lower - hits_upper;
+        
+        let n = n_paths as f64;
+        let p_lower = hits_lower as f64 / n;
+        let p_upper = hits_upper as f64 / n;
+        let p = hits as f64 / n;
+        
+        Ok(SpreadResult {
+            lower,
+            upper,
+            p_lower,
+            p_upper,
+            p,
+            ci: utils::binomial_ci(hits, n_paths, 0.95),
+            stderr: utils::binomial_stderr(p, n_paths),
+            independent_stderr: (utils::binomial_stderr(p_lower, n_paths).powi(2)
+                + utils::binomial_stderr(p_upper, n_paths).powi(2))
+            .sqrt(),
+            n: n_paths,
+        })
+    }
+    
+    fn implied_vol(&self, target: Target, market_price_cents: f64) -> Result<ImpliedVol, String> {
+        let inputs = &self.model.sim_inputs;
+        let s0 = match &inputs.basket {
+            Some(basket) => basket.assets.iter().map(|asset| asset.weight * asset.s0).sum(),
+            None => inputs.s0,
+        };
+        let strike = match target.kind.as_str() {
+            "above" => target.K.ok_or("Strike price K required for 'above' target")?,
+            "return_above" => s0 * (1.0 + target.pct.ok_or("Return threshold pct required for 'return_above' target")?),
+            kind => return Err(format!("Implied vol needs a digital 'above' style target, got '{}'", kind)),
+        };
+        
+        let implied_vol = implied_digital_vol(s0, strike, inputs.t, market_price_cents / 100.0)?;
+        let model_vol = annualized_vol(inputs, self.model.pi0()).blended;
+        
+        Ok(ImpliedVol {
+            target,
+            market_price_cents,
+            implied_vol,
+            model_vol,
+            vol_ratio: implied_vol / model_vol,
+        })
+    }
+    
+    /// Levels are checked at t = 0 and at every step end, so a touch between steps is missed
+    fn simulate_first_touch(&mut self, target: Target, n_paths: u32) -> Result<FirstTouchRace, String> {
+        if target.kind != "first_touch" {
+            return Err(format!("First touch race needs a 'first_touch' target, got '{}'", target.kind));
+        }
+        if n_paths == 0 {
+            return Err("n_paths must be positive".to_string());
+        }
+        
+        let replay_seed = self.replay_seed();
+        let (mut upper, mut lower) = (0u32, 0u32);
+        for i in 0..n_paths {
+            let outcome = run_path(&self.model, replay_seed, i, None, Some(&target));
+            // Validates the levels on the first path
+            target_hit(&target, &outcome)?;
+            match outcome.first_touch {
+                FirstTouch::Upper => upper += 1,
+                FirstTouch::Lower => lower += 1,
+                FirstTouch::Neither | FirstTouch::NotMonitored => {},
+            }
+        }
+        
+        let neither = n_paths - upper - lower;
+        let share = |count: u32| count as f64 / n_paths as f64;
+        let ci = |count: u32| utils::binomial_ci(count, n_paths, 0.95);
+        
+        Ok(FirstTouchRace {
+            target,
+            p_upper: share(upper),
+            p_lower: share(lower),
+            p_neither: share(neither),
+            ci_upper: ci(upper),
+            ci_lower: ci(lower),
+            ci_neither: ci(neither),
+            n: n_paths,
+        })
+    }
+    
+    /// Each path is traced into one reused buffer and scored by the share of its time above K, each
+    /// step counting for its length from the end price; steps may differ in size under a `dt_schedule`
+    fn time_in_the_money(&mut self, target: Target, n_paths: u32) -> Result<TimeInTheMoney, String> {
+        if target.kind != "above" {
+            return Err(format!("Time in the money needs an 'above' target, got '{}'", target.kind));
+        }
+        let strike = target.K.ok_or("Strike price K required for 'above' target")?;
+        if n_paths < 2 {
+            return Err("Time in the money needs at least 2 paths".to_string());
+        }
+        
+        let replay_seed = self.replay_seed();
+        let mut points = Vec::new();
+        let mut fractions = Vec::with_capacity(n_paths as usize);
+        for i in 0..n_paths {
+            points.clear();
+            let outcome = run_path(&self.model, replay_seed, i, Some(&mut points), None);
+            if !outcome.final_price.is_finite() {
+                return Err(format!("Path {} produced a non-finite final price ({})", i, outcome.final_price));
+            }
+            
+            // points[0] is t = 0; every later point ends a step
+            let horizon = points.last().map_or(0.0, |point| point.t);
+            let above: f64 = points
+                .windows(2)
+                .filter(|step| step[1].price > strike)
+                .map(|step| step[1].t - step[0].t)
+                .sum();
+            fractions.push(if horizon > 0.0 { above / horizon } else { 0.0 });
+        }
+        
+        let n = n_paths as f64;
+        let fraction = fractions.iter().sum::<f64>() / n;
+        let variance = fractions.iter().map(|f| (f - fraction).powi(2)).sum::<f64>() / (n - 1.0);
+        let horizon = points.last().map_or(0.0, |point| point.t);
+        
+        Ok(TimeInTheMoney {
+            target,
+            fraction,
+            stderr: (variance / n).sqrt(),
+            hours: fraction * horizon,
+            n: n_paths,
+        })
+    }
+    
+    fn terminal_quantile_function(&mut self, probs: &[f64], n_paths: u32) -> Result<TerminalQuantiles, String> {
+        if probs.is_empty() {
+            return Err("At least one probability is required".to_string());
+        }
+        if let Some(prob) = probs.iter().find(|prob| !(0.0..=1.0).contains(*prob)) {
+            return Err(format!("Probabilities must be in [0, 1], got {}", prob));
+        }
+        
+        let mut prices = self.simulate_terminal_prices(n_paths)?;
+        prices.sort_by(f64::total_cmp);
+        
+        Ok(TerminalQuantiles {
+            probs: probs.to_vec(),
+            quantiles: probs.iter().map(|&prob| utils::quantile_sorted(&prices, prob)).collect(),
+            n: n_paths,
+        })
+    }
+    
+    fn terminal_risk(&mut self, n_paths: u32, level: f64) -> Result<RiskMetrics, String> {
+        if !(level > 0.0 && level < 1.0) {
+            return Err(format!("Risk level must be in (0, 1), got {}", level));
+        }
+        
+        let prices = self.simulate_terminal_prices(n_paths)?;
+        tail_risk(&prices, self.model.sim_inputs.s0, level).ok_or_else(|| "n_paths must be positive".to_string())
+    }
+    
+    /// Invert the empirical terminal CDF so that P(above K) * 100 == target_cents
+    fn crossover_strike(&mut self, target_cents: f64, n_paths: u32) -> Result<StrikeForFair, String> {
+        if !(target_cents > 0.0 && target_cents < 100.0) {
+            return Err("Target price must be between 0 and 100 cents".to_string());
+        }
+        
+        let mut prices = self.simulate_terminal_prices(n_paths)?;
+        prices.sort_by(|a, b| a.total_cmp(b));
+        
+        // P(above K) = q means K sits at the (1 - q) quantile of the terminal price
+        let q = target_cents / 100.0;
+        let strike = utils::quantile_sorted(&prices, 1.0 - q);
+        
+        // Distribution-free interval from the binomial spread of the order statistic's rank
+        let n = n_paths as f64;
+        let half_width = utils::two_sided_z(0.95) * (n * q * (1.0 - q)).sqrt() / n;
+        let ci = [
+            utils::quantile_sorted(&prices, (1.0 - q - half_width).max(0.0)),
+            utils::quantile_sorted(&prices, (1.0 - q + half_width).min(1.0)),
+        ];
+        
+        Ok(StrikeForFair {
+            target_cents,
+            strike,
+            ci,
+            n: n_paths,
+        })
+    }
+    
+    fn simulate_batches(
+        &mut self,
+        target: Target,
+        n_paths: u32,
+        batch_size: u32,
+        mut on_batch: impl FnMut(&IntermediateResult) -> Result<(), String>,
+    ) -> Result<SimResult, String> {
+        if n_paths == 0 || batch_size == 0 {
+            return Err("n_paths and batch_size must be positive".to_string());
+        }
+        
+        let mut acc = RunAccumulator::new(n_paths as usize);
+        let replay_seed = self.replay_seed();
+        
+        let num_batches = n_paths.div_ceil(batch_size);
+        
+        for batch in 0..num_batches {
+            let batch_paths = if batch == num_batches - 1 {
+                n_paths - batch * batch_size
+            } else {
+                batch_size
+            };
+            
+            // Run batch
+            for path_idx in 0..batch_paths {
+                let global_index = batch * batch_size + path_idx;
+                
+                let mut trace = if acc.wants_sample() {
+                    Some(Vec::new())
+                } else {
+                    None
+                };
+                
+                let outcome = run_path(&self.model, replay_seed, global_index, trace.as_mut(), Some(&target));
+                let hit = target_hit(&target, &outcome)?;
+                
+                acc.record(global_index, &outcome, trace, hit)?;
+            }
+            
+            // Calculate intermediate result
+            let p = acc.p();
+            let ci = acc.ci();
+            
+            on_batch(&IntermediateResult {
+                batch: batch + 1,
+                total_paths: acc.n,
+                p,
+                ci,
+                fair: p * 100.0,
+            })?;
+        }
+        
+        // Final summary with distribution and diagnostics
+        let mut result = self.finish_run(acc, target)?;
+        result.diagnostics.replay_seed = Some(replay_seed);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn test_inputs() -> SimInputs {
+        serde_json::from_str(r#"{
+            "s0": 100000.0,
+            "t": 24.0,
+            "dt": 1.0,
+            "regimes": {
+                "BULL": { "mu": 0.0001, "heston": { "kappa": 2.0, "theta": 0.0001, "xi": 0.01, "rho": -0.5 } },
+                "BEAR": { "mu": -0.0001, "heston": { "kappa": 2.0, "theta": 0.0002, "xi": 0.01, "rho": -0.5 } }
+            },
+            "hmm": { "p": [[0.95, 0.05], [0.10, 0.90]], "pi0": [0.7, 0.3] },
+            "jumps": { "lambda": 0.01, "mu_j": -0.01, "sigma_j": 0.02, "kind": "merton" }
+        }"#).unwrap()
+    }
+    
+    fn seeded_engine(sim_inputs: SimInputs, seed: u64) -> MonteCarloEngine {
+        MonteCarloEngine::from_inputs(sim_inputs, seed).unwrap()
+    }
+    
+    /// The system allocator, counting allocations per thread so parallel tests don't see each other's
+    struct CountingAlloc;
+    
+    thread_local! {
+        static ALLOCATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+    
+    unsafe impl std::alloc::GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            // Thread-local storage is gone while a thread shuts down: those allocations go uncounted
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            std::alloc::System.alloc(layout)
+        }
+        
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            std::alloc::System.dealloc(ptr, layout)
+        }
+    }
+    
+    #[global_allocator]
+    static ALLOCATOR: CountingAlloc = CountingAlloc;
+    
+    fn allocations_during<T>(f: impl FnOnce() -> T) -> usize {
+        let before = ALLOCATIONS.with(|count| count.get());
+        f();
+        ALLOCATIONS.with(|count| count.get()) - before
+    }
+    
+    /// Driftless, jump-free, identical regimes with near-deterministic variance: pure diffusion
+    fn diffusion_inputs() -> SimInputs {
+        let mut inputs = test_inputs();
+        inputs.regimes[BULL].mu = 0.0;
+        inputs.regimes[BULL].heston.xi = 1e-6;
+        inputs.regimes[BEAR] = inputs.regimes[BULL].clone();
+        inputs.jumps.lambda = 0.0;
+        inputs
+    }
+    
+    /// `diffusion_inputs` with the variance floored at theta: constant-vol GBM
+    fn gbm_inputs() -> SimInputs {
+        let mut inputs = diffusion_inputs();
+        inputs.regimes[BULL].heston.var_floor = inputs.regimes[BULL].heston.theta;
+        inputs.regimes[BEAR] = inputs.regimes[BULL].clone();
+        inputs
+    }
+    
+    fn basket_inputs(rho: f64) -> SimInputs {
+        let mut inputs = diffusion_inputs();
+        // Identical, near-deterministic variance so the index spread comes from the shocks alone
+        let asset = BasketAsset {
+            weight: 0.5,
+            s0: 100000.0,
+            regimes: inputs.regimes.clone(),
+            jumps: JumpParams { lambda: 0.0, ..inputs.jumps.clone() },
+        };
+        inputs.basket = Some(Basket {
+            assets: vec![asset.clone(), asset],
+            correlation: vec![vec![1.0, rho], vec![rho, 1.0]],
+        });
+        inputs
+    }
+    
+    /// `inputs` switching at `at_hour` to its own regimes, hmm and jumps: the same paths as `inputs`
+    fn switch_to_same(inputs: SimInputs, at_hour: f64) -> SimInputs {
+        let same = ScenarioInputs { regimes: inputs.regimes.clone(), hmm: inputs.hmm.clone(), jumps: inputs.jumps.clone() };
+        SimInputs { scenario_switch: Some(ScenarioSwitch { at_hour, inputs: same }), ..inputs }
+    }
+    
+    fn above(k: f64) -> Target {
+        Target { kind: "above".to_string(), K: Some(k), ..Default::default() }
+    }
+    
+    #[test]
+    fn test_regime_switch_stats() {
+        let mut inputs = test_inputs();
+        inputs.hmm.p = vec![vec![0.5, 0.5], vec![0.5, 0.5]];
+        let mut engine = seeded_engine(inputs, 1);
+        
+        let result = engine.simulate(above(100000.0), 2000).unwrap();
+        let stats = result.diagnostics.regime_switches.unwrap();
+        
+        // 24 steps with a 0.5 switch chance each
+        assert!((stats.mean - 12.0).abs() < 0.5);
+        assert!(stats.variance > 0.0);
+        assert_eq!(stats.counts.iter().sum::<u32>(), 2000);
+        assert_eq!(stats.counts.len() as u32, stats.max + 1);
+    }
+    
+    #[test]
+    fn test_regime_occupancy_in_diagnostics() {
+        let mut inputs = test_inputs();
+        inputs.hmm.p = vec![vec![0.98, 0.02], vec![0.30, 0.70]];
+        inputs.hmm.pi0 = vec![0.5, 0.5];
+        let mut engine = seeded_engine(inputs, 2);
+        
+        let result = engine.simulate(above(100000.0), 2000).unwrap();
+        let occupancy = &result.diagnostics.regime_occupancy;
+        
+        assert_eq!(occupancy.len(), 2);
+        assert!((occupancy.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        assert!(occupancy[BULL] > 0.5);
+    }
+    
+    #[test]
+    fn test_saturated_result() {
+        let mut engine = seeded_engine(test_inputs(), 2);
+        
+        // Far OTM: no path gets anywhere near
+        let result = engine.simulate(above(1_000_000.0), 1000).unwrap();
+        assert_eq!(result.p, 0.0);
+        assert!(result.diagnostics.saturated);
+        assert!(result.diagnostics.stderr > 0.0);
+        assert!(result.ci[1] > 0.0);
+        assert!(result.diagnostics.relative_stderr.is_none());
+        
+        let result = engine.simulate(above(100_000.0), 1000).unwrap();
+        assert!(!result.diagnostics.saturated);
+        let relative = result.diagnostics.relative_stderr.unwrap();
+        assert!((relative - result.diagnostics.stderr / result.p).abs() < 1e-12);
+    }
+    
+    #[test]
+    fn test_position_economics() {
+        let mut engine = seeded_engine(test_inputs(), 4);
+        let result = engine.simulate(above(100000.0), 2000).unwrap();
+        
+        let buy = position_economics(&result, "buy", 40.0, 10).unwrap();
+        let sell = position_economics(&result, "sell", 40.0, 10).unwrap();
+        
+        // roundup(0.07 * 10 * 0.4 * 0.6) = $0.17
+        assert_eq!(buy.fee_cents, 17.0);
+        assert!((buy.expected_profit_cents - (10.0 * (result.p * 100.0 - 40.0) - 17.0)).abs() < 1e-9);
+        assert!((buy.expected_profit_cents + sell.expected_profit_cents + 34.0).abs() < 1e-9);
+        assert!(buy.ci[0] <= buy.expected_profit_cents && buy.expected_profit_cents <= buy.ci[1]);
+        
+        assert!(position_economics(&result, "hold", 40.0, 10).is_err());
+        assert!(position_economics(&result, "buy", 100.0, 10).is_err());
+    }
+    
+    #[test]
+    fn test_strike_ladder_smoothing_is_monotone() {
+        let mut engine = seeded_engine(test_inputs(), 5);
+        let strikes: Vec<f64> = (0..11).map(|i| 99000.0 + 200.0 * i as f64).collect();
+        
+        let ladder = engine.strike_ladder(&strikes, 2000, Some("logistic"), false).unwrap();
+        let smoothed = ladder.smoothed.unwrap();
+        
+        assert_eq!(smoothed.len(), strikes.len());
+        for pair in smoothed.windows(2) {
+            assert!(pair[1] <= pair[0]);
+        }
+        for pair in ladder.p.windows(2) {
+            assert!(pair[1] <= pair[0]);
+        }
+        
+        assert!(engine.strike_ladder(&strikes, 100, None, false).unwrap().smoothed.is_none());
+        assert!(engine.strike_ladder(&strikes, 100, Some("spline"), false).is_err());
+        
+        // The shared paths already order the raw curve, so the isotonic fit keeps it as is,
+        // whatever order the strikes come in
+        let shuffled: Vec<f64> = strikes.iter().rev().copied().collect();
+        let ladder = seeded_engine(test_inputs(), 5).strike_ladder(&shuffled, 2000, Some("isotonic"), false).unwrap();
+        assert_eq!(ladder.smoothed.unwrap(), ladder.p);
+    }
+    
+    #[test]
+    fn test_strike_ladder_covariance() {
+        let mut engine = seeded_engine(test_inputs(), 47);
+        let strikes = [99500.0, 100000.0, 100500.0];
+        
+        assert!(engine.strike_ladder(&strikes, 100, None, false).unwrap().covariance.is_none());
+        
+        let n = 3000;
+        let ladder = engine.strike_ladder(&strikes, n, None, true).unwrap();
+        let cov = ladder.covariance.unwrap();
+        
+        // Diagonal is the binomial variance; neighbours are strongly positively correlated
+        for (i, row) in cov.iter().enumerate() {
+            assert!((row[i] - utils::binomial_stderr(ladder.p[i], n).powi(2)).abs() < 1e-15);
+            for (j, &entry) in row.iter().enumerate() {
+                assert_eq!(entry, cov[j][i]);
+                assert!(entry > 0.0);
+            }
+        }
+        assert!(cov[0][1] / (cov[0][0] * cov[1][1]).sqrt() > 0.5);
+        
+        // Matches the sample covariance of the hit indicators, recomputed from the same paths
+        let mut replay = seeded_engine(test_inputs(), 47);
+        replay.strike_ladder(&strikes, 100, None, false).unwrap();
+        let prices = replay.simulate_terminal_prices(n).unwrap();
+        let joint = prices.iter().filter(|&&s| s > strikes[0] && s > strikes[2]).count() as f64 / n as f64;
+        assert!((cov[0][2] - (joint - ladder.p[0] * ladder.p[2]) / n as f64).abs() < 1e-15);
+    }
+    
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_parallel_simulation() {
+        let mut engine = seeded_engine(test_inputs(), 6);
+        
+        let parallel = engine.run_simulation_parallel(above(100000.0), 8000, 4).unwrap();
+        let sequential = engine.simulate(above(100000.0), 8000).unwrap();
+        
+        assert_eq!(parallel.diagnostics.n, 8000);
+        assert_eq!(parallel.diagnostics.regime_switches.unwrap().counts.iter().sum::<u32>(), 8000);
+        assert!(parallel.distribution.unwrap().samples.len() <= MAX_PATH_SAMPLES);
+        assert!((parallel.p - sequential.p).abs() < 4.0 * (parallel.diagnostics.stderr + sequential.diagnostics.stderr));
+        
+        // Paths come from per-path seeds, so a re-seeded engine reproduces the run on any thread count,
+        // and it matches the sequential run from the same engine state
+        let again = seeded_engine(test_inputs(), 6).run_simulation_parallel(above(100000.0), 8000, 3).unwrap();
+        assert_eq!(again.p, parallel.p);
+        let serial = seeded_engine(test_inputs(), 6).simulate(above(100000.0), 8000).unwrap();
+        assert_eq!(serial.hits, parallel.hits);
+        assert_eq!(serial.diagnostics.replay_seed, parallel.diagnostics.replay_seed);
+    }
+    
+    #[test]
+    fn test_warmup_does_not_extend_horizon() {
+        let mut inputs = test_inputs();
+        inputs.warmup_hours = 48.0;
+        let mut engine = seeded_engine(inputs, 7);
+        
+        let result = engine.simulate(above(100000.0), 200).unwrap();
+        let sample = &result.distribution.unwrap().samples[0];
+        
+        assert_eq!(sample.points.len(), 25);
+        assert_eq!(sample.points[0].t, 0.0);
+        assert_eq!(sample.points[0].price, 100000.0);
+        assert_eq!(sample.points[24].t, 24.0);
+        
+        let mut negative = test_inputs();
+        negative.warmup_hours = -1.0;
+        assert!(MonteCarloEngine::from_inputs(negative, 0).is_err());
+    }
+    
+    #[test]
+    fn test_return_above_matches_equivalent_strike() {
+        let s0 = test_inputs().s0;
+        
+        for pct in [0.01, -0.01] {
+            let target = Target { kind: "return_above".to_string(), pct: Some(pct), ..Default::default() };
+            let relative = seeded_engine(test_inputs(), 8).simulate(target, 2000).unwrap();
+            let absolute = seeded_engine(test_inputs(), 8).simulate(above(s0 * (1.0 + pct)), 2000).unwrap();
+            
+            assert!((relative.p - absolute.p).abs() < 1e-12);
+            assert_eq!(relative.target.pct, Some(pct));
+        }
+        
+        let missing = Target { kind: "return_above".to_string(), ..Default::default() };
+        assert!(seeded_engine(test_inputs(), 8).simulate(missing, 10).is_err());
+    }
+    
+    #[test]
+    fn test_annualized_vol_by_regime() {
+        let mut inputs = test_inputs();
+        // 50% and 90% annual vol expressed as hourly variance
+        inputs.regimes[BULL].heston.theta = 0.25 / utils::HOURS_PER_YEAR;
+        inputs.regimes[BEAR].heston.theta = 0.81 / utils::HOURS_PER_YEAR;
+        inputs.hmm.pi0 = vec![0.5, 0.5];
+        
+        let vol = annualized_vol(&inputs, &inputs.hmm.pi0);
+        assert!((vol.by_regime[0] - 0.5).abs() < 1e-12);
+        assert!((vol.by_regime[1] - 0.9).abs() < 1e-12);
+        assert!((vol.blended - 0.53f64.sqrt()).abs() < 1e-12);
+        
+        let result = seeded_engine(inputs, 9).simulate(above(100000.0), 10).unwrap();
+        assert_eq!(result.diagnostics.annualized_vol.by_regime.len(), 2);
+    }
+    
+    #[test]
+    fn test_strike_for_fair_inverts_ladder() {
+        let crossover = seeded_engine(test_inputs(), 10).crossover_strike(50.0, 4000).unwrap();
+        assert!(crossover.ci[0] <= crossover.strike && crossover.strike <= crossover.ci[1]);
+        
+        // Same paths priced at the recovered strike land back on ~50c
+        let priced = seeded_engine(test_inputs(), 10).simulate(above(crossover.strike), 4000).unwrap();
+        assert!((priced.fair - 50.0).abs() < 0.1);
+        
+        assert!(seeded_engine(test_inputs(), 10).crossover_strike(100.0, 10).is_err());
+    }
+    
+    #[test]
+    fn test_time_in_the_money() {
+        let mut engine = seeded_engine(gbm_inputs(), 79);
+        
+        // At the money the price spends about half the time above; far away, almost never
+        let atm = engine.time_in_the_money(above(100000.0), 2000).unwrap();
+        assert!((atm.fraction - 0.5).abs() < 0.1);
+        assert!(atm.stderr > 0.0 && atm.stderr < 0.02);
+        assert!((atm.hours - 24.0 * atm.fraction).abs() < 1e-9);
+        
+        let far = engine.time_in_the_money(above(120000.0), 500).unwrap();
+        assert!(far.fraction < 0.01);
+        
+        // Paths start below an out-of-the-money strike, so the time share trails the terminal probability
+        let otm = engine.time_in_the_money(above(101000.0), 2000).unwrap();
+        assert!(otm.fraction < engine.simulate(above(101000.0), 2000).unwrap().p);
+        
+        let range = Target { kind: "range".to_string(), L: Some(1.0), U: Some(2.0), ..Default::default() };
+        assert!(engine.time_in_the_money(range, 100).is_err());
+        
+        // A near-deterministic climb through K at hour 18.1, on four-hour then quarter-hour steps:
+        // the steps ending above K cover the last 6 hours, though they are 24 of the 51 steps
+        let mut climb = SimInputs { dt_schedule: vec![(0.0, 4.0), (12.0, 0.25)], ..gbm_inputs() };
+        for params in climb.regimes.iter_mut() {
+            params.mu = 0.001;
+            params.heston.theta = 1e-14;
+            params.heston.var_floor = 1e-14;
+        }
+        let strike = climb.s0 * (0.001f64 * 18.1).exp();
+        let climb = seeded_engine(climb, 80).time_in_the_money(above(strike), 10).unwrap();
+        assert!((climb.fraction - 0.25).abs() < 1e-9, "fraction {}", climb.fraction);
+    }
+    
+    #[test]
+    fn test_hit_counts_pool_across_runs() {
+        let first = seeded_engine(test_inputs(), 5).simulate(above(100000.0), 1000).unwrap();
+        let second = seeded_engine(test_inputs(), 6).simulate(above(100000.0), 3000).unwrap();
+        assert_eq!(first.n, 1000);
+        assert_eq!(first.hits as f64, first.p * 1000.0);
+        
+        let json: serde_json::Value = serde_json::to_value(&second).unwrap();
+        assert_eq!(json["hits"], second.hits);
+        assert_eq!(json["n"], 3000);
+        
+        // Pooled counts give the path-weighted p, not the mean of the two estimates
+        let pooled = (first.hits + second.hits) as f64 / (first.n + second.n) as f64;
+        assert!((pooled - (first.p + 3.0 * second.p) / 4.0).abs() < 1e-12);
+    }
+    
+    #[test]
+    fn test_feller_check() {
+        // 2 kappa theta = 4e-4 >= xi^2 = 1e-4 in both regimes
+        let healthy = test_inputs();
+        let checks = feller_checks(&healthy);
+        assert_eq!(checks.len(), 2);
+        assert!(checks.iter().all(|check| check.satisfied));
+        assert!((checks[BULL].ratio.unwrap() - 4.0).abs() < 1e-9);
+        
+        // xi = 0.05 puts xi^2 = 2.5e-3 above 2 kappa theta in both regimes
+        let mut violating = test_inputs();
+        for params in violating.regimes.iter_mut() {
+            params.heston.xi = 0.05;
+        }
+        let checks = feller_checks(&violating);
+        assert!(checks.iter().all(|check| !check.satisfied && check.ratio.unwrap() < 1.0));
+        
+        let result = seeded_engine(violating, 49).simulate(above(100000.0), 50).unwrap();
+        assert!(result.diagnostics.feller_violations);
+        let result = seeded_engine(healthy, 49).simulate(above(100000.0), 50).unwrap();
+        assert!(!result.diagnostics.feller_violations);
+    }
+    
+    #[test]
+    fn test_full_truncation_scheme() {
+        // Feller violated: 2 kappa theta < xi^2
+        let mut inputs = test_inputs();
+        for params in inputs.regimes.iter_mut() {
+            params.heston.xi = 0.05;
+        }
+        let qe = seeded_engine(inputs.clone(), 12).simulate(above(100000.0), 2000).unwrap();
+        assert!(qe.diagnostics.qe_branches.is_some());
+        
+        inputs.variance_scheme = "full_truncation".to_string();
+        let euler = seeded_engine(inputs.clone(), 12).simulate(above(100000.0), 2000).unwrap();
+        assert!(euler.diagnostics.qe_branches.is_none());
+        assert!((euler.p - qe.p).abs() < 0.06);
+        
+        inputs.variance_scheme = "exact".to_string();
+        assert!(Model::new(inputs).is_err());
+    }
+    
+    #[test]
+    fn test_jump_kinds() {
+        let mut inputs = test_inputs();
+        inputs.jumps = JumpParams { kind: "kou".to_string(), lambda: 0.5, p_up: 0.3, eta_up: 40.0, eta_down: 25.0, ..inputs.jumps };
+        let crashy = seeded_engine(inputs.clone(), 2).simulate(above(100000.0), 2000).unwrap();
+        assert!(crashy.p > 0.0 && crashy.p < 1.0);
+        
+        // Unknown kinds are an error instead of silently falling back to Merton
+        inputs.jumps.kind = "variance_gamma".to_string();
+        assert!(MonteCarloEngine::from_inputs(inputs.clone(), 0).is_err());
+        inputs.jumps.kind = "kou".to_string();
+        inputs.jumps.eta_up = 0.5;
+        assert!(MonteCarloEngine::from_inputs(inputs, 0).is_err());
+    }
+    
+    #[test]
+    fn test_jump_vol_spike() {
+        // Full truncation at a fine step, so the variance actually moves around theta
+        let mut inputs = test_inputs();
+        inputs.variance_scheme = "full_truncation".to_string();
+        inputs.dt = 0.1;
+        inputs.jumps.lambda = 0.2;
+        inputs.hmm.pi0 = vec![0.0, 1.0];
+        inputs.hmm.p = vec![vec![1.0, 0.0], vec![0.0, 1.0]];
+        let mut spiked = inputs.clone();
+        spiked.regimes[BULL].jump_vol_spike = 2.0;
+        spiked.regimes[BEAR].jump_vol_spike = 10.0;
+        
+        // No extra draws: the paths agree up to the first jump
+        let (calm, shocked) = (seeded_engine(inputs, 1), seeded_engine(spiked.clone(), 1));
+        let (calm_steps, shocked_steps) = (calm.replay(3, 1).unwrap(), shocked.replay(3, 1).unwrap());
+        let first_jump = calm_steps.iter().position(|step| step.jump_occurred).unwrap();
+        assert!(first_jump + 1 < calm_steps.len());
+        for (i, (a, b)) in calm_steps.iter().zip(&shocked_steps).enumerate() {
+            if i <= first_jump {
+                assert_eq!(a.v, b.v);
+            }
+        }
+        
+        // On average the spikes lift the variance
+        let mean_v = |engine: &MonteCarloEngine| {
+            let steps: Vec<PathStep> = (0..500).flat_map(|i| engine.replay(5, i).unwrap()).collect();
+            steps.iter().map(|step| step.v).sum::<f64>() / steps.len() as f64
+        };
+        assert!(mean_v(&shocked) > 1.2 * mean_v(&calm));
+        
+        // Paths stay in BEAR, so the BULL coefficient never applies
+        let mut bull_only = spiked.clone();
+        bull_only.regimes[BEAR].jump_vol_spike = 0.0;
+        let bull_only_steps = seeded_engine(bull_only, 1).replay(3, 1).unwrap();
+        assert!(bull_only_steps.iter().zip(&calm_steps).all(|(a, b)| a.v == b.v));
+        
+        // Every jump in the step adds its own spike
+        let model = Model::new(spiked.clone()).unwrap();
+        let (params, mut factors) = (&spiked.regimes[BEAR], [1e-4, 0.0]);
+        model.apply_jump_vol_spike(&mut factors, params, 3);
+        assert!((factors[0] - (1e-4 + 3.0 * 10.0 * params.heston.theta)).abs() < 1e-15);
+        model.apply_jump_vol_spike(&mut factors, params, 0);
+        assert!((factors[0] - (1e-4 + 3.0 * 10.0 * params.heston.theta)).abs() < 1e-15);
+        
+        spiked.regimes[BULL].jump_vol_spike = -1.0;
+        assert!(Model::new(spiked).is_err());
+    }
+    
+    #[test]
+    fn test_terminal_stats() {
+        let s0 = diffusion_inputs().s0;
+        let result = seeded_engine(diffusion_inputs(), 37).simulate(above(s0), 4000).unwrap();
+        let stats = result.terminal_stats.unwrap();
+        
+        // Driftless and jump-free: the median sits within a small log-move of the start
+        assert!(stats.p50 > s0 * (-0.01f64).exp() && stats.p50 < s0 * 0.01f64.exp());
+        assert!(stats.p5 < stats.p25 && stats.p25 < stats.p50 && stats.p50 < stats.p75 && stats.p75 < stats.p95);
+        
+        // About half the paths finish above the median
+        assert!((result.p - 0.5).abs() < 0.05);
+    }
+    
+    #[test]
+    fn test_multi_target_ladder_is_monotonic() {
+        let s0 = test_inputs().s0;
+        let strikes: Vec<f64> = (0..8).map(|i| s0 * (0.98 + 0.005 * i as f64)).collect();
+        let targets = strikes.iter().map(|&k| above(k)).collect();
+        
+        let mut engine = seeded_engine(test_inputs(), 48);
+        let results = engine.simulate_multi_target(targets, 2000).unwrap();
+        assert_eq!(results.len(), strikes.len());
+        assert!(results.windows(2).all(|pair| pair[1].p <= pair[0].p));
+        assert!(results.iter().zip(&strikes).all(|(result, &k)| result.target.K == Some(k) && result.n == 2000));
+        
+        // The same paths as a single-target run from the same engine state
+        let single = seeded_engine(test_inputs(), 48).simulate(above(strikes[3]), 2000).unwrap();
+        assert_eq!(single.p, results[3].p);
+    }
+    
+    #[test]
+    fn test_multi_target_watches_touch_levels() {
+        let s0 = test_inputs().s0;
+        let touch = Target { kind: "touch".to_string(), K: Some(s0 * 1.01), ..Default::default() };
+        let race = Target { kind: "first_touch".to_string(), L: Some(s0 * 0.99), U: Some(s0 * 1.01), ..Default::default() };
+        let targets = vec![above(s0), touch.clone(), race.clone()];
+        
+        // Each watching target agrees with its own run, first-passage times included
+        let results = seeded_engine(test_inputs(), 50).simulate_multi_target(targets.clone(), 2000).unwrap();
+        for (result, target) in results.iter().zip(&targets) {
+            let single = seeded_engine(test_inputs(), 50).simulate(target.clone(), 2000).unwrap();
+            assert_eq!(serde_json::to_string(result).unwrap(), serde_json::to_string(&single).unwrap());
+        }
+        assert!(results[1].first_passage.is_some());
+        
+        // and antithetic twins watch them too
+        let paired = seeded_engine(test_inputs(), 50).simulate_multi_target_antithetic(targets, 2000).unwrap();
+        let single = seeded_engine(test_inputs(), 50).simulate_antithetic(touch, 2000).unwrap();
+        assert_eq!(paired[1].hits, single.hits);
+        assert_eq!(paired[1].first_passage.as_ref().map(|fpt| fpt.p50), single.first_passage.map(|fpt| fpt.p50));
+    }
+    
+    #[test]
+    fn test_antithetic_multi_target_reduces_stderr() {
+        let s0 = gbm_inputs().s0;
+        let targets: Vec<Target> = [0.99, 1.0, 1.01].iter().map(|m| above(s0 * m)).collect();
+        
+        let plain = seeded_engine(gbm_inputs(), 49).simulate_multi_target(targets.clone(), 4000).unwrap();
+        let paired = seeded_engine(gbm_inputs(), 49).simulate_multi_target_antithetic(targets, 4000).unwrap();
+        
+        assert_eq!(paired.len(), 3);
+        assert!(paired.windows(2).all(|pair| pair[1].p <= pair[0].p));
+        // Near the money the indicator is close to odd in the shock, so the pairs cancel most noise
+        assert!(paired[1].diagnostics.stderr < plain[1].diagnostics.stderr);
+        assert!((paired[1].p - plain[1].p).abs() < 3.0 * plain[1].diagnostics.stderr);
+    }
+    
+    #[test]
+    fn test_first_passage_times() {
+        let inputs = gbm_inputs();
+        let (s0, t) = (inputs.s0, inputs.t);
+        let touch_up = |k: f64| Target { kind: "touch".to_string(), K: Some(k), direction: Some("up".to_string()), ..Default::default() };
+        
+        let mut engine = seeded_engine(inputs, 47);
+        let near = engine.simulate(touch_up(s0 * 1.005), 3000).unwrap();
+        let far = engine.simulate(touch_up(s0 * 1.03), 3000).unwrap();
+        
+        let (near_fpt, far_fpt) = (near.first_passage.unwrap(), far.first_passage.unwrap());
+        assert_eq!(near_fpt.touched, near.hits);
+        assert!(near_fpt.p5 >= 0.0 && near_fpt.p95 <= t && far_fpt.p5 >= 0.0 && far_fpt.p95 <= t);
+        assert!(far_fpt.p50 > near_fpt.p50, "far {} vs near {}", far_fpt.p50, near_fpt.p50);
+        
+        // Plain terminal targets watch nothing
+        assert!(engine.simulate(above(s0), 100).unwrap().first_passage.is_none());
+    }
+    
+    #[test]
+    fn test_price_histogram() {
+        let s0 = diffusion_inputs().s0;
+        let mut engine = seeded_engine(diffusion_inputs(), 38);
+        let result = engine.simulate_with_histogram(above(s0), 1500, 20).unwrap();
+        let histogram = result.price_histogram.unwrap();
+        
+        assert_eq!(histogram.counts.len(), 20);
+        assert_eq!(histogram.bin_edges.len(), 21);
+        assert_eq!(histogram.counts.iter().sum::<u32>(), 1500);
+        assert!(histogram.bin_edges.windows(2).all(|pair| pair[0] < pair[1]));
+        
+        // One-off: later runs go back to plain results
+        assert!(engine.simulate(above(s0), 10).unwrap().price_histogram.is_none());
+        assert!(engine.simulate_with_histogram(above(s0), 10, 0).is_err());
+    }
+    
+    #[test]
+    fn test_touch_target() {
+        let touch = |k: f64, direction: &str| Target {
+            kind: "touch".to_string(),
+            K: Some(k),
+            direction: Some(direction.to_string()),
+            ..Default::default()
+        };
+        let below = |k: f64| Target { kind: "below".to_string(), K: Some(k), ..Default::default() };
+        
+        // Same paths: a path finishing beyond the barrier touched it on the way
+        for k in [100500.0, 101000.0, 102000.0] {
+            let touched = seeded_engine(gbm_inputs(), 61).simulate(touch(k, "up"), 3000).unwrap();
+            let finished = seeded_engine(gbm_inputs(), 61).simulate(above(k), 3000).unwrap();
+            assert!(touched.p >= finished.p);
+        }
+        let touched = seeded_engine(gbm_inputs(), 61).simulate(touch(99000.0, "down"), 3000).unwrap();
+        let finished = seeded_engine(gbm_inputs(), 61).simulate(below(99000.0), 3000).unwrap();
+        assert!(touched.p > finished.p);
+        
+        // Direction defaults to up
+        let up = Target { direction: None, ..touch(101000.0, "up") };
+        let outcome = PathOutcome { initial_price: 100.0, final_price: 100.0, min_price: 95.0, max_price: 101000.0, ..Default::default() };
+        assert!(target_hit(&up, &outcome).unwrap());
+        assert!(!target_hit(&touch(94.0, "down"), &outcome).unwrap());
+        assert!(target_hit(&touch(1.0, "sideways"), &outcome).is_err());
+        assert!(target_hit(&Target { K: None, ..up }, &outcome).is_err());
+    }
+    
+    #[test]
+    fn test_bridge_correction_raises_touch_probability() {
+        let touch = Target { kind: "touch".to_string(), K: Some(102000.0), direction: Some("up".to_string()), ..Default::default() };
+        let run = |bridge_correction: bool| {
+            let mut sim_inputs = gbm_inputs();
+            sim_inputs.t = 12.0;
+            sim_inputs.dt = 2.0;
+            sim_inputs.bridge_correction = bridge_correction;
+            seeded_engine(sim_inputs, 71).simulate(touch.clone(), 20000).unwrap().p
+        };
+        
+        // Continuous monitoring of a driftless-mu GBM: log drift m = -sigma^2 / 2
+        let inputs = gbm_inputs();
+        let (sigma, t) = (inputs.regimes[BULL].heston.theta.sqrt(), 12.0);
+        let (b, m) = ((102000.0f64 / inputs.s0).ln(), -0.5 * sigma * sigma);
+        let continuous = normal_cdf((-b + m * t) / (sigma * t.sqrt()))
+            + (2.0 * m * b / (sigma * sigma)).exp() * normal_cdf((-b - m * t) / (sigma * t.sqrt()));
+        
+        let (plain, bridged) = (run(false), run(true));
+        assert!(plain < bridged);
+        assert!((bridged - continuous).abs() < (plain - continuous).abs());
+        assert!((bridged - continuous).abs() < 0.015, "{} vs {}", bridged, continuous);
+    }
+    
+    #[test]
+    fn test_bridge_correction_in_multi_target() {
+        let mut inputs = gbm_inputs();
+        inputs.dt = 2.0;
+        inputs.bridge_correction = true;
+        let touch = Target { kind: "touch".to_string(), K: Some(inputs.s0 * 1.01), ..Default::default() };
+        let targets = vec![above(inputs.s0), touch.clone()];
+        
+        // The shared paths cross the touch level between steps exactly as a single-target run does
+        let results = seeded_engine(inputs.clone(), 72).simulate_multi_target(targets.clone(), 4000).unwrap();
+        let single = seeded_engine(inputs.clone(), 72).simulate(touch.clone(), 4000).unwrap();
+        assert_eq!(results[1].p, single.p);
+        assert_eq!(serde_json::to_string(&results[1]).unwrap(), serde_json::to_string(&single).unwrap());
+        
+        let paired = seeded_engine(inputs.clone(), 72).simulate_multi_target_antithetic(targets, 4000).unwrap();
+        assert_eq!(paired[1].p, seeded_engine(inputs, 72).simulate_antithetic(touch, 4000).unwrap().p);
+    }
+    
+    #[test]
+    fn test_first_touch_race() {
+        let race = |lower: f64, upper: f64| Target {
+            kind: "first_touch".to_string(),
+            L: Some(lower),
+            U: Some(upper),
+            ..Default::default()
+        };
+        let mut engine = seeded_engine(gbm_inputs(), 47);
+        
+        let result = engine.simulate_first_touch(race(99000.0, 101000.0), 3000).unwrap();
+        assert!((result.p_upper + result.p_lower + result.p_neither - 1.0).abs() < 1e-12);
+        assert!(result.p_upper > 0.0 && result.p_lower > 0.0);
+        
+        // Same paths as the target's own p; a touch is path-dependent, so it beats finishing above U
+        let mut replay = seeded_engine(gbm_inputs(), 47);
+        assert_eq!(replay.simulate(race(99000.0, 101000.0), 3000).unwrap().p, result.p_upper);
+        let finish = seeded_engine(gbm_inputs(), 47).simulate(above(101000.0), 3000).unwrap();
+        assert!(result.p_upper > finish.p);
+        
+        // Levels far away are never touched; a level at the start is touched at t = 0
+        assert_eq!(engine.simulate_first_touch(race(1.0, 1e9), 200).unwrap().p_neither, 1.0);
+        assert_eq!(engine.simulate_first_touch(race(50000.0, 100000.0), 200).unwrap().p_upper, 1.0);
+        
+        assert!(engine.simulate_first_touch(race(101000.0, 99000.0), 10).is_err());
+        assert!(engine.simulate_first_touch(above(100000.0), 10).is_err());
+        assert!(target_hit(&race(1.0, 2.0), &PathOutcome::default()).is_err());
+    }
+    
+    #[test]
+    fn test_expected_path_follows_compensated_drift() {
+        // Both gbm regimes are the same, so this is a single-regime model
+        let mut sim_inputs = gbm_inputs();
+        sim_inputs.regimes[BULL].mu = 0.002;
+        sim_inputs.regimes[BEAR].mu = 0.002;
+        sim_inputs.jumps.lambda = 0.05;
+        let engine = seeded_engine(sim_inputs.clone(), 7);
+        
+        let path = engine.expected_path().unwrap();
+        let drift = sim_inputs.regimes[BULL].mu - jump_compensator(&sim_inputs.jumps);
+        let expected = sim_inputs.s0 * (drift * sim_inputs.t).exp();
+        assert_eq!(path.points.len(), 25);
+        assert!((path.final_price - expected).abs() < 1e-6 * expected);
+        assert!(jump_compensator(&sim_inputs.jumps) != 0.0);
+    }
+    
+    #[test]
+    fn test_mu_schedule() {
+        let flat = |inputs: &SimInputs| {
+            let mut inputs = inputs.clone();
+            for params in inputs.regimes.iter_mut() {
+                params.mu_schedule = vec![(0.0, params.mu)];
+            }
+            inputs
+        };
+        let scalar = seeded_engine(test_inputs(), 97).simulate(above(100000.0), 1000).unwrap();
+        let term = seeded_engine(flat(&test_inputs()), 97).simulate(above(100000.0), 1000).unwrap();
+        assert_eq!((scalar.hits, scalar.distribution.unwrap().mean), (term.hits, term.distribution.unwrap().mean));
+        
+        // The drift in force switches halfway through the horizon
+        let mut inputs = gbm_inputs();
+        inputs.jumps.lambda = 0.0;
+        for params in inputs.regimes.iter_mut() {
+            params.mu_schedule = vec![(0.0, 0.001), (12.0, -0.003)];
+        }
+        let path = seeded_engine(inputs.clone(), 97).expected_path().unwrap();
+        assert!((path.points[12].price - inputs.s0 * (0.001f64 * 12.0).exp()).abs() < 1e-6);
+        assert!((path.final_price - inputs.s0 * (-0.002f64 * 12.0).exp()).abs() < 1e-6);
+        
+        for schedule in [vec![(1.0, 0.001)], vec![(0.0, 0.001), (0.0, 0.002)], vec![(0.0, 0.001), (24.0, 0.002)], vec![(0.0, f64::NAN)]] {
+            let mut bad = gbm_inputs();
+            bad.regimes[BULL].mu_schedule = schedule;
+            assert!(Model::new(bad).is_err());
+        }
+    }
+    
+    #[test]
+    fn test_replay_path() {
+        let mut engine = seeded_engine(test_inputs(), 83);
+        let result = engine.simulate(above(100000.0), 300).unwrap();
+        let seed = result.diagnostics.replay_seed.unwrap();
+        
+        // Replaying every path recovers the run's hit count
+        let replayed_hits = (0..300)
+            .filter(|&i| engine.replay(seed, i).unwrap().last().unwrap().s > 100000.0)
+            .count();
+        assert_eq!(replayed_hits as f64, result.p * 300.0);
+        
+        let steps = engine.replay(seed, 7).unwrap();
+        assert_eq!(steps.len(), engine.replay(seed, 7).unwrap().len());
+        assert!((steps.last().unwrap().t - test_inputs().t).abs() < 1e-9);
+        assert!(steps.iter().all(|step| step.s > 0.0 && step.v >= 0.0));
+        assert_ne!(steps.last().unwrap().s, engine.replay(seed, 8).unwrap().last().unwrap().s);
+        
+        let basket = seeded_engine(basket_inputs(0.5), 1);
+        assert!(basket.replay(seed, 0).is_err());
+    }
+    
+    #[test]
+    fn test_risk_metrics() {
+        let mut engine = seeded_engine(test_inputs(), 72);
+        for level in [0.01, 0.05, 0.25] {
+            let metrics = engine.terminal_risk(2000, level).unwrap();
+            assert!(metrics.var < 0.0);
+            assert!(metrics.cvar <= metrics.var, "level {}: cvar {} vs var {}", level, metrics.cvar, metrics.var);
+        }
+        
+        // A wider tail is milder
+        let (narrow, wide) = (engine.terminal_risk(2000, 0.01).unwrap(), engine.terminal_risk(2000, 0.25).unwrap());
+        assert!(narrow.var < wide.var && narrow.cvar < wide.cvar);
+        assert!(engine.terminal_risk(100, 0.0).is_err());
+        
+        // The same figures from a run's own terminal prices
+        let mut engine = seeded_engine(test_inputs(), 72);
+        engine.set_output(OutputOptions { risk_level: Some(0.05), ..Default::default() }).unwrap();
+        let result = engine.simulate(above(100000.0), 2000).unwrap();
+        let risk = result.risk.unwrap();
+        let replayed = seeded_engine(test_inputs(), 72).terminal_risk(2000, 0.05).unwrap();
+        assert_eq!((risk.var, risk.cvar, risk.n), (replayed.var, replayed.cvar, replayed.n));
+        assert!(engine.set_output(OutputOptions { risk_level: Some(1.0), ..Default::default() }).is_err());
+    }
+    
+    #[test]
+    fn test_terminal_quantiles() {
+        let mut engine = seeded_engine(test_inputs(), 71);
+        let probs: Vec<f64> = (0..=100).map(|i| i as f64 / 100.0).collect();
+        
+        let result = engine.terminal_quantile_function(&probs, 2000).unwrap();
+        assert_eq!(result.quantiles.len(), probs.len());
+        for pair in result.quantiles.windows(2) {
+            assert!(pair[0] <= pair[1]);
+        }
+        
+        // Same paths: half of them finish above the sample median
+        let mut replay = seeded_engine(test_inputs(), 71);
+        let median = result.quantiles[50];
+        let p = replay.simulate(above(median), 2000).unwrap().p;
+        assert!((p - 0.5).abs() <= 1.0 / 2000.0);
+        
+        assert!(engine.terminal_quantile_function(&[0.5, 1.5], 10).is_err());
+        assert!(engine.terminal_quantile_function(&[], 10).is_err());
+    }
+    
+    #[test]
+    fn test_ou_drift_widens_terminal_distribution() {
+        let base = seeded_engine(test_inputs(), 11).simulate(above(100000.0), 2000).unwrap();
+        
+        let mut inputs = test_inputs();
+        inputs.drift_ou = Some(DriftOu { speed: 0.1, vol: 0.002 });
+        let ou = seeded_engine(inputs, 11).simulate(above(100000.0), 2000).unwrap();
+        
+        assert!(ou.distribution.unwrap().stddev > base.distribution.unwrap().stddev);
+    }
+    
+    #[test]
+    fn test_stochastic_carry_reduces_drift() {
+        let mean_price = |carry: Option<CarryOu>| {
+            let inputs = SimInputs { carry, ..gbm_inputs() };
+            let result = seeded_engine(inputs, 23).simulate(above(100000.0), 4000).unwrap();
+            result.distribution.unwrap()
+        };
+        
+        let base = mean_price(None);
+        let fixed = mean_price(Some(CarryOu { initial: 0.001, mean: 0.001, speed: 0.5, vol: 0.0 }));
+        let floating = mean_price(Some(CarryOu { initial: 0.001, mean: 0.001, speed: 0.5, vol: 0.002 }));
+        
+        // 0.1%/hour for 24 hours
+        assert!((fixed.mean / base.mean - (-0.024f64).exp()).abs() < 0.005);
+        assert!(floating.stddev > fixed.stddev);
+        
+        let bad = SimInputs { carry: Some(CarryOu { initial: 0.0, mean: 0.0, speed: -1.0, vol: 0.0 }), ..gbm_inputs() };
+        assert!(Model::new(bad).is_err());
+    }
+    
+    #[test]
+    fn test_scenario_switch_mid_path() {
+        let mut crash = test_inputs();
+        crash.regimes[BULL].mu = -0.002;
+        crash.regimes[BEAR].mu = -0.002;
+        
+        let crash = ScenarioInputs { regimes: crash.regimes, hmm: crash.hmm, jumps: crash.jumps };
+        let with_switch = |at_hour: f64, inputs: ScenarioInputs| SimInputs {
+            scenario_switch: Some(ScenarioSwitch { at_hour, inputs }),
+            ..test_inputs()
+        };
+        let switched = |at_hour: f64| seeded_engine(with_switch(at_hour, crash.clone()), 41).simulate(above(100000.0), 2000).unwrap();
+        
+        // A switch after the last step starts never fires: identical draws, identical result
+        let base = seeded_engine(test_inputs(), 41).simulate(above(100000.0), 2000).unwrap();
+        for late in [23.5, 24.0] {
+            let never = switched(late);
+            assert_eq!(never.p, base.p);
+            assert_eq!(never.diagnostics.scenario_switch_hour, None);
+        }
+        
+        // Crashing for the second half of the horizon drags the price down
+        let halfway = switched(12.0);
+        assert_eq!(halfway.diagnostics.scenario_switch_hour, Some(12.0));
+        assert!(halfway.p < base.p - 0.1);
+        assert!(halfway.p > switched(0.0).p);
+        
+        // The switched parameter sets are validated in place of the base ones
+        let mut short = crash.clone();
+        short.regimes.truncate(1);
+        assert!(Model::new(with_switch(1.0, short)).is_err());
+        let mut bad_hmm = crash;
+        bad_hmm.hmm.pi0 = vec![1.0];
+        assert!(Model::new(with_switch(1.0, bad_hmm)).is_err());
+    }
+    
+    #[test]
+    fn test_diff_results_flags_significant_moves() {
+        let mut engine = seeded_engine(test_inputs(), 12);
+        let a = engine.simulate(above(100000.0), 4000).unwrap();
+        let b = engine.simulate(above(100000.0), 4000).unwrap();
+        let far = engine.simulate(above(101000.0), 4000).unwrap();
+        
+        // Same model, fresh paths: within noise
+        let same = diff_sim_results(&a, &b);
+        assert!(same.overlap && !same.significant);
+        
+        let moved = diff_sim_results(&a, &far);
+        assert!(moved.significant);
+        assert!(moved.dp < 0.0 && moved.z < -3.0);
+        assert!((moved.dfair - (far.fair - a.fair)).abs() < 1e-12);
+        
+        // Round-trips through JSON the way the wasm entry point sees it
+        let parsed: SimResult = serde_json::from_str(&serde_json::to_string(&a).unwrap()).unwrap();
+        assert_eq!(diff_sim_results(&a, &parsed).dp, 0.0);
+    }
+    
+    #[test]
+    fn test_hourly_candles() {
+        let mut inputs = test_inputs();
+        inputs.dt = 0.25;
+        let engine = seeded_engine(inputs, 1);
+        
+        let candles = engine.seeded_candles(99);
+        assert_eq!(candles.len(), 24);
+        assert_eq!(candles[0].open, 100000.0);
+        for (h, candle) in candles.iter().enumerate() {
+            assert_eq!(candle.hour, h as u32);
+            assert!(candle.high >= candle.open.max(candle.close));
+            assert!(candle.low <= candle.open.min(candle.close));
+            if h > 0 {
+                assert_eq!(candle.open, candles[h - 1].close);
+            }
+        }
+        
+        // Deterministic per seed
+        let again = engine.seeded_candles(99);
+        assert_eq!(again.last().unwrap().close, candles.last().unwrap().close);
+        
+        // Coarser steps than an hour leave flat filler candles
+        let sparse = hourly_candles(&[
+            PathPoint { t: 0.0, price: 1.0 },
+            PathPoint { t: 2.0, price: 3.0 },
+        ]);
+        assert_eq!(sparse.len(), 2);
+        assert_eq!((sparse[0].high, sparse[0].close), (1.0, 1.0));
+        assert_eq!((sparse[1].open, sparse[1].high, sparse[1].close), (1.0, 3.0, 3.0));
+    }
+    
+    #[test]
+    fn test_vol_shock_scales_diffusion() {
+        let log_return_std = |shocks: Vec<VolShock>| {
+            let mut inputs = diffusion_inputs();
+            inputs.vol_shocks = shocks;
+            let mut engine = seeded_engine(inputs, 21);
+            
+            let returns: Vec<f64> = engine
+                .simulate_terminal_prices(4000)
+                .unwrap()
+                .iter()
+                .map(|s| (s / 100000.0).ln())
+                .collect();
+            let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+            (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / returns.len() as f64).sqrt()
+        };
+        
+        let base = log_return_std(Vec::new());
+        let doubled = log_return_std(vec![VolShock { start: 0.0, end: 24.0, vol_multiplier: 2.0 }]);
+        assert!((doubled / base - 2.0).abs() < 0.15);
+        
+        // A one-hour spike only adds that hour's extra variance
+        let spike = log_return_std(vec![VolShock { start: 4.0, end: 5.0, vol_multiplier: 2.0 }]);
+        let expected = base * (27.0f64 / 24.0).sqrt();
+        assert!((spike / expected - 1.0).abs() < 0.08);
+        
+        let mut inputs = test_inputs();
+        inputs.vol_shocks = vec![VolShock { start: 5.0, end: 4.0, vol_multiplier: 2.0 }];
+        assert!(MonteCarloEngine::from_inputs(inputs, 0).is_err());
+    }
+    
+    #[test]
+    fn test_non_finite_results_are_rejected() {
+        let mut engine = seeded_engine(test_inputs(), 4);
+        let mut result = engine.simulate(above(100000.0), 200).unwrap();
+        assert!(ensure_finite(&result).is_ok());
+        
+        result.diagnostics.stderr = f64::NAN;
+        let err = ensure_finite(&result).unwrap_err();
+        assert!(err.contains("diagnostics.stderr"));
+        
+        // Every optional section is checked too, control variates attached after the run included
+        let mut result = engine.simulate_control_variate(above(100000.0), 200).unwrap();
+        assert!(ensure_finite(&result).is_ok());
+        result.control_variate.as_mut().unwrap().beta = f64::NAN;
+        assert!(ensure_finite(&result).unwrap_err().contains("control_variate.beta"));
+        
+        let mut result = engine.simulate(above(100000.0), 200).unwrap();
+        result.terminal_stats.as_mut().unwrap().p95 = f64::INFINITY;
+        assert!(ensure_finite(&result).unwrap_err().contains("terminal_stats.p95"));
+        result.terminal_stats = None;
+        result.diagnostics.convergence.as_mut().unwrap()[3] = f64::NAN;
+        assert!(ensure_finite(&result).unwrap_err().contains("diagnostics.convergence[3]"));
+        
+        let mut acc = RunAccumulator::new(1);
+        let outcome = PathOutcome { initial_price: 1.0, final_price: f64::INFINITY, ..Default::default() };
+        assert!(acc.record(7, &outcome, None, false).unwrap_err().contains("Path 7"));
+        // the check terminal-price helpers (quantiles, risk metrics, ladders) share
+        assert!(checked_final_price(7, &outcome).unwrap_err().contains("Path 7"));
+    }
+    
+    #[test]
+    fn test_smart_run_picks_strategy() {
+        let mut engine = seeded_engine(diffusion_inputs(), 31);
+        
+        let near = engine.smart_simulate(above(100000.0), 4000).unwrap();
+        assert_eq!(near.strategy, "antithetic");
+        assert_eq!(near.result.diagnostics.n, 4000);
+        
+        // Mirrored shocks make the near-money digital pairs strongly anti-correlated
+        let plain = engine.simulate(above(100000.0), 4000).unwrap();
+        assert!(near.result.diagnostics.stderr < 0.8 * plain.diagnostics.stderr);
+        
+        let tail = engine.smart_simulate(above(150000.0), 1000).unwrap();
+        assert_eq!(tail.strategy, "importance");
+        assert!(tail.reason.contains("tilted"));
+        
+        let mut fat_tailed = diffusion_inputs();
+        fat_tailed.innovation = "student_t".to_string();
+        fat_tailed.df = 5.0;
+        let tail = seeded_engine(fat_tailed, 31).smart_simulate(above(150000.0), 1000).unwrap();
+        assert_eq!(tail.strategy, "plain");
+        assert!(tail.reason.contains("importance sampling needs"));
+        
+        let range = Target { kind: "range".to_string(), L: Some(99900.0), U: Some(100100.0), ..Default::default() };
+        assert_eq!(engine.smart_simulate(range, 1000).unwrap().strategy, "plain");
+    }
+    
+    #[test]
+    fn test_seeded_engines_are_reproducible() {
+        let runs = |seed: u64| {
+            let mut engine = seeded_engine(test_inputs(), seed);
+            let first = engine.simulate(above(100000.0), 500).unwrap();
+            let second = engine.simulate(above(100000.0), 500).unwrap();
+            [(first.p, first.ci), (second.p, second.ci)]
+        };
+        
+        // Consecutive runs advance the stream; a fresh engine with the seed replays it
+        let a = runs(2024);
+        assert_ne!(a[0], a[1]);
+        assert_eq!(a, runs(2024));
+        assert_ne!(a, runs(2025));
+    }
+    
+    #[test]
+    fn test_rng_state_resumes_a_split_run() {
+        let target = above(100000.0);
+        let mut straight = seeded_engine(test_inputs(), 91);
+        let first = straight.simulate(target.clone(), 1500).unwrap();
+        let second = straight.simulate(target.clone(), 1500).unwrap();
+        
+        let mut engine = seeded_engine(test_inputs(), 91);
+        let before = engine.simulate(target.clone(), 1500).unwrap();
+        let state: RngState = serde_json::from_str(&serde_json::to_string(&engine.rng_state()).unwrap()).unwrap();
+        drop(engine);
+        
+        let mut resumed = MonteCarloEngine::from_state(test_inputs(), &state).unwrap();
+        let after = resumed.simulate(target, 1500).unwrap();
+        assert_eq!(before.hits + after.hits, first.hits + second.hits);
+        assert_eq!(after.p, second.p);
+        assert_eq!(resumed.rng_state(), straight.rng_state());
+        
+        let bad = RngState { word_pos: "-1".to_string(), ..state };
+        assert!(MonteCarloEngine::from_state(test_inputs(), &bad).is_err());
+    }
+    
+    #[test]
+    fn test_config_round_trip_reproduces_results() {
+        let mut inputs = test_inputs();
+        inputs.vol_shocks = vec![VolShock { start: 2.0, end: 3.0, vol_multiplier: 1.5 }];
+        let mut original = seeded_engine(inputs, 77);
+        
+        let blob = serde_json::to_string(&original.config()).unwrap();
+        let config: EngineConfig = serde_json::from_str(&blob).unwrap();
+        let mut imported = MonteCarloEngine::from_config(config.clone()).unwrap();
+        
+        let a = original.simulate(above(100000.0), 500).unwrap();
+        let b = imported.simulate(above(100000.0), 500).unwrap();
+        assert_eq!(a.p, b.p);
+        assert_eq!(a.distribution.unwrap().mean, b.distribution.unwrap().mean);
+        
+        let future = EngineConfig { version: CONFIG_VERSION + 1, ..config };
+        assert!(MonteCarloEngine::from_config(future).is_err());
+    }
+    
+    #[test]
+    fn test_hours_until_expiry() {
+        assert_eq!(hours_between("2025-03-03T04:00:00Z", "2025-03-01T16:00:00Z").unwrap(), 36.0);
+        // Offsets are honoured: 20:00 at -05:00 is 01:00Z the next day
+        assert_eq!(hours_between("2025-03-02T20:00:00-05:00", "2025-03-01T16:00:00Z").unwrap(), 33.0);
+        assert_eq!(hours_between("2025-03-01T16:30:00Z", "2025-03-01T16:00:00Z").unwrap(), 0.5);
+        
+        assert!(hours_between("tomorrow", "2025-03-01T16:00:00Z").is_err());
+        assert!(hours_between("2025-03-03T04:00:00Z", "2025-03-01 16:00").is_err());
+    }
+    
+    #[test]
+    fn test_build_info() {
+        let info = build_info();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.target, "native");
+        assert!(info.parallel);
+        assert_eq!(info.variance_reduction, ["antithetic", "control_variate", "qmc", "importance"]);
+    }
+    
+    #[test]
+    fn test_rolling_window_tracks_recent_paths() {
+        let mut engine = seeded_engine(diffusion_inputs(), 8);
+        assert!(engine.simulate_rolling(above(100000.0), 10).is_err());
+        
+        engine.set_window(500).unwrap();
+        let partial = engine.simulate_rolling(above(100000.0), 200).unwrap();
+        assert_eq!((partial.effective_n, partial.total_paths), (200, 200));
+        
+        let full = engine.simulate_rolling(above(100000.0), 400).unwrap();
+        assert_eq!((full.effective_n, full.window_size, full.total_paths), (500, 500, 600));
+        assert!((full.p - 0.5).abs() < 0.1);
+        
+        // After a parameter change the window fully turns over to the new regime
+        let mut bullish = diffusion_inputs();
+        bullish.s0 = 101000.0;
+        engine.replace_inputs(bullish).unwrap();
+        let shifted = engine.simulate_rolling(above(100000.0), 500).unwrap();
+        assert_eq!(shifted.p, 1.0);
+        
+        // A new target restarts the window
+        let other = engine.simulate_rolling(above(200000.0), 50).unwrap();
+        assert_eq!((other.effective_n, other.total_paths, other.p), (50, 50, 0.0));
+        
+        assert_eq!(engine.config().rolling_window, Some(500));
+    }
+    
+    #[test]
+    fn test_realized_vol_target() {
+        let mut engine = seeded_engine(diffusion_inputs(), 5);
+        
+        // Realized vol of every path sits near the annualized per-step vol
+        let mut trace = Vec::new();
+        let outcome = engine.model.simulate_path(&mut engine.rng, Some(&mut trace));
+        let returns: Vec<f64> = trace.windows(2).map(|w| (w[1].price / w[0].price).ln()).collect();
+        let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+        let sd = (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len() as f64 - 1.0)).sqrt();
+        assert!((outcome.realized_vol - sd * utils::HOURS_PER_YEAR.sqrt()).abs() < 1e-9);
+        
+        let vol_target = |vol: f64| Target { kind: "realized_vol_above".to_string(), vol: Some(vol), ..Default::default() };
+        let median_vol = outcome.realized_vol;
+        assert_eq!(engine.simulate(vol_target(median_vol * 10.0), 200).unwrap().p, 0.0);
+        assert_eq!(engine.simulate(vol_target(median_vol / 10.0), 200).unwrap().p, 1.0);
+        
+        let missing = Target { kind: "realized_vol_above".to_string(), ..Default::default() };
+        assert!(engine.simulate(missing, 10).is_err());
+        
+        // Under a dt_schedule each return is scaled by its own step, so constant-vol GBM recovers
+        // its vol on the mixed grid too
+        let mixed = SimInputs { dt_schedule: vec![(0.0, 1.0), (12.0, 0.25)], ..gbm_inputs() };
+        let mut engine = seeded_engine(mixed, 6);
+        let mut trace = Vec::new();
+        let outcome = engine.model.simulate_path(&mut engine.rng, Some(&mut trace));
+        let scaled: Vec<f64> = trace.windows(2).map(|w| (w[1].price / w[0].price).ln() / (w[1].t - w[0].t).sqrt()).collect();
+        let mean = scaled.iter().sum::<f64>() / scaled.len() as f64;
+        let sd = (scaled.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (scaled.len() as f64 - 1.0)).sqrt();
+        assert!((outcome.realized_vol - sd * utils::HOURS_PER_YEAR.sqrt()).abs() < 1e-9);
+        
+        let vol = (gbm_inputs().regimes[BULL].heston.theta * utils::HOURS_PER_YEAR).sqrt();
+        let vols: Vec<f64> = (0..400).map(|_| engine.model.simulate_path(&mut engine.rng, None).realized_vol).collect();
+        let mean_vol = vols.iter().sum::<f64>() / vols.len() as f64;
+        assert!((mean_vol / vol - 1.0).abs() < 0.02, "realized {} vs {}", mean_vol, vol);
+    }
+    
+    #[test]
+    fn test_dt_convergence_study() {
+        let mut engine = seeded_engine(gbm_inputs(), 13);
+        
+        let study = engine.dt_study(above(100500.0), &[1.0, 0.5, 0.25], 2000).unwrap();
+        assert_eq!(study.rows.len(), 3);
+        assert_eq!(study.rows[1].dt, 0.5);
+        
+        // GBM with exact log steps has no discretization bias: all dts agree within noise
+        let p0 = study.rows[0].p;
+        for row in &study.rows {
+            assert!((row.p - p0).abs() < 4.0 * row.stderr);
+        }
+        
+        assert!(engine.dt_study(above(100500.0), &[1.0, 0.0], 100).is_err());
+        assert!(engine.dt_study(above(100500.0), &[], 100).is_err());
+    }
+    
+    #[test]
+    fn test_theta_decay_curve() {
+        let mut engine = seeded_engine(gbm_inputs(), 17);
+        
+        // Out of the money: less time left means less chance to get there
+        let curve = engine.theta_decay(above(101000.0), &[24.0, 6.0, 1.0], 2000).unwrap();
+        assert_eq!(curve.points.len(), 3);
+        assert_eq!(curve.points[1].t, 6.0);
+        assert!(curve.points[0].fair > curve.points[1].fair);
+        assert!(curve.points[1].fair > curve.points[2].fair);
+        
+        assert!(engine.theta_decay(above(101000.0), &[1.0, -1.0], 100).is_err());
+        assert!(engine.theta_decay(above(101000.0), &[], 100).is_err());
+    }
+    
+    #[test]
+    fn test_stderr_curve_decays() {
+        let mut engine = seeded_engine(test_inputs(), 95);
+        let curve = engine.stderr_by_path_count(above(100000.0), 5000).unwrap();
+        
+        let counts: Vec<u32> = curve.iter().map(|&(n, _)| n).collect();
+        assert_eq!(counts, vec![100, 300, 1000, 3000, 5000]);
+        assert!(curve[curve.len() - 1].1 < curve[0].1);
+        // Roughly 1/sqrt(N): 50x the paths, about a seventh of the stderr
+        let ratio = curve[0].1 / curve[curve.len() - 1].1;
+        assert!(ratio > 5.0 && ratio < 9.0, "{}", ratio);
+        
+        // A target no path reaches still reports the uncertainty its run would
+        let far = above(1e7);
+        let curve = seeded_engine(test_inputs(), 95).stderr_by_path_count(far.clone(), 1000).unwrap();
+        let run = seeded_engine(test_inputs(), 95).simulate(far, 1000).unwrap();
+        assert!(curve.iter().all(|&(_, stderr)| stderr > 0.0));
+        assert!(curve.windows(2).all(|pair| pair[1].1 < pair[0].1));
+        assert_eq!(curve[curve.len() - 1].1, run.diagnostics.stderr);
+    }
+    
+    #[test]
+    fn test_convergence_trace() {
+        let mut engine = seeded_engine(test_inputs(), 73);
+        let result = engine.simulate(above(100000.0), 1000).unwrap();
+        
+        let trace = result.diagnostics.convergence.unwrap();
+        assert_eq!(trace.len(), CONVERGENCE_CHECKPOINTS);
+        assert!((trace.last().unwrap() - result.p).abs() < 1e-12);
+        
+        // First checkpoint is the estimate after 50 paths: a shorter run on the same stream
+        let head = seeded_engine(test_inputs(), 73).simulate(above(100000.0), 50).unwrap();
+        assert_eq!(trace[0], head.p);
+        
+        let tiny = engine.simulate(above(100000.0), 7).unwrap();
+        let trace = tiny.diagnostics.convergence.unwrap();
+        assert!(trace.len() <= 7);
+        assert!((trace.last().unwrap() - tiny.p).abs() < 1e-12);
+    }
+    
+    #[test]
+    fn test_weighted_accumulator() {
+        let outcome = PathOutcome { final_price: 100000.0, ..Default::default() };
+        let hits = [true, false, true, true, false, false, true, false, false, false];
+        
+        // Unit weights reproduce the plain binomial estimator
+        let mut acc = RunAccumulator::new(hits.len());
+        for (i, &hit) in hits.iter().enumerate() {
+            acc.record(i as u32, &outcome, None, hit).unwrap();
+        }
+        assert_eq!(acc.p(), 0.4);
+        assert!((acc.stderr() - utils::binomial_stderr(0.4, 10)).abs() < 1e-15);
+        assert_eq!(acc.ci(), utils::binomial_ci(4, 10, 0.95));
+        
+        // Doubling the hit weights: p = 8 / 14, stderr from the weighted residuals
+        let mut acc = RunAccumulator::new(hits.len());
+        for (i, &hit) in hits.iter().enumerate() {
+            acc.record_weighted(i as u32, &outcome, None, hit, if hit { 2.0 } else { 1.0 }).unwrap();
+        }
+        let p = 8.0 / 14.0;
+        let spread = 4.0 * 4.0 * (1.0 - p) * (1.0 - p) + 6.0 * p * p;
+        assert!((acc.p() - p).abs() < 1e-15);
+        assert!((acc.stderr() - spread.sqrt() / 14.0).abs() < 1e-15);
+        assert!(acc.ci()[0] < p && p < acc.ci()[1]);
+        
+        // As likelihood ratios the same weights average over the 10 paths: p = 8 / 10
+        let mut ratios = RunAccumulator::with_likelihood_ratios(hits.len());
+        for (i, &hit) in hits.iter().enumerate() {
+            ratios.record_weighted(i as u32, &outcome, None, hit, if hit { 2.0 } else { 1.0 }).unwrap();
+        }
+        let spread: f64 = 4.0 * (2.0 - 0.8) * (2.0 - 0.8) + 6.0 * 0.8 * 0.8;
+        assert!((ratios.p() - 0.8).abs() < 1e-15);
+        assert!((ratios.stderr() - spread.sqrt() / 10.0).abs() < 1e-15);
+        assert_eq!(ratios.convergence().unwrap().last(), Some(&ratios.p()));
+        
+        assert!(acc.record_weighted(10, &outcome, None, true, -1.0).is_err());
+        assert!(acc.record_weighted(10, &outcome, None, true, f64::NAN).is_err());
+    }
+    
+    #[test]
+    fn test_regime_occupancy_drifts_to_stationary() {
+        let mut inputs = test_inputs();
+        inputs.t = 48.0;
+        inputs.hmm.pi0 = vec![1.0, 0.0];
+        let mut engine = seeded_engine(inputs, 19);
+        
+        let occupancy = engine.simulate_regime_occupancy(4000).unwrap();
+        assert_eq!(occupancy.times.len(), occupancy.occupancy.len());
+        assert_eq!(occupancy.occupancy[0], [1.0, 0.0]);
+        
+        // After many switching times the mix sits at the stationary distribution
+        let stationary = occupancy.stationary.unwrap();
+        let last = occupancy.occupancy.last().unwrap();
+        assert!((last[0] - stationary[0]).abs() < 0.04);
+        assert!(occupancy.occupancy.iter().all(|row| (row[0] + row[1] - 1.0).abs() < 1e-12));
+    }
+    
+    #[test]
+    fn test_implied_vol_recovers_flat_vol() {
+        let engine = seeded_engine(gbm_inputs(), 29);
+        
+        // Both branches: out of the money (rising in vol) and in the money (falling)
+        for strike in [101000.0, 99000.0] {
+            let cents = 100.0 * bs_digital_above(100000.0, strike, 0.6, 24.0, 0.0);
+            let implied = engine.implied_vol(above(strike), cents).unwrap();
+            assert!((implied.implied_vol - 0.6).abs() < 1e-9);
+        }
+        
+        // Pinned variance: the model's own vol is sqrt(theta) annualized
+        let implied = engine.implied_vol(above(101000.0), 30.0).unwrap();
+        assert!((implied.model_vol - (1e-4 * utils::HOURS_PER_YEAR).sqrt()).abs() < 1e-12);
+        assert!((implied.vol_ratio - implied.implied_vol / implied.model_vol).abs() < 1e-12);
+        
+        // An out-of-the-money digital can't trade above its peak over all vols
+        assert!(engine.implied_vol(above(101000.0), 60.0).is_err());
+        assert!(engine.implied_vol(above(99000.0), 100.0).is_err());
+    }
+    
+    #[test]
+    fn test_aborted_run_returns_partial_result() {
+        let mut engine = seeded_engine(test_inputs(), 31);
+        
+        let stop_after = 3 * STOP_CHECK_INTERVAL;
+        let result = engine.simulate_interruptible(above(100000.0), 10_000, |done| Ok(done >= stop_after)).unwrap();
+        assert!(result.incomplete);
+        assert_eq!(result.diagnostics.n, stop_after);
+        assert!(result.ci[0] <= result.p && result.p <= result.ci[1]);
+        
+        // Same stream prefix: the partial run is exactly a shorter complete run
+        let full = seeded_engine(test_inputs(), 31).simulate(above(100000.0), stop_after).unwrap();
+        assert!(!full.incomplete);
+        assert_eq!(full.p, result.p);
+        
+        // A zero budget still completes the first block of paths
+        let result = engine.simulate_interruptible(above(100000.0), 10_000, |_| Ok(true)).unwrap();
+        assert_eq!(result.diagnostics.n, STOP_CHECK_INTERVAL);
+        
+        let failing = engine.simulate_interruptible(above(100000.0), 10_000, |_| Err("cancel check failed".to_string()));
+        assert!(failing.is_err());
+    }
+    
+    #[test]
+    fn test_simulator_matches_bs_digital() {
+        let mut inputs = gbm_inputs();
+        for params in inputs.regimes.iter_mut() {
+            params.mu = 0.0001;
+        }
+        let (s0, t, k) = (inputs.s0, inputs.t, 100500.0);
+        let vol = (inputs.regimes[BULL].heston.theta * utils::HOURS_PER_YEAR).sqrt();
+        let exact = bs_digital_above(s0, k, vol, t, 0.0001 * utils::HOURS_PER_YEAR);
+        
+        let result = seeded_engine(inputs, 49).simulate(above(k), 20000).unwrap();
+        let stderr = result.diagnostics.stderr;
+        assert!((result.p - exact).abs() < 3.0 * stderr, "p {} vs analytic {} (stderr {})", result.p, exact, stderr);
+        
+        // The drift moves the analytic value by more than the tolerance, so the check has teeth
+        assert!((bs_digital_above(s0, k, vol, t, 0.0) - exact).abs() > 3.0 * stderr);
+    }
+    
+    #[test]
+    fn test_importance_sampling_prices_the_tail() {
+        // Constant variance, no drift or jumps: a strike near 3.5 sd out has p ~ 2e-4
+        let inputs = gbm_inputs();
+        let (s0, t) = (inputs.s0, inputs.t);
+        let vol = (inputs.regimes[BULL].heston.theta * utils::HOURS_PER_YEAR).sqrt();
+        let k = s0 * (3.5 * vol * (t / utils::HOURS_PER_YEAR).sqrt()).exp();
+        let exact = bs_digital_above(s0, k, vol, t, 0.0);
+        
+        let tilted = seeded_engine(gbm_inputs(), 82).simulate_importance(above(k), 4000, 3.5).unwrap();
+        assert!((tilted.p - exact).abs() < 3.0 * tilted.diagnostics.stderr, "{} vs {}", tilted.p, exact);
+        // Far tighter than the binomial stderr of plain sampling at the same size
+        assert!(tilted.diagnostics.stderr < 0.2 * utils::binomial_stderr(exact, 4000));
+        assert!(tilted.diagnostics.replay_seed.is_some());
+        
+        // A zero tilt is plain sampling on the same paths
+        let plain = seeded_engine(gbm_inputs(), 82).simulate(above(s0), 500).unwrap();
+        assert_eq!(seeded_engine(gbm_inputs(), 82).simulate_importance(above(s0), 500, 0.0).unwrap().p, plain.p);
+        
+        // The smart run tilts downside tails the other way
+        let low = s0 * s0 / k;
+        let smart = seeded_engine(gbm_inputs(), 83).smart_simulate(Target { kind: "below".to_string(), K: Some(low), ..Default::default() }, 4000).unwrap();
+        let exact_below = 1.0 - bs_digital_above(s0, low, vol, t, 0.0);
+        assert_eq!(smart.strategy, "importance");
+        assert!((smart.result.p - exact_below).abs() < 3.0 * smart.result.diagnostics.stderr, "{} vs {}", smart.result.p, exact_below);
+        assert!(smart.result.diagnostics.stderr < 0.2 * utils::binomial_stderr(exact_below, 4000));
+        
+        assert!(seeded_engine(gbm_inputs(), 82).simulate_importance(above(k), 100, f64::NAN).is_err());
+        assert!(seeded_engine(basket_inputs(0.5), 82).simulate_importance(above(k), 100, 1.0).is_err());
+    }
+    
+    #[test]
+    fn test_qmc_beats_pseudo_random() {
+        // Constant variance, no drift or jumps: the closed-form digital is the infinite-N reference
+        let inputs = gbm_inputs();
+        let (s0, t) = (inputs.s0, inputs.t);
+        let vol = (inputs.regimes[BULL].heston.theta * utils::HOURS_PER_YEAR).sqrt();
+        let exact = bs_digital_above(s0, s0 * 1.005, vol, t, 0.0);
+        
+        let error = |qmc: bool, seed: u64| {
+            let inputs = SimInputs { qmc, ..gbm_inputs() };
+            (seeded_engine(inputs, seed).simulate(above(s0 * 1.005), 1024).unwrap().p - exact).abs()
+        };
+        let qmc_error: f64 = (0..4).map(|seed| error(true, seed)).sum();
+        let pseudo_error: f64 = (0..4).map(|seed| error(false, seed)).sum();
+        assert!(qmc_error < 0.5 * pseudo_error, "qmc {} vs pseudo-random {}", qmc_error, pseudo_error);
+        
+        // Replay reproduces the QMC paths
+        let mut engine = seeded_engine(SimInputs { qmc: true, ..gbm_inputs() }, 5);
+        let result = engine.simulate(above(s0), 20).unwrap();
+        let replay_seed = result.diagnostics.replay_seed.unwrap();
+        let steps = engine.replay(replay_seed, 3).unwrap();
+        assert_eq!(steps.last().unwrap().s, run_path(&engine.model, replay_seed, 3, None, None).final_price);
+        assert!(Model::new(SimInputs { qmc: true, ..basket_inputs(0.5) }).is_err());
+        
+        // The antithetic twin mirrors the Sobol shocks: with constant variance the two log-returns
+        // sum to the doubled drift, -theta * t
+        let theta = inputs.regimes[BULL].heston.theta;
+        let mut shocks = ShockBuffer::new();
+        let first = run_recorded_path(&engine.model, replay_seed, 3, &mut shocks, None, None);
+        let twin = replay_path(&engine.model, replay_seed, 3, &shocks, true, None, None);
+        let log_sum = (first.final_price / s0).ln() + (twin.final_price / s0).ln();
+        assert!((log_sum + theta * t).abs() < 1e-9, "log-returns sum to {}", log_sum);
+        
+        // and antithetic runs, on QMC paths too, are replayable
+        let paired = engine.simulate_antithetic(above(s0 * 1.005), 1024).unwrap();
+        assert!(paired.diagnostics.replay_seed.is_some());
+        assert!((paired.p - exact).abs() < 3.0 * paired.diagnostics.stderr.max(1.0 / 1024.0));
+        assert!(engine.set_window(10).is_ok());
+        assert!(engine.simulate_rolling(above(s0), 10).is_err());
+    }
+    
+    #[test]
+    fn test_control_variate_reduces_stderr() {
+        // Constant variance and rare small jumps: the GBM digital tracks the model indicator closely
+        let mut inputs = gbm_inputs();
+        inputs.jumps = JumpParams { lambda: 0.01, mu_j: 0.0, sigma_j: 0.01, ..inputs.jumps };
+        let s0 = inputs.s0;
+        let result = seeded_engine(inputs, 33).simulate_control_variate(above(s0 * 1.01), 4000).unwrap();
+        let cv = result.control_variate.unwrap();
+        
+        assert_eq!(cv.p_raw, result.p);
+        assert!(cv.beta > 0.5, "beta = {}", cv.beta);
+        assert!(cv.stderr < 0.5 * cv.stderr_raw, "cv {} vs raw {}", cv.stderr, cv.stderr_raw);
+        assert!((cv.p - cv.p_raw).abs() < 3.0 * cv.stderr_raw);
+        
+        // Jump-free GBM on a mixed grid: the control is the model's own terminal price, step for step
+        let scheduled = SimInputs { dt_schedule: vec![(0.0, 1.0), (12.0, 0.25)], ..gbm_inputs() };
+        let result = seeded_engine(scheduled, 34).simulate_control_variate(above(s0 * 1.01), 2000).unwrap();
+        let cv = result.control_variate.unwrap();
+        assert!(cv.stderr < 0.1 * cv.stderr_raw, "cv {} vs raw {}", cv.stderr, cv.stderr_raw);
+        
+        let mut engine = seeded_engine(diffusion_inputs(), 33);
+        assert!(engine.simulate_control_variate(Target { kind: "range".to_string(), ..Default::default() }, 100).is_err());
+        let fat = SimInputs { innovation: "student_t".to_string(), df: 5.0, ..diffusion_inputs() };
+        assert!(seeded_engine(fat, 33).simulate_control_variate(above(s0), 100).is_err());
+    }
+    
+    #[test]
+    fn test_simulate_until_stops_on_tolerance() {
+        let s0 = test_inputs().s0;
+        let mut engine = seeded_engine(test_inputs(), 32);
+        
+        let result = engine.simulate_until(above(s0 * 0.5), 50_000, 0.005).unwrap();
+        assert!(result.p > 0.99);
+        assert_eq!(result.diagnostics.n, CI_CHECK_INTERVAL);
+        assert!((result.ci[1] - result.ci[0]) / 2.0 <= 0.005);
+        
+        // An unreachable tolerance runs every path
+        let result = engine.simulate_until(above(s0), 1500, 1e-6).unwrap();
+        assert_eq!(result.diagnostics.n, 1500);
+        assert!(engine.simulate_until(above(s0), 1500, 0.0).is_err());
+    }
+    
+    #[test]
+    fn test_jump_intensity_sensitivity() {
+        let mut inputs = gbm_inputs();
+        inputs.jumps.lambda = 0.05;
+        inputs.jumps.mu_j = 0.0;
+        inputs.jumps.sigma_j = 0.05;
+        let mut engine = seeded_engine(inputs, 43);
+        
+        // Far out of the money, only jumps get the price there: more jumps, more value
+        let sensitivity = engine.jump_sensitivity(above(110000.0), 4000, 0.02).unwrap();
+        assert!((sensitivity.lambda_down - 0.03).abs() < 1e-12 && (sensitivity.lambda_up - 0.07).abs() < 1e-12);
+        assert!(sensitivity.dfair_dlambda > 3.0 * sensitivity.stderr);
+        
+        // Bump larger than lambda: forward difference from lambda itself
+        let forward = engine.jump_sensitivity(above(110000.0), 200, 0.1).unwrap();
+        assert_eq!(forward.lambda_down, 0.05);
+        
+        assert!(engine.jump_sensitivity(above(110000.0), 200, 0.0).is_err());
+    }
+    
+    #[test]
+    fn test_jump_substeps() {
+        // Two jumps an hour at hourly steps
+        let mut inputs = gbm_inputs();
+        inputs.jumps = JumpParams { lambda: 2.0, mu_j: -0.01, sigma_j: 0.02, ..inputs.jumps };
+        let s0 = inputs.s0;
+        
+        // A threshold the step never reaches leaves the paths untouched
+        let plain = seeded_engine(inputs.clone(), 50).simulate_terminal_prices(300).unwrap();
+        let loose = SimInputs { jump_substep_threshold: Some(5.0), ..inputs.clone() };
+        assert_eq!(seeded_engine(loose, 50).simulate_terminal_prices(300).unwrap(), plain);
+        
+        // Eight substeps a step: the compensated price stays a martingale, E[S_T] = s0 with mu = 0
+        let fine = SimInputs { jump_substep_threshold: Some(0.25), ..inputs.clone() };
+        let prices = seeded_engine(fine, 50).simulate_terminal_prices(6000).unwrap();
+        let n = prices.len() as f64;
+        let mean = prices.iter().sum::<f64>() / n;
+        let sd = (prices.iter().map(|p| (p - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt();
+        assert!((mean - s0).abs() < 3.0 * sd / n.sqrt(), "mean {} vs {}", mean, s0);
+        
+        assert!(Model::new(SimInputs { jump_substep_threshold: Some(0.0), ..inputs }).is_err());
+    }
+    
+    #[test]
+    fn test_hawkes_jumps_cluster() {
+        let mut inputs = gbm_inputs();
+        inputs.dt = 0.25;
+        inputs.jumps = JumpParams { lambda: 0.1, mu_j: 0.0, sigma_j: 0.01, ..inputs.jumps };
+        
+        // Steps with a jump per path; quarter-hour steps rarely hold two
+        let jump_counts = |inputs: SimInputs| -> (f64, f64) {
+            let model = Model::new(inputs).unwrap();
+            let counts: Vec<f64> = (0..4000)
+                .map(|i| {
+                    let mut steps = Vec::new();
+                    model.simulate_path_steps(&mut path_rng(17, i), &mut steps, None).unwrap();
+                    steps.iter().filter(|step| step.jump_occurred).count() as f64
+                })
+                .collect();
+            let n = counts.len() as f64;
+            let mean = counts.iter().sum::<f64>() / n;
+            (mean, counts.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / (n - 1.0))
+        };
+        
+        // Poisson counts have variance equal to the mean; self-excitation overdisperses them
+        let (mean, variance) = jump_counts(inputs.clone());
+        assert!(variance / mean < 1.1, "baseline dispersion {}", variance / mean);
+        let clustered = JumpParams { alpha: 0.6, beta: 1.0, ..inputs.jumps.clone() };
+        let (mean, variance) = jump_counts(SimInputs { jumps: clustered.clone(), ..inputs.clone() });
+        assert!(variance / mean > 1.5, "hawkes dispersion {}", variance / mean);
+        
+        // alpha = 0 is the constant-intensity process, draw for draw
+        let flat = SimInputs { jumps: JumpParams { alpha: 0.0, beta: 3.0, ..inputs.jumps.clone() }, ..inputs.clone() };
+        let expected = seeded_engine(inputs.clone(), 18).simulate_terminal_prices(200).unwrap();
+        assert_eq!(seeded_engine(flat, 18).simulate_terminal_prices(200).unwrap(), expected);
+        
+        let explosive = JumpParams { beta: 0.5, ..clustered };
+        assert!(Model::new(SimInputs { jumps: explosive, ..inputs }).is_err());
+    }
+    
+    #[test]
+    fn test_dt_schedule() {
+        // A one-segment schedule at the base dt is the constant-dt grid
+        let uniform = SimInputs { dt_schedule: vec![(0.0, 1.0)], ..test_inputs() };
+        let expected = seeded_engine(test_inputs(), 46).simulate_terminal_prices(200).unwrap();
+        assert_eq!(seeded_engine(uniform, 46).simulate_terminal_prices(200).unwrap(), expected);
+        
+        // Quarter-hour steps over the last hour
+        let fine_end = SimInputs { dt_schedule: vec![(0.0, 1.0), (23.0, 0.25)], ..test_inputs() };
+        let model = Model::new(fine_end.clone()).unwrap();
+        assert_eq!(model.step_sizes().len(), 23 + 4);
+        assert!((model.horizon() - 24.0).abs() < 1e-12);
+        
+        let engine = seeded_engine(fine_end, 46);
+        let steps = engine.replay(7, 0).unwrap();
+        assert_eq!(steps[22].t, 23.0);
+        assert_eq!(steps[23].t, 23.25);
+        
+        for schedule in [vec![(1.0, 1.0)], vec![(0.0, 1.0), (0.0, 0.5)], vec![(0.0, 1.0), (30.0, 0.5)], vec![(0.0, -1.0)]] {
+            assert!(Model::new(SimInputs { dt_schedule: schedule, ..test_inputs() }).is_err());
+        }
+    }
+    
+    #[test]
+    fn test_risk_neutral_drift() {
+        // Zero variance (full truncation keeps it at exactly 0) and no jumps: S_T = s0 e^(r t)
+        let mut inputs = diffusion_inputs();
+        for params in inputs.regimes.iter_mut() {
+            params.mu = 0.01;
+            params.heston.theta = 0.0;
+            params.heston.xi = 0.0;
+        }
+        inputs.variance_scheme = "full_truncation".to_string();
+        inputs.pricing_measure = "risk_neutral".to_string();
+        inputs.r = 0.0005;
+        let (s0, t) = (inputs.s0, inputs.t);
+        
+        let prices = seeded_engine(inputs.clone(), 45).simulate_terminal_prices(5).unwrap();
+        assert!(prices.iter().all(|price| (price / (s0 * (inputs.r * t).exp()) - 1.0).abs() < 1e-12));
+        
+        // The real-world measure keeps the regime mu
+        let real_world = SimInputs { pricing_measure: "real_world".to_string(), ..inputs.clone() };
+        let prices = seeded_engine(real_world, 45).simulate_terminal_prices(5).unwrap();
+        assert!(prices.iter().all(|price| (price / (s0 * (0.01 * t).exp()) - 1.0).abs() < 1e-12));
+        
+        assert!(Model::new(SimInputs { pricing_measure: "q".to_string(), ..inputs }).is_err());
+    }
+    
+    #[test]
+    fn test_student_t_innovations() {
+        // One hourly step of constant-vol GBM, so the terminal return is a single shock
+        let inputs = SimInputs { t: 1.0, ..gbm_inputs() };
+        let moments = |inputs: SimInputs| -> (f64, f64) {
+            let prices = seeded_engine(inputs.clone(), 48).simulate_terminal_prices(40000).unwrap();
+            let returns: Vec<f64> = prices.iter().map(|price| (price / inputs.s0).ln()).collect();
+            let n = returns.len() as f64;
+            let mean = returns.iter().sum::<f64>() / n;
+            let m2 = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / n;
+            let m4 = returns.iter().map(|r| (r - mean).powi(4)).sum::<f64>() / n;
+            (m2, m4 / (m2 * m2))
+        };
+        
+        let (normal_variance, normal_kurtosis) = moments(inputs.clone());
+        let fat = SimInputs { innovation: "student_t".to_string(), df: 5.0, ..inputs.clone() };
+        let (t_variance, t_kurtosis) = moments(fat.clone());
+        // t_5 has kurtosis 9 against 3, at the same unit variance
+        assert!((normal_kurtosis - 3.0).abs() < 0.2, "normal kurtosis {}", normal_kurtosis);
+        assert!(t_kurtosis > normal_kurtosis + 2.0, "t kurtosis {} vs {}", t_kurtosis, normal_kurtosis);
+        assert!((t_variance / normal_variance - 1.0).abs() < 0.05);
+        
+        assert!(Model::new(SimInputs { df: 2.0, ..fat.clone() }).is_err());
+        assert!(Model::new(SimInputs { qmc: true, ..fat }).is_err());
+        assert!(Model::new(SimInputs { innovation: "laplace".to_string(), ..inputs }).is_err());
+    }
+    
+    #[test]
+    fn test_dual_measure_run() {
+        let mut inputs = gbm_inputs();
+        for params in inputs.regimes.iter_mut() {
+            params.mu = 0.0002;
+        }
+        let target = above(100500.0);
+        
+        // r == mu: the two drifts coincide, so do the runs
+        inputs.r = 0.0002;
+        let dual = seeded_engine(inputs.clone(), 47).simulate_dual(target.clone(), 1000).unwrap();
+        assert_eq!(dual.real_world.hits, dual.risk_neutral.hits);
+        assert_eq!(
+            serde_json::to_string(&dual.real_world).unwrap(),
+            serde_json::to_string(&dual.risk_neutral).unwrap(),
+        );
+        
+        // A higher r lifts only the risk-neutral side, path for path
+        inputs.r = 0.001;
+        let higher = seeded_engine(inputs, 47).simulate_dual(target, 1000).unwrap();
+        assert_eq!(higher.real_world.hits, dual.real_world.hits);
+        assert!(higher.risk_neutral.hits > higher.real_world.hits);
+    }
+    
+    #[test]
+    fn test_implied_vol_scale() {
+        let target = above(103000.0);
+        let base = seeded_engine(gbm_inputs(), 93).simulate(target.clone(), 2000).unwrap();
+        
+        // Same engine state, so factor 1 replays the base paths exactly
+        let fit = seeded_engine(gbm_inputs(), 93).fit_vol_scale(target.clone(), base.p, 2000).unwrap();
+        assert!((fit.factor - 1.0).abs() < 0.05, "{}", fit.factor);
+        assert_eq!(fit.iterations, 0);
+        
+        // Out of the money, a richer quote needs more vol
+        let fit = seeded_engine(gbm_inputs(), 93).fit_vol_scale(target.clone(), base.p + 0.05, 2000).unwrap();
+        assert!(fit.factor > 1.1);
+        assert!((fit.p - (base.p + 0.05)).abs() < 0.01);
+        
+        assert!(seeded_engine(gbm_inputs(), 93).fit_vol_scale(target.clone(), 1.0, 2000).is_err());
+        
+        // A switch to the same parameter sets changes nothing, so the fit must scale them too
+        let switched = switch_to_same(gbm_inputs(), 0.0);
+        let fit = seeded_engine(switched, 93).fit_vol_scale(target.clone(), base.p + 0.05, 2000).unwrap();
+        let plain = seeded_engine(gbm_inputs(), 93).fit_vol_scale(target, base.p + 0.05, 2000).unwrap();
+        assert_eq!((fit.factor, fit.p), (plain.factor, plain.p));
+    }
+    
+    #[test]
+    fn test_greeks() {
+        let s0 = gbm_inputs().s0;
+        let mut engine = seeded_engine(gbm_inputs(), 44);
+        let greeks = engine.greeks(above(s0 * 1.01), 4000, 100.0, 0.05).unwrap();
+        
+        // Raising the spot raises an "above" digital, by less than its full 100 cents over the bump
+        assert!(greeks.delta > 3.0 * greeks.delta_stderr);
+        assert!(greeks.delta * 2.0 * greeks.ds <= 100.0);
+        // Out of the money, more vol means more chance of getting there
+        assert!(greeks.vega > 3.0 * greeks.vega_stderr);
+        
+        assert!(engine.greeks(above(s0), 100, 0.0, 0.05).is_err());
+        assert!(engine.greeks(above(s0), 100, 100.0, 10.0).is_err());
+        
+        // Two equal factors against one carrying their combined variance: vega is per unit of
+        // total vol, so both see the same bump, one larger than the first factor's vol alone
+        let mut double = gbm_inputs();
+        for params in double.regimes.iter_mut() {
+            params.heston2 = Some(params.heston.clone());
+        }
+        let mut single = gbm_inputs();
+        for params in single.regimes.iter_mut() {
+            params.heston.theta *= 2.0;
+            params.heston.var_floor *= 2.0;
+        }
+        let dvol = 1.0;
+        assert!((gbm_inputs().regimes[BULL].heston.theta * utils::HOURS_PER_YEAR).sqrt() < dvol);
+        let vega = |inputs: SimInputs| seeded_engine(inputs, 45).greeks(above(s0 * 1.01), 4000, 100.0, dvol).unwrap();
+        let (double, single) = (vega(double), vega(single));
+        let tolerance = 4.0 * (double.vega_stderr.powi(2) + single.vega_stderr.powi(2)).sqrt();
+        assert!((double.vega - single.vega).abs() < tolerance, "vega {} vs {}", double.vega, single.vega);
+    }
+    
+    #[test]
+    fn test_diagnose_degenerate_models() {
+        let codes = |inputs: &SimInputs| -> Vec<String> {
+            degenerate_warnings(inputs).into_iter().map(|warning| warning.code).collect()
+        };
+        
+        let healthy = test_inputs();
+        assert!(codes(&healthy).is_empty());
+        
+        // GBM in disguise: identical regimes, negligible vol of vol, variance floored at theta
+        let gbm = codes(&gbm_inputs());
+        for code in ["negligible_vol_of_vol", "variance_pinned_by_floor", "indistinguishable_regimes"] {
+            assert!(gbm.iter().any(|c| c == code), "missing {}", code);
+        }
+        
+        let mut wild = healthy.clone();
+        wild.jumps.lambda = 5.0;
+        wild.regimes[BULL].mu = 0.01;
+        let wild = codes(&wild);
+        for code in ["implausible_jump_intensity", "jumps_dominate", "drift_dominates_diffusion"] {
+            assert!(wild.iter().any(|c| c == code), "missing {}", code);
+        }
+    }
+    
+    #[test]
+    fn test_request_id_is_echoed() {
+        let result = seeded_engine(test_inputs(), 53).simulate(above(100000.0), 50).unwrap();
+        let value = serde_json::to_value(&result).unwrap();
+        
+        let tagged = tag_request_id(value.clone(), Some("worker-3/17"));
+        assert_eq!(tagged["request_id"], "worker-3/17");
+        assert_eq!(tagged["p"], value["p"]);
+        
+        // Untagged results are unchanged; arrays are tagged element by element
+        assert_eq!(tag_request_id(value.clone(), None), value);
+        let tagged = tag_request_id(serde_json::json!([{ "p": 0.1 }, { "p": 0.2 }]), Some("r"));
+        assert!(tagged.as_array().unwrap().iter().all(|entry| entry["request_id"] == "r"));
+    }
+    
+    /// Wraps another generator and counts the words drawn from it
+    struct CountingRng<R: rand::RngCore> {
+        inner: R,
+        draws: u64,
+    }
+    
+    impl<R: rand::RngCore> rand::RngCore for CountingRng<R> {
+        fn next_u32(&mut self) -> u32 {
+            self.draws += 1;
+            self.inner.next_u32()
+        }
+        
+        fn next_u64(&mut self) -> u64 {
+            self.draws += 1;
+            self.inner.next_u64()
+        }
+        
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            self.draws += 1;
+            self.inner.fill_bytes(dest)
+        }
+        
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+            self.draws += 1;
+            self.inner.try_fill_bytes(dest)
+        }
+    }
+    
+    #[test]
+    fn test_paths_accept_dyn_rng() {
+        use rand::SeedableRng;
+        let engine = seeded_engine(test_inputs(), 0);
+        
+        let mut generic = rand::rngs::StdRng::seed_from_u64(3);
+        let expected = engine.model.simulate_path(&mut generic, None).final_price;
+        
+        let mut counting = CountingRng { inner: rand::rngs::StdRng::seed_from_u64(3), draws: 0 };
+        let dyn_rng: &mut dyn rand::RngCore = &mut counting;
+        let outcome = engine.model.simulate_path(dyn_rng, None);
+        
+        assert_eq!(outcome.final_price, expected);
+        assert!(counting.draws > 24);
+    }
+    
+    #[test]
+    fn test_terminal_variance_check() {
+        // Variance pinned at theta: the terminal log-price variance must match theta * T
+        let mut engine = seeded_engine(gbm_inputs(), 17);
+        
+        let result = engine.simulate(above(100000.0), 5000).unwrap();
+        let check = result.diagnostics.terminal_variance.unwrap();
+        assert!((check.theoretical - 24.0 * 1e-4).abs() < 1e-12);
+        assert!((check.ratio - 1.0).abs() < 0.06);
+        
+        // Regime mixing and jumps have no closed form here
+        let mut engine = seeded_engine(test_inputs(), 17);
+        assert!(engine.simulate(above(100000.0), 100).unwrap().diagnostics.terminal_variance.is_none());
+    }
+    
+    #[test]
+    fn test_linear_payoff_is_monotonic_in_s0() {
+        let linear = Target { payoff: Some("linear".to_string()), ..above(100000.0) };
+        let expected = |s0: f64| {
+            let mut inputs = gbm_inputs();
+            inputs.s0 = s0;
+            seeded_engine(inputs, 81).simulate(linear.clone(), 2000).unwrap().payoff.unwrap()
+        };
+        
+        let payoffs: Vec<PayoffEstimate> = [98000.0, 99000.0, 100000.0, 101000.0].into_iter().map(expected).collect();
+        assert!(payoffs.windows(2).all(|pair| pair[0].expected < pair[1].expected));
+        assert!(payoffs.iter().all(|payoff| payoff.stderr > 0.0));
+        
+        // Binary stays the hit rate, with or without the explicit payoff
+        let plain = seeded_engine(gbm_inputs(), 81).simulate(above(100000.0), 2000).unwrap();
+        let binary = seeded_engine(gbm_inputs(), 81).simulate(Target { payoff: Some("binary".to_string()), ..above(100000.0) }, 2000).unwrap();
+        assert!(plain.payoff.is_none() && binary.payoff.is_none());
+        assert_eq!((plain.p, plain.fair), (binary.p, binary.fair));
+        
+        // A cap bounds the expectation, and bad fields are rejected
+        let capped = Target { payoff: Some("capped".to_string()), cap: Some(500.0), ..above(100000.0) };
+        let capped = seeded_engine(gbm_inputs(), 81).simulate(capped, 2000).unwrap().payoff.unwrap();
+        assert!(capped.expected <= 500.0 && capped.expected < payoffs[2].expected);
+        let outcome = PathOutcome { final_price: 100.0, ..Default::default() };
+        for bad in [
+            Target { payoff: Some("capped".to_string()), ..above(1.0) },
+            Target { payoff: Some("capped".to_string()), cap: Some(5.0), floor: Some(6.0), ..above(1.0) },
+            Target { payoff: Some("linear".to_string()), cap: Some(5.0), ..above(1.0) },
+            Target { payoff: Some("linear".to_string()), kind: "range".to_string(), ..above(1.0) },
+            Target { payoff: Some("quadratic".to_string()), ..above(1.0) },
+        ] {
+            assert!(target_hit(&bad, &outcome).is_err());
+        }
+    }
+    
+    #[test]
+    fn test_below_target() {
+        let below = |k: f64| Target { kind: "below".to_string(), K: Some(k), ..Default::default() };
+        let mut engine = seeded_engine(test_inputs(), 59);
+        let result = engine.simulate(below(100000.0), 2000).unwrap();
+        
+        // Same paths: below K and above K partition them (a tie has probability zero)
+        let mut replay = seeded_engine(test_inputs(), 59);
+        let complement = replay.simulate(above(100000.0), 2000).unwrap();
+        assert!((result.p + complement.p - 1.0).abs() < 1e-12);
+        
+        let batched = seeded_engine(test_inputs(), 59).simulate_batches(below(100000.0), 2000, 500, |_| Ok(())).unwrap();
+        assert_eq!(batched.p, result.p);
+        
+        let missing = Target { kind: "below".to_string(), ..Default::default() };
+        assert!(engine.simulate(missing.clone(), 10).is_err());
+        assert!(engine.simulate_batches(missing, 10, 5, |_| Ok(())).is_err());
+    }
+    
+    #[test]
+    fn test_batch_rejects_malformed_targets_like_single_runs() {
+        let mut engine = seeded_engine(test_inputs(), 67);
+        let malformed = [
+            Target { kind: "above".to_string(), ..Default::default() },
+            Target { kind: "range".to_string(), L: Some(99000.0), ..Default::default() },
+            Target { kind: "range".to_string(), U: Some(101000.0), ..Default::default() },
+        ];
+        
+        // An error, not a panic, with the very same message either way
+        for target in malformed {
+            let single = engine.simulate(target.clone(), 10).unwrap_err();
+            let batched = engine.simulate_batches(target, 10, 5, |_| Ok(())).unwrap_err();
+            assert_eq!(single, batched);
+        }
+    }
+    
+    #[test]
+    fn test_range_inclusivity() {
+        let at = |price: f64| PathOutcome { initial_price: 100.0, final_price: price, ..Default::default() };
+        let range = |l_inclusive: Option<bool>, u_inclusive: Option<bool>| Target {
+            kind: "range".to_string(),
+            L: Some(90.0),
+            U: Some(110.0),
+            l_inclusive,
+            u_inclusive,
+            ..Default::default()
+        };
+        
+        // Default [L, U]
+        assert!(target_hit(&range(None, None), &at(90.0)).unwrap());
+        assert!(target_hit(&range(None, None), &at(110.0)).unwrap());
+        
+        // (L, U]
+        assert!(!target_hit(&range(Some(false), None), &at(90.0)).unwrap());
+        assert!(target_hit(&range(Some(false), None), &at(110.0)).unwrap());
+        
+        // [L, U)
+        assert!(target_hit(&range(None, Some(false)), &at(90.0)).unwrap());
+        assert!(!target_hit(&range(None, Some(false)), &at(110.0)).unwrap());
+        assert!(target_hit(&range(Some(false), Some(false)), &at(100.0)).unwrap());
+        
+        let parsed: Target = serde_json::from_str(r#"{"kind":"range","K":null,"L":90,"U":110,"u_inclusive":false}"#).unwrap();
+        assert_eq!(parsed, range(None, Some(false)));
+    }
+    
+    #[test]
+    fn test_qe_branch_stats() {
+        let mut inputs = test_inputs();
+        inputs.warmup_hours = 6.0;
+        let mut engine = seeded_engine(inputs, 23);
+        
+        let stats = engine.simulate(above(100000.0), 300).unwrap().diagnostics.qe_branches.unwrap();
+        assert_eq!(stats.steps, 300 * (24 + 6));
+        assert!((stats.low_psi + stats.high_psi + stats.high_psi_zero - 1.0).abs() < 1e-12);
+        
+        // psi = v e^(-kappa dt) / (theta (1 - e^(-kappa dt))) is ~0.16 at v = theta with
+        // kappa dt = 2, and the variance only falls from there: always the chi-square branch
+        let mut engine = seeded_engine(diffusion_inputs(), 23);
+        let stats = engine.simulate(above(100000.0), 50).unwrap().diagnostics.qe_branches.unwrap();
+        assert_eq!(stats.low_psi, 1.0);
+    }
+    
+    #[test]
+    fn test_fixed_point_output() {
+        let mut engine = seeded_engine(test_inputs(), 6);
+        assert!(engine.simulate(above(100000.0), 50).unwrap().fixed_point.is_none());
+        
+        engine.set_output(OutputOptions { fixed_point_decimals: Some(0), ..Default::default() }).unwrap();
+        let result = engine.simulate(above(100000.0), 333).unwrap();
+        let fixed = result.fixed_point.as_ref().unwrap();
+        assert_eq!(fixed.fair, result.fair.round() as i64);
+        assert_eq!(fixed.price_mean, Some(result.distribution.as_ref().unwrap().mean.round() as i64));
+        
+        let values = SimResult { fair: 52.30000000000001, ..result.clone() };
+        assert_eq!(fixed_point_values(&values, 2).fair, 5230);
+        assert_eq!(fixed_point_values(&SimResult { fair: 0.125, ..result }, 2).fair, 13);
+        
+        assert!(engine.set_output(OutputOptions { fixed_point_decimals: Some(12), ..Default::default() }).is_err());
+        assert_eq!(engine.config().output.fixed_point_decimals, Some(0));
+    }
+    
+    #[test]
+    fn test_multiple_ci_levels() {
+        let mut engine = seeded_engine(test_inputs(), 37);
+        assert!(engine.simulate(above(100000.0), 50).unwrap().ci_levels.is_empty());
+        
+        engine.set_output(OutputOptions { ci_levels: vec![0.5, 0.9, 0.99], ..Default::default() }).unwrap();
+        let result = engine.simulate(above(100000.0), 1000).unwrap();
+        let levels: Vec<f64> = result.ci_levels.iter().map(|entry| entry.level).collect();
+        assert_eq!(levels, vec![0.5, 0.9, 0.99]);
+        
+        // Nested around p, widening with the level
+        for pair in result.ci_levels.windows(2) {
+            assert!(pair[1].ci[0] < pair[0].ci[0] && pair[0].ci[1] < pair[1].ci[1]);
+        }
+        assert!(result.ci_levels[0].ci[0] < result.p && result.p < result.ci_levels[0].ci[1]);
+        
+        assert!(engine.set_output(OutputOptions { ci_levels: vec![0.9, 1.0], ..Default::default() }).is_err());
+        assert!(engine.set_output(OutputOptions { ci_levels: vec![0.0], ..Default::default() }).is_err());
+    }
+    
+    #[test]
+    fn test_hmm_schedule_switches_during_event() {
+        let mut inputs = test_inputs();
+        inputs.hmm.p = vec![vec![1.0, 0.0], vec![0.0, 1.0]];
+        inputs.hmm.schedule = vec![
+            HmmScheduleEntry { from_hour: 4.0, p: vec![vec![0.0, 1.0], vec![1.0, 0.0]] },
+            HmmScheduleEntry { from_hour: 5.0, p: vec![vec![1.0, 0.0], vec![0.0, 1.0]] },
+        ];
+        let mut engine = seeded_engine(inputs.clone(), 3);
+        
+        // Only the 4h -> 5h step is under the always-switch matrix
+        let stats = engine.simulate(above(100000.0), 200).unwrap().diagnostics.regime_switches.unwrap();
+        assert_eq!((stats.mean, stats.max), (1.0, 1));
+        
+        inputs.hmm.schedule.reverse();
+        assert!(MonteCarloEngine::from_inputs(inputs, 0).is_err());
+    }
+    
+    #[test]
+    fn test_representative_paths() {
+        let mut engine = seeded_engine(diffusion_inputs(), 19);
+        let target = above(100000.0);
+        
+        let paths = engine.pick_representative_paths(&target, 301).unwrap();
+        let labels: Vec<&str> = paths.iter().map(|path| path.label.as_str()).collect();
+        assert_eq!(labels, ["lowest_hit", "median_hit", "highest_hit", "median_miss"]);
+        
+        for path in &paths {
+            // The replayed trace ends where the run's path did
+            assert_eq!(path.points.last().unwrap().price, path.final_price);
+            assert_eq!(path.final_price > 100000.0, path.hit);
+        }
+        assert!(paths[0].final_price <= paths[1].final_price && paths[1].final_price <= paths[2].final_price);
+        
+        // Nothing hits a far strike: only the misser comes back
+        assert_eq!(engine.pick_representative_paths(&above(1e9), 50).unwrap().len(), 1);
+    }
+    
+    #[test]
+    fn test_stressed_inputs_move_regimes_together() {
+        let inputs = test_inputs();
+        let stressed = stressed_inputs(&inputs, 0.5).unwrap();
+        
+        for (base, bumped) in [
+            (&inputs.regimes[BULL], &stressed.regimes[BULL]),
+            (&inputs.regimes[BEAR], &stressed.regimes[BEAR]),
+        ] {
+            assert!((bumped.heston.theta - base.heston.theta * 2.25).abs() < 1e-15);
+            assert!((bumped.heston.xi - base.heston.xi * 1.5).abs() < 1e-15);
+            assert!((bumped.heston.var_floor - base.heston.var_floor * 2.25).abs() < 1e-20);
+            assert_eq!((bumped.mu, bumped.heston.kappa, bumped.heston.rho), (base.mu, base.heston.kappa, base.heston.rho));
+        }
+        assert!((stressed.jumps.lambda - inputs.jumps.lambda * 1.5).abs() < 1e-15);
+        assert_eq!(stressed.jumps.mu_j, inputs.jumps.mu_j);
+        
+        let basket = stressed_inputs(&basket_inputs(0.3), 1.0).unwrap().basket.unwrap();
+        assert!((basket.assets[0].regimes[BULL].heston.theta - 4e-4).abs() < 1e-15);
+        
+        assert!(stressed_inputs(&inputs, -1.0).is_err());
+        
+        // The whole path is stressed, after a scenario switch too
+        let stressed = stressed_inputs(&switch_to_same(inputs.clone(), 6.0), 0.5).unwrap();
+        let switch = stressed.scenario_switch.unwrap();
+        assert_eq!(switch.inputs.regimes[BULL].heston.theta, stressed.regimes[BULL].heston.theta);
+        assert_eq!(switch.inputs.jumps.lambda, stressed.jumps.lambda);
+        assert_eq!(switch.at_hour, 6.0);
+    }
+    
+    #[test]
+    fn test_n_regime_inputs() {
+        // The legacy {"BULL", "BEAR"} object round-trips through the list form unchanged
+        let inputs = test_inputs();
+        let json = serde_json::to_value(&inputs).unwrap();
+        assert!(json["regimes"].is_array());
+        let reparsed: SimInputs = serde_json::from_value(json).unwrap();
+        assert_eq!(reparsed.regimes.0, inputs.regimes.0);
+        assert_eq!(inputs.regimes[BEAR].mu, -0.0001);
+        
+        // A third, sideways regime
+        let mut chop = inputs.regimes[BULL].clone();
+        chop.mu = 0.0;
+        let mut three = inputs.clone();
+        three.regimes.push(chop);
+        three.hmm.p = vec![vec![0.6, 0.1, 0.3], vec![0.2, 0.4, 0.4], vec![0.3, 0.2, 0.5]];
+        three.hmm.pi0 = vec![0.0, 0.0, 1.0];
+        
+        let mut engine = seeded_engine(three.clone(), 4);
+        assert!(engine.simulate(above(100000.0), 200).is_ok());
+        let occupancy = engine.simulate_regime_occupancy(500).unwrap();
+        assert_eq!(occupancy.occupancy[0], [0.0, 0.0, 1.0]);
+        assert_eq!(occupancy.stationary.unwrap().len(), 3);
+        assert_eq!(engine.model.pi0().len(), 3);
+        
+        // Every regime-indexed input must agree on N
+        let mut bad = three.clone();
+        bad.hmm.pi0 = vec![0.5, 0.5];
+        assert!(Model::new(bad).is_err());
+        let mut bad = three.clone();
+        bad.hmm.p.pop();
+        assert!(Model::new(bad).is_err());
+        
+        // Rows are per-step probabilities, not rates
+        let mut bad = three;
+        bad.hmm.p[2] = vec![0.3, 0.2, 0.0];
+        assert!(Model::new(bad).is_err());
+    }
+    
+    #[test]
+    fn test_stationary_pi0_option() {
+        let mut inputs = test_inputs();
+        inputs.hmm.pi0 = vec![0.0, 1.0];
+        inputs.use_stationary_pi0 = true;
+        let engine = seeded_engine(inputs.clone(), 1);
+        
+        let pi = engine.model.stationary_distribution().unwrap();
+        assert_eq!(engine.model.pi0(), pi);
+        assert!((pi[0] - 2.0 / 3.0).abs() < 1e-12);
+        
+        inputs.use_stationary_pi0 = false;
+        assert_eq!(seeded_engine(inputs.clone(), 1).model.pi0(), [0.0, 1.0]);
+        
+        inputs.use_stationary_pi0 = true;
+        inputs.hmm.p = vec![vec![1.0, 0.0], vec![0.0, 1.0]];
+        assert!(MonteCarloEngine::from_inputs(inputs, 0).is_err());
+    }
+    
+    #[test]
+    fn test_stayed_above_is_path_dependent() {
+        let mut engine = seeded_engine(gbm_inputs(), 27);
+        let stayed = |k: f64| Target { kind: "stayed_above".to_string(), K: Some(k), ..Default::default() };
+        
+        // Surviving the whole path is rarer than merely finishing above the floor
+        let survive = engine.simulate(stayed(99000.0), 3000).unwrap();
+        let finish = engine.simulate(above(99000.0), 3000).unwrap();
+        assert!(survive.p < finish.p - 0.05);
+        
+        // A floor above the start is breached at t = 0
+        assert_eq!(engine.simulate(stayed(100001.0), 100).unwrap().p, 0.0);
+        
+        let outcome = PathOutcome { initial_price: 100.0, final_price: 120.0, min_price: 90.0, ..Default::default() };
+        assert!(target_hit(&stayed(90.0), &outcome).unwrap());
+        assert!(!target_hit(&stayed(90.5), &outcome).unwrap());
+    }
+    
+    #[test]
+    fn test_shock_buffer_replays_the_twin() {
+        use rand::SeedableRng;
+        let engine = seeded_engine(test_inputs(), 0);
+        let mut rng = rand::rngs::StdRng::seed_from_u64(8);
+        let mut shocks = ShockBuffer::new();
+        
+        for _ in 0..20 {
+            let reference_rng = rng.clone();
+            let first = engine.model.simulate_path(&mut shocks.record(&mut rng), None);
+            let twin = engine.model.simulate_mirrored_path(&mut shocks.replay(), None, Some(&above(100000.0)), None);
+            
+            // Identical to regenerating the twin from a copy of the stream
+            let regenerated = engine.model.simulate_mirrored_path(&mut reference_rng.clone(), None, Some(&above(100000.0)), None);
+            assert_eq!(twin.final_price, regenerated.final_price);
+            assert_eq!(twin.regime_switches, first.regime_switches);
+            
+            // The replay consumed exactly what the record drew: the engine stream is not advanced twice
+            let mut after = reference_rng.clone();
+            engine.model.simulate_path(&mut after, None);
+            assert_eq!(rand::Rng::gen::<u64>(&mut after), rand::Rng::gen::<u64>(&mut rng.clone()));
+        }
+        
+        // One allocation serves every pair: recording the same paths again never grows the buffer
+        let capacity = shocks.capacity();
+        assert!(capacity > 0);
+        let mut rng = rand::rngs::StdRng::seed_from_u64(8);
+        for _ in 0..20 {
+            engine.model.simulate_path(&mut shocks.record(&mut rng), None);
+            assert_eq!(shocks.capacity(), capacity);
+        }
+        
+        // So each extra pair costs an antithetic run no more allocations than two plain paths
+        let extra = |antithetic: bool| {
+            let run = |n_paths: u32| {
+                let mut engine = seeded_engine(test_inputs(), 3);
+                allocations_during(|| match antithetic {
+                    true => engine.simulate_antithetic(above(100000.0), n_paths),
+                    false => engine.simulate(above(100000.0), n_paths),
+                })
+            };
+            run(2000) - run(1000)
+        };
+        let (plain, paired) = (extra(false), extra(true));
+        assert!(paired <= plain, "antithetic run made {} more allocations for 1000 more paths, plain {}", paired, plain);
+    }
+    
+    #[test]
+    fn test_antithetic_reduces_variance() {
+        let target = above(100000.0);
+        let (mut plain_p, mut paired_p) = (Vec::new(), Vec::new());
+        let (mut plain_se, mut paired_se) = (0.0, 0.0);
+        
+        for seed in 0..12 {
+            let plain = seeded_engine(gbm_inputs(), seed).simulate(target.clone(), 2000).unwrap();
+            let paired = seeded_engine(gbm_inputs(), seed).simulate_antithetic(target.clone(), 2000).unwrap();
+            plain_se += plain.diagnostics.stderr;
+            paired_se += paired.diagnostics.stderr;
+            plain_p.push(plain.p);
+            paired_p.push(paired.p);
+        }
+        
+        // The reported stderr drops, and so does the actual spread of the estimates across seeds
+        assert!(paired_se < 0.8 * plain_se);
+        let spread = |p: &[f64]| {
+            let mean = p.iter().sum::<f64>() / p.len() as f64;
+            p.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (p.len() - 1) as f64
+        };
+        assert!(spread(&paired_p) < spread(&plain_p));
+    }
+    
+    #[test]
+    fn test_digital_spread_pairs_the_legs() {
+        let mut engine = seeded_engine(gbm_inputs(), 33);
+        
+        let spread = engine.digital_spread(99000.0, 101000.0, 4000).unwrap();
+        assert!((spread.p - (spread.p_lower - spread.p_upper)).abs() < 1e-12);
+        assert!(spread.ci[0] <= spread.p && spread.p <= spread.ci[1]);
+        
+        // Positively correlated legs: the paired error is well below the independent one
+        assert!(spread.stderr < 0.8 * spread.independent_stderr);
+        
+        // Same as a range target with an open lower bound
+        let mut engine = seeded_engine(gbm_inputs(), 33);
+        let range = Target {
+            kind: "range".to_string(),
+            L: Some(99000.0),
+            U: Some(101000.0),
+            l_inclusive: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(engine.simulate(range, 4000).unwrap().p, spread.p);
+        
+        assert!(engine.digital_spread(101000.0, 99000.0, 100).is_err());
+    }
+    
+    #[test]
+    fn test_debug_snapshot() {
+        let mut engine = seeded_engine(test_inputs(), 41);
+        let snapshot = engine.snapshot();
+        assert_eq!(snapshot.config.seed, 41);
+        assert!(snapshot.last_diagnostics.is_none() && snapshot.rolling_window.is_none());
+        
+        let result = engine.simulate(above(100000.0), 300).unwrap();
+        engine.set_window(100).unwrap();
+        engine.simulate_rolling(above(100000.0), 40).unwrap();
+        
+        let snapshot = engine.snapshot();
+        assert_eq!(snapshot.last_diagnostics.unwrap().stderr, result.diagnostics.stderr);
+        let window = snapshot.rolling_window.unwrap();
+        assert_eq!((window.target, window.estimate.effective_n), (Some(above(100000.0)), 40));
+        assert!(serde_json::to_string(&engine.snapshot()).is_ok());
+    }
+    
+    #[test]
+    fn test_conditional_on_realized_vol() {
+        let mut engine = seeded_engine(test_inputs(), 43);
+        
+        // No condition at all: the conditional probability is the plain one
+        let unconditional = engine.conditional_on_vol(above(100000.0), 0.0, 1000).unwrap();
+        assert_eq!(unconditional.condition_rate, 1.0);
+        
+        let mut engine = seeded_engine(test_inputs(), 43);
+        let plain = engine.simulate(above(100000.0), 1000).unwrap();
+        assert_eq!(unconditional.p, plain.p);
+        
+        // An unreachable threshold leaves nothing to condition on
+        let none = engine.conditional_on_vol(above(100000.0), 1e6, 200).unwrap();
+        assert_eq!((none.n_conditioned, none.p, none.condition_rate), (0, 0.0, 0.0));
+        assert_eq!(none.ci, [0.0, 1.0]);
+        
+        let mut vols: Vec<f64> = (0..500).map(|_| engine.model.simulate_path(&mut engine.rng, None).realized_vol).collect();
+        vols.sort_by(|a, b| a.total_cmp(b));
+        let median = utils::quantile_sorted(&vols, 0.5);
+        let half = engine.conditional_on_vol(above(100000.0), median, 2000).unwrap();
+        assert!((half.condition_rate - 0.5).abs() < 0.1);
+    }
+    
+    #[test]
+    fn test_double_heston_adds_variance_factors() {
+        // Two pinned factors at 1e-4 and 3e-4: paths diffuse at the summed variance
+        let mut inputs = gbm_inputs();
+        let mut slow = inputs.regimes[BULL].heston.clone();
+        slow.theta = 3e-4;
+        slow.var_floor = 3e-4;
+        inputs.regimes[BULL].heston2 = Some(slow);
+        inputs.regimes[BEAR] = inputs.regimes[BULL].clone();
+        let mut engine = seeded_engine(inputs.clone(), 51);
+        
+        let result = engine.simulate(above(100000.0), 4000).unwrap();
+        let check = result.diagnostics.terminal_variance.unwrap();
+        assert!((check.theoretical - 24.0 * 4e-4).abs() < 1e-12);
+        assert!((check.ratio - 1.0).abs() < 0.06);
+        assert!((result.diagnostics.annualized_vol.by_regime[0] - (4e-4 * utils::HOURS_PER_YEAR).sqrt()).abs() < 1e-12);
+        
+        // Each factor takes its own QE step
+        assert_eq!(result.diagnostics.qe_branches.unwrap().steps, 4000 * 24 * 2);
+        
+        inputs.regimes[BEAR].heston2.as_mut().unwrap().var_floor = 0.0;
+        assert!(MonteCarloEngine::from_inputs(inputs, 0).is_err());
+    }
+    
+    #[test]
+    fn test_experiment_variants_are_reproducible() {
+        let variant = |name: &str, s0: f64| ExperimentVariant {
+            name: name.to_string(),
+            inputs: SimInputs { s0, ..test_inputs() },
+        };
+        let variants = vec![variant("control", 100000.0), variant("treatment", 101000.0)];
+        
+        let runs = run_variants(&above(100500.0), 5, variants.clone(), 300).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_ne!(runs[0].seed, runs[1].seed);
+        
+        // Order and companions don't matter: a variant alone reproduces its own result
+        let alone = run_variants(&above(100500.0), 5, vec![variants[1].clone()], 300).unwrap();
+        assert_eq!((alone[0].seed, alone[0].result.p), (runs[1].seed, runs[1].result.p));
+        
+        assert!(run_variants(&above(100500.0), 5, vec![variants[0].clone(), variants[0].clone()], 10).is_err());
+    }
+    
+    #[test]
+    fn test_rejects_non_positive_var_floor() {
+        let mut inputs = test_inputs();
+        inputs.regimes[BEAR].heston.var_floor = 0.0;
+        assert!(MonteCarloEngine::from_inputs(inputs, 0).is_err());
+    }
+    
+    #[test]
+    fn test_basket_correlation_widens_index() {
+        let correlated = seeded_engine(basket_inputs(0.95), 3)
+            .simulate(above(100000.0), 4000)
+            .unwrap();
+        let offsetting = seeded_engine(basket_inputs(-0.95), 3)
+            .simulate(above(100000.0), 4000)
+            .unwrap();
+        
+        // Index starts at the weighted sum and diversifies away with negative correlation
+        let wide = correlated.distribution.unwrap().stddev;
+        let narrow = offsetting.distribution.unwrap().stddev;
+        assert!(wide > 3.0 * narrow);
         
-        Ok(s)
+        let mut bad = basket_inputs(0.5);
+        bad.basket.as_mut().unwrap().correlation = vec![vec![1.0, 1.5], vec![1.5, 1.0]];
+        assert!(MonteCarloEngine::from_inputs(bad, 0).is_err());
     }
 }
@@ -1,33 +1,166 @@
 use rand::prelude::*;
 use rand_distr::{Normal, Poisson, StandardNormal};
 use crate::types::*;
+use crate::utils::normal_cdf;
 
 /// Update regime using HMM transition matrix
-pub fn update_regime(rng: &mut impl Rng, current: Regime, hmm: &HMM, dt: f64) -> Regime {
+pub fn update_regime<R: Rng + ?Sized>(rng: &mut R, current: Regime, hmm: &HMM) -> Regime {
+    update_regime_with(rng, current, &hmm.p)
+}
+
+/// Stationary distribution of a per-step transition matrix, in regime order
+///
+/// Solves the left eigenvector pi P = pi with sum(pi) = 1, written as pi (P - I) = 0 with the diagonal
+/// of P - I taken as minus the row's off-diagonal sum (equal, since rows sum to 1). None when it is
+/// not unique (e.g. no state can be left).
+pub fn stationary_distribution(p: &[Vec<f64>]) -> Option<Vec<f64>> {
+    let n = p.len();
+    if n == 0 {
+        return None;
+    }
+    
+    // Row j of the system is column j of Q; the last equation becomes the normalization
+    let mut a: Vec<Vec<f64>> = (0..n)
+        .map(|j| {
+            let mut row: Vec<f64> = (0..n).map(|i| if i == j { 0.0 } else { p[i][j] }).collect();
+            row[j] = -p[j].iter().enumerate().filter(|&(k, _)| k != j).map(|(_, rate)| rate).sum::<f64>();
+            row.push(0.0);
+            row
+        })
+        .collect();
+    a[n - 1] = vec![1.0; n + 1];
+    
+    // Gaussian elimination with partial pivoting
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&x, &y| a[x][col].abs().total_cmp(&a[y][col].abs()))?;
+        let magnitude = a[pivot][col].abs();
+        if magnitude.is_nan() || magnitude <= 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        let pivot_row = a[col].clone();
+        for (row, values) in a.iter_mut().enumerate() {
+            if row != col {
+                let factor = values[col] / pivot_row[col];
+                for (value, pivot_value) in values[col..].iter_mut().zip(&pivot_row[col..]) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+    
+    let pi: Vec<f64> = (0..n).map(|i| (a[i][n] / a[i][i]).max(0.0)).collect();
+    pi.iter().all(|x| x.is_finite()).then_some(pi)
+}
+
+/// Drift of a regime at `time` hours: the last `mu_schedule` piece that has started, else `mu`
+pub fn mu_at(params: &RegimeParams, time: f64) -> f64 {
+    params
+        .mu_schedule
+        .iter()
+        .rev()
+        .find(|&&(from_hour, _)| from_hour <= time)
+        .map_or(params.mu, |&(_, mu)| mu)
+}
+
+/// Transition matrix in force at `time` hours: the last schedule entry that has started, else `p`
+pub fn transition_matrix_at(hmm: &HMM, time: f64) -> &[Vec<f64>] {
+    hmm.schedule
+        .iter()
+        .rev()
+        .find(|entry| entry.from_hour <= time)
+        .map_or(&hmm.p, |entry| &entry.p)
+}
+
+/// Update regime using an explicit transition matrix
+///
+/// `p` is a per-step stochastic matrix: p[i][j] is the probability of being in j one step (of the
+/// inputs' dt) after being in i, and rows sum to 1. It is not rescaled by dt, so a matrix calibrated
+/// for one step size has to be recalibrated (or raised to a power) for another. One uniform walks the row.
+pub fn update_regime_with<R: Rng + ?Sized>(rng: &mut R, current: Regime, p: &[Vec<f64>]) -> Regime {
     let u: f64 = rng.gen();
     
-    let transition_prob = match current {
-        Regime::Bull => hmm.p[0][1] * dt, // Prob of Bull -> Bear
-        Regime::Bear => hmm.p[1][0] * dt, // Prob of Bear -> Bull
+    let row = &p[current];
+    let mut cumulative = 0.0;
+    for (next, prob) in row.iter().enumerate() {
+        cumulative += prob;
+        if u < cumulative {
+            return next;
+        }
+    }
+    
+    // Rounding left the row a hair short of 1
+    current
+}
+
+/// Shift both regimes together by one stress level: volatility scales by (1 + level), so
+/// theta and var_floor scale by its square and xi with it. Drift, kappa and rho are left alone.
+pub fn stress_regimes(regimes: &RegimeSet, level: f64) -> RegimeSet {
+    let m = 1.0 + level;
+    let stress_heston = |heston: &HestonParams| HestonParams {
+        theta: heston.theta * m * m,
+        xi: heston.xi * m,
+        var_floor: heston.var_floor * m * m,
+        ..heston.clone()
+    };
+    let stress = |params: &RegimeParams| RegimeParams {
+        heston: stress_heston(&params.heston),
+        heston2: params.heston2.as_ref().map(stress_heston),
+        ..params.clone()
     };
     
-    if u < transition_prob {
-        match current {
-            Regime::Bull => Regime::Bear,
-            Regime::Bear => Regime::Bull,
-        }
+    RegimeSet(regimes.iter().map(stress).collect())
+}
+
+/// Jump counterpart of `stress_regimes`: intensity and jump size dispersion scale by (1 + level)
+pub fn stress_jumps(jumps: &JumpParams, level: f64) -> JumpParams {
+    let m = 1.0 + level;
+    JumpParams {
+        lambda: jumps.lambda * m,
+        sigma_j: jumps.sigma_j * m,
+        ..jumps.clone()
+    }
+}
+
+/// Exact Ornstein-Uhlenbeck step: x mean-reverts to `mean` at `speed` with volatility `vol`
+pub fn simulate_ou<R: Rng + ?Sized>(rng: &mut R, x: f64, mean: f64, speed: f64, vol: f64, dt: f64) -> f64 {
+    let z: f64 = rng.sample(StandardNormal);
+    
+    if speed > 0.0 {
+        let decay = (-speed * dt).exp();
+        let sd = vol * ((1.0 - decay * decay) / (2.0 * speed)).sqrt();
+        mean + (x - mean) * decay + sd * z
     } else {
-        current
+        // No reversion: plain Brownian drift
+        x + vol * dt.sqrt() * z
     }
 }
 
+/// Which branch of the QE scheme produced a variance step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QeBranch {
+    LowPsi,       // chi-square branch
+    HighPsi,      // moment-matched exponential branch
+    HighPsiZero,  // point mass at zero in the high-psi branch
+}
+
 /// Simulate Heston variance using Andersen's QE scheme
-pub fn simulate_heston_variance(
-    rng: &mut impl Rng,
+pub fn simulate_heston_variance<R: Rng + ?Sized>(
+    rng: &mut R,
     v_current: f64,
     params: &HestonParams,
     dt: f64,
 ) -> f64 {
+    simulate_heston_variance_branch(rng, v_current, params, dt).0
+}
+
+/// `simulate_heston_variance` that also reports which QE branch it took
+pub fn simulate_heston_variance_branch<R: Rng + ?Sized>(
+    rng: &mut R,
+    v_current: f64,
+    params: &HestonParams,
+    dt: f64,
+) -> (f64, QeBranch) {
     let kappa = params.kappa;
     let theta = params.theta;
     let xi = params.xi;
@@ -44,7 +177,7 @@ pub fn simulate_heston_variance(
     let psi_c = 1.5;
     let psi = lambda / c3;
     
-    let v_next = if psi <= psi_c {
+    let (v_next, branch) = if psi <= psi_c {
         // Use exact simulation for low psi
         let m = c3;
         let beta = 2.0 / c1;
@@ -62,7 +195,7 @@ pub fn simulate_heston_variance(
             -2.0 * u.ln()
         };
         
-        chi_sq / beta
+        (chi_sq / beta, QeBranch::LowPsi)
     } else {
         // Use moment-matching for high psi
         let p = (psi - 1.0) / (psi + 1.0);
@@ -70,19 +203,36 @@ pub fn simulate_heston_variance(
         let u: f64 = rng.gen();
         
         if u <= p {
-            0.0
+            (0.0, QeBranch::HighPsiZero)
         } else {
-            (1.0 - p).ln() / beta
+            ((1.0 - p).ln() / beta, QeBranch::HighPsi)
         }
     };
     
     // Ensure variance stays positive
-    v_next.max(1e-8)
+    (v_next.max(params.var_floor), branch)
+}
+
+/// Euler full-truncation step: v + kappa (theta - v+) dt + xi sqrt(v+ dt) Z, floored at zero
+///
+/// Biased at coarse dt but simple and robust when the Feller condition fails; an alternative to
+/// the QE scheme for comparing the two. var_floor is not applied, so paths can sit at exactly 0.
+pub fn simulate_heston_variance_full_truncation<R: Rng + ?Sized>(
+    rng: &mut R,
+    v_current: f64,
+    params: &HestonParams,
+    dt: f64,
+) -> f64 {
+    let v_plus = v_current.max(0.0);
+    let z: f64 = rng.sample(StandardNormal);
+    
+    let v_next = v_current + params.kappa * (params.theta - v_plus) * dt + params.xi * (v_plus * dt).sqrt() * z;
+    v_next.max(0.0)
 }
 
 /// Simulate price with jumps (Merton model)
-pub fn simulate_price_with_jumps(
-    rng: &mut impl Rng,
+pub fn simulate_price_with_jumps<R: Rng + ?Sized>(
+    rng: &mut R,
     s_current: f64,
     v_current: f64,
     mu: f64,
@@ -90,66 +240,162 @@ pub fn simulate_price_with_jumps(
     jumps: &JumpParams,
     dt: f64,
 ) -> (f64, bool) {
-    let sqrt_v = v_current.sqrt();
-    let sqrt_dt = dt.sqrt();
-    
     // Generate correlated Brownian motions
     let z1: f64 = rng.sample(StandardNormal);
     let z2: f64 = rng.sample(StandardNormal);
     
     // Correlated shocks for price
     let w1 = z1;
-    let w2 = heston.rho * z1 + (1.0 - heston.rho * heston.rho).sqrt() * z2;
+    let _w2 = heston.rho * z1 + (1.0 - heston.rho * heston.rho).sqrt() * z2;
+    
+    let (s_next, n_jumps) = simulate_price_with_shock(rng, s_current, v_current, mu, jumps, jumps.lambda, dt, w1);
+    (s_next, n_jumps > 0)
+}
+
+/// Simulate price with jumps given the diffusive standard normal shock `w1`, returning the
+/// number of jumps
+///
+/// Used directly when the shock comes from outside, e.g. correlated basket constituents.
+/// `intensity` is the jump rate over the step: `jumps.lambda`, plus any Hawkes excitation.
+#[allow(clippy::too_many_arguments)]
+pub fn simulate_price_with_shock<R: Rng + ?Sized>(
+    rng: &mut R,
+    s_current: f64,
+    v_current: f64,
+    mu: f64,
+    jumps: &JumpParams,
+    intensity: f64,
+    dt: f64,
+    w1: f64,
+) -> (f64, u64) {
+    let sqrt_v = v_current.sqrt();
+    let sqrt_dt = dt.sqrt();
     
     // Jump component
-    let mut jump_occurred = false;
-    let jump_multiplier = if jumps.lambda > 0.0 {
-        // Determine if jump occurs
-        let poisson = Poisson::new(jumps.lambda * dt).unwrap();
-        let n_jumps = rng.sample(poisson) as u64;
-        
-        if n_jumps > 0 {
-            jump_occurred = true;
-            let mut total_jump = 1.0;
-            
-            for _ in 0..n_jumps {
-                // Log-normal jump size
-                let normal = Normal::new(jumps.mu_j, jumps.sigma_j).unwrap();
-                let log_jump: f64 = rng.sample(normal);
-                total_jump *= log_jump.exp();
-            }
-            
-            total_jump
-        } else {
-            1.0
-        }
-    } else {
-        1.0
-    };
+    let (jump_multiplier, n_jumps) = sample_jump_multiplier(rng, jumps, intensity, dt);
     
     // Compensated drift (risk-neutral)
-    let compensator = jumps.lambda * ((jumps.mu_j + 0.5 * jumps.sigma_j * jumps.sigma_j).exp() - 1.0);
+    let compensator = jump_compensator_at(jumps, intensity);
     let drift = mu - 0.5 * v_current - compensator;
     
     // Apply Euler-Maruyama with jumps
     let log_return = drift * dt + sqrt_v * sqrt_dt * w1;
     let s_next = s_current * log_return.exp() * jump_multiplier;
     
-    (s_next, jump_occurred)
+    (s_next, n_jumps)
 }
 
-/// Generate antithetic paths for variance reduction
-pub fn generate_antithetic_normals(rng: &mut impl Rng, n: usize) -> (Vec<f64>, Vec<f64>) {
-    let mut normals = Vec::with_capacity(n);
-    let mut antithetic = Vec::with_capacity(n);
-    
-    for _ in 0..n {
-        let z: f64 = rng.sample(StandardNormal);
-        normals.push(z);
-        antithetic.push(-z);
+/// Product of the jump multipliers e^J over one step at jump rate `intensity`, and the jump count
+fn sample_jump_multiplier<R: Rng + ?Sized>(rng: &mut R, jumps: &JumpParams, intensity: f64, dt: f64) -> (f64, u64) {
+    if intensity > 0.0 {
+        // Determine if jump occurs
+        let poisson = Poisson::new(intensity * dt).unwrap();
+        let n_jumps = rng.sample(poisson) as u64;
+        
+        let mut total_jump = 1.0;
+        for _ in 0..n_jumps {
+            let log_jump = sample_log_jump(rng, jumps);
+            total_jump *= log_jump.exp();
+        }
+        
+        (total_jump, n_jumps)
+    } else {
+        (1.0, 0)
     }
+}
+
+/// Hawkes excitation after a step of `dt`: the earlier excitation decays at `beta` and each of
+/// the step's `n_jumps` adds `alpha`. Jumps are placed at the end of the step, so there is no
+/// decay within it; zero whenever `alpha` is, leaving the plain Poisson process
+pub fn hawkes_excitation(jumps: &JumpParams, excitation: f64, n_jumps: u64, dt: f64) -> f64 {
+    if jumps.alpha > 0.0 {
+        excitation * (-jumps.beta * dt).exp() + jumps.alpha * n_jumps as f64
+    } else {
+        0.0
+    }
+}
+
+/// Long-run mean jump rate: lambda, scaled up by 1 / (1 - alpha / beta) under Hawkes clustering
+pub fn mean_jump_intensity(jumps: &JumpParams) -> f64 {
+    if jumps.alpha > 0.0 {
+        jumps.lambda / (1.0 - jumps.alpha / jumps.beta)
+    } else {
+        jumps.lambda
+    }
+}
+
+/// Sample a single log jump size from the configured distribution
+pub fn sample_log_jump<R: Rng + ?Sized>(rng: &mut R, jumps: &JumpParams) -> f64 {
+    match jumps.kind.as_str() {
+        "skew_normal" => {
+            // Skew-normal via two correlated normals: keep u1 when u0 >= 0, else flip it
+            let delta = skew_normal_delta(jumps.skew);
+            let u0: f64 = rng.sample(StandardNormal);
+            let v: f64 = rng.sample(StandardNormal);
+            let u1 = delta * u0 + (1.0 - delta * delta).sqrt() * v;
+            let z = if u0 >= 0.0 { u1 } else { -u1 };
+            jumps.mu_j + jumps.sigma_j * z
+        },
+        "kou" => {
+            // Asymmetric double exponential: the side first, then an exponential size
+            let up = rng.gen::<f64>() < jumps.p_up;
+            let size: f64 = rng.sample(rand_distr::Exp1);
+            if up {
+                size / jumps.eta_up
+            } else {
+                -size / jumps.eta_down
+            }
+        },
+        _ => {
+            // Merton log-normal jump size (any other kind is rejected up front)
+            let normal = Normal::new(jumps.mu_j, jumps.sigma_j).unwrap();
+            rng.sample(normal)
+        },
+    }
+}
+
+/// Second moment E[J^2] of one log jump, for comparing jump and diffusive variance
+pub fn jump_second_moment(jumps: &JumpParams) -> f64 {
+    let (mu, sigma) = (jumps.mu_j, jumps.sigma_j);
+    match jumps.kind.as_str() {
+        "skew_normal" => {
+            let delta = skew_normal_delta(jumps.skew);
+            mu * mu + 2.0 * mu * sigma * delta * (2.0 / std::f64::consts::PI).sqrt() + sigma * sigma
+        },
+        "kou" => {
+            2.0 * jumps.p_up / (jumps.eta_up * jumps.eta_up) + 2.0 * (1.0 - jumps.p_up) / (jumps.eta_down * jumps.eta_down)
+        },
+        _ => mu * mu + sigma * sigma,
+    }
+}
+
+/// Jump compensator lambda * (E[e^J] - 1) for the configured jump distribution
+pub fn jump_compensator(jumps: &JumpParams) -> f64 {
+    jump_compensator_at(jumps, jumps.lambda)
+}
+
+/// `jump_compensator` at jump rate `intensity` instead of the base lambda
+pub fn jump_compensator_at(jumps: &JumpParams, intensity: f64) -> f64 {
+    let half_var = 0.5 * jumps.sigma_j * jumps.sigma_j;
+    let mean_multiplier = match jumps.kind.as_str() {
+        // Skew-normal MGF at 1: 2 * exp(mu + sigma^2 / 2) * Phi(delta * sigma)
+        "skew_normal" => {
+            let delta = skew_normal_delta(jumps.skew);
+            2.0 * (jumps.mu_j + half_var).exp() * normal_cdf(delta * jumps.sigma_j)
+        },
+        // E[e^J] = p eta_up / (eta_up - 1) + (1 - p) eta_down / (eta_down + 1), finite for eta_up > 1
+        "kou" => {
+            let p = jumps.p_up;
+            p * jumps.eta_up / (jumps.eta_up - 1.0) + (1.0 - p) * jumps.eta_down / (jumps.eta_down + 1.0)
+        },
+        _ => (jumps.mu_j + half_var).exp(),
+    };
     
-    (normals, antithetic)
+    intensity * (mean_multiplier - 1.0)
+}
+
+fn skew_normal_delta(alpha: f64) -> f64 {
+    alpha / (1.0 + alpha * alpha).sqrt()
 }
 
 #[cfg(test)]
@@ -164,6 +410,7 @@ mod tests {
             theta: 0.04,
             xi: 0.3,
             rho: -0.5,
+            var_floor: 1e-8,
         };
         
         let v0 = 0.04;
@@ -175,26 +422,233 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_variance_floor_override() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(5);
+        let params = HestonParams {
+            kappa: 2.0,
+            theta: 0.0001,
+            xi: 0.01,
+            rho: -0.5,
+            var_floor: 5e-5,
+        };
+        
+        let mut v = params.theta;
+        for _ in 0..200 {
+            v = simulate_heston_variance(&mut rng, v, &params, 1.0);
+            assert!(v >= params.var_floor);
+        }
+    }
+    
+    #[test]
+    fn test_ou_mean_reversion() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(13);
+        let (speed, vol) = (0.5, 0.02);
+        
+        let n = 20_000;
+        let draws: Vec<f64> = (0..n).map(|_| simulate_ou(&mut rng, 1.0, 0.0, speed, vol, 10.0)).collect();
+        let mean = draws.iter().sum::<f64>() / n as f64;
+        let var = draws.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / (n - 1) as f64;
+        
+        // After 20 half-lives only the stationary spread vol^2 / (2 * speed) remains
+        assert!(mean.abs() < 0.01);
+        assert!((var / (vol * vol / (2.0 * speed)) - 1.0).abs() < 0.05);
+    }
+    
+    #[test]
+    fn test_stationary_distribution() {
+        let p = vec![vec![0.95, 0.05], vec![0.10, 0.90]];
+        let pi = stationary_distribution(&p).unwrap();
+        assert!((pi[0] - 2.0 / 3.0).abs() < 1e-12);
+        
+        // Left eigenvector: pi P == pi
+        for j in 0..2 {
+            assert!((pi[0] * p[0][j] + pi[1] * p[1][j] - pi[j]).abs() < 1e-12);
+        }
+        
+        assert!(stationary_distribution(&[vec![1.0, 0.0], vec![0.0, 1.0]]).is_none());
+    }
+    
+    #[test]
+    fn test_transition_schedule() {
+        let calm = vec![vec![1.0, 0.0], vec![0.0, 1.0]];
+        let event = vec![vec![0.0, 1.0], vec![1.0, 0.0]];
+        let hmm = HMM {
+            p: calm.clone(),
+            pi0: vec![1.0, 0.0],
+            schedule: vec![
+                HmmScheduleEntry { from_hour: 4.0, p: event.clone() },
+                HmmScheduleEntry { from_hour: 5.0, p: calm.clone() },
+            ],
+        };
+        
+        assert_eq!(transition_matrix_at(&hmm, 0.0), calm.as_slice());
+        assert_eq!(transition_matrix_at(&hmm, 4.0), event.as_slice());
+        assert_eq!(transition_matrix_at(&hmm, 4.5), event.as_slice());
+        assert_eq!(transition_matrix_at(&hmm, 7.0), calm.as_slice());
+        
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        assert_eq!(update_regime_with(&mut rng, BULL, transition_matrix_at(&hmm, 4.0)), BEAR);
+    }
+    
+    #[test]
+    fn test_variance_schemes_stay_non_negative() {
+        // 2 kappa theta = 0.2 < xi^2 = 1: Feller is violated and the variance keeps hitting zero
+        let params = HestonParams { kappa: 1.0, theta: 0.1, xi: 1.0, rho: -0.5, var_floor: 1e-8 };
+        let mut rng = rand::rngs::StdRng::seed_from_u64(21);
+        let dt = 0.1;
+        
+        let (mut qe, mut euler) = (params.theta, params.theta);
+        let mut zeros = 0;
+        for _ in 0..1000 {
+            qe = simulate_heston_variance(&mut rng, qe, &params, dt);
+            euler = simulate_heston_variance_full_truncation(&mut rng, euler, &params, dt);
+            assert!(qe >= 0.0 && qe.is_finite());
+            assert!(euler >= 0.0 && euler.is_finite());
+            zeros += u32::from(euler == 0.0);
+        }
+        assert!(zeros > 0);
+        
+        // Without noise the Euler step is plain mean reversion
+        let calm = HestonParams { xi: 0.0, ..params };
+        let v = simulate_heston_variance_full_truncation(&mut rng, 0.3, &calm, dt);
+        assert!((v - (0.3 + 1.0 * (0.1 - 0.3) * dt)).abs() < 1e-15);
+    }
+    
     #[test]
     fn test_regime_switching() {
-        let mut rng = rand::thread_rng();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
         let hmm = HMM {
-            p: [[0.95, 0.05], [0.10, 0.90]],
-            pi0: [0.7, 0.3],
+            p: vec![vec![0.95, 0.05], vec![0.10, 0.90]],
+            pi0: vec![0.7, 0.3],
+            schedule: Vec::new(),
         };
         
-        let dt = 1.0 / 24.0;
+        // Each step leaves BULL with probability p[0][1] itself, not p[0][1] * dt
+        let n = 100_000;
+        let mut bull_steps = 0;
+        let mut bull_exits = 0;
         let mut bull_count = 0;
-        let mut current = Regime::Bull;
+        let mut current = BULL;
         
-        for _ in 0..1000 {
-            current = update_regime(&mut rng, current, &hmm, dt);
-            if matches!(current, Regime::Bull) {
+        for _ in 0..n {
+            let next = update_regime(&mut rng, current, &hmm);
+            if current == BULL {
+                bull_steps += 1;
+                bull_exits += u32::from(next == BEAR);
+            }
+            current = next;
+            if current == BULL {
                 bull_count += 1;
             }
         }
         
-        // Should spend more time in Bull regime
-        assert!(bull_count > 500);
+        assert!((bull_exits as f64 / bull_steps as f64 - 0.05).abs() < 0.005);
+        
+        // Time in BULL settles at the stationary 0.10 / (0.05 + 0.10)
+        assert!((bull_count as f64 / n as f64 - 2.0 / 3.0).abs() < 0.02);
+    }
+    
+    #[test]
+    fn test_three_regime_chain() {
+        // Bull, bear and a sideways state that both trending states fall into
+        let p = vec![
+            vec![0.6, 0.1, 0.3],
+            vec![0.2, 0.4, 0.4],
+            vec![0.3, 0.2, 0.5],
+        ];
+        let pi = stationary_distribution(&p).unwrap();
+        assert!((pi.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        
+        // Left eigenvector: pi P == pi
+        for j in 0..3 {
+            let next: f64 = (0..3).map(|i| pi[i] * p[i][j]).sum();
+            assert!((next - pi[j]).abs() < 1e-12);
+        }
+        
+        // A long chain spends roughly pi of its time in each state
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let n = 200_000;
+        let mut counts = [0u32; 3];
+        let mut current = BULL;
+        for _ in 0..n {
+            current = update_regime_with(&mut rng, current, &p);
+            counts[current] += 1;
+        }
+        for (count, share) in counts.iter().zip(&pi) {
+            assert!((*count as f64 / n as f64 - share).abs() < 0.01);
+        }
+    }
+    
+    #[test]
+    fn test_skew_normal_jumps() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(11);
+        let mut jumps = JumpParams {
+            lambda: 1.0,
+            mu_j: 0.0,
+            sigma_j: 0.05,
+            kind: "skew_normal".to_string(),
+            skew: -4.0,
+            p_up: 0.0,
+            eta_up: 0.0,
+            eta_down: 0.0,
+            alpha: 0.0,
+            beta: 0.0,
+        };
+        
+        // Negative shape puts the mass on the crash side
+        let n = 20_000;
+        let samples: Vec<f64> = (0..n).map(|_| sample_log_jump(&mut rng, &jumps)).collect();
+        let mean = samples.iter().sum::<f64>() / n as f64;
+        assert!(mean < 0.0);
+        
+        // Compensator matches the empirical mean jump multiplier
+        let empirical = samples.iter().map(|j| j.exp()).sum::<f64>() / n as f64 - 1.0;
+        assert!((jump_compensator(&jumps) - empirical).abs() < 1e-3);
+        
+        // Zero skew collapses to the Merton compensator
+        jumps.skew = 0.0;
+        let merton = JumpParams { kind: "merton".to_string(), ..jumps.clone() };
+        assert!((jump_compensator(&jumps) - jump_compensator(&merton)).abs() < 1e-12);
+    }
+    
+    #[test]
+    fn test_kou_jumps() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(13);
+        // Balanced sides, but smaller down rate = larger down moves: a crash-heavy tail
+        let jumps = JumpParams {
+            lambda: 1.0,
+            mu_j: 0.0,
+            sigma_j: 0.0,
+            kind: "kou".to_string(),
+            skew: 0.0,
+            p_up: 0.5,
+            eta_up: 50.0,
+            eta_down: 20.0,
+            alpha: 0.0,
+            beta: 0.0,
+        };
+        
+        let n = 50_000;
+        let samples: Vec<f64> = (0..n).map(|_| sample_log_jump(&mut rng, &jumps)).collect();
+        let mean = samples.iter().sum::<f64>() / n as f64;
+        let m2 = samples.iter().map(|j| (j - mean).powi(2)).sum::<f64>() / n as f64;
+        let m3 = samples.iter().map(|j| (j - mean).powi(3)).sum::<f64>() / n as f64;
+        assert!((mean - (0.5 / 50.0 - 0.5 / 20.0)).abs() < 5e-4);
+        assert!(m3 / m2.powf(1.5) < 0.0);
+        
+        // Mirrored rates flip the skew
+        let mirrored = JumpParams { eta_up: 20.0, eta_down: 50.0, ..jumps.clone() };
+        let samples: Vec<f64> = (0..n).map(|_| sample_log_jump(&mut rng, &mirrored)).collect();
+        let mean = samples.iter().sum::<f64>() / n as f64;
+        assert!(samples.iter().map(|j| (j - mean).powi(3)).sum::<f64>() > 0.0);
+        
+        // Compensator matches the empirical mean jump multiplier
+        let samples: Vec<f64> = (0..n).map(|_| sample_log_jump(&mut rng, &jumps)).collect();
+        let empirical = samples.iter().map(|j| j.exp()).sum::<f64>() / n as f64 - 1.0;
+        assert!((jump_compensator(&jumps) - empirical).abs() < 1e-3);
+        
+        let second: f64 = samples.iter().map(|j| j * j).sum::<f64>() / n as f64;
+        assert!((jump_second_moment(&jumps) / second - 1.0).abs() < 0.05);
     }
 }
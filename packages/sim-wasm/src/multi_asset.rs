@@ -0,0 +1,216 @@
+use rand::Rng;
+use wasm_bindgen::prelude::*;
+use crate::simulator::{Model, PathOutcome};
+use crate::types::*;
+use crate::utils;
+use crate::{path_rng, target_hit, to_json_tagged, JS_SAFE_SEED_MASK};
+
+/// Two single-asset models driven by correlated Brownian increments, e.g. BTC and ETH
+///
+/// Each asset keeps its own regime chain, variance and jumps; only the diffusive price shocks
+/// are linked, through the Cholesky factor of [[1, rho], [rho, 1]].
+#[wasm_bindgen]
+pub struct MultiAssetEngine {
+    first: Model,
+    second: Model,
+    rho_assets: f64,
+    rng: rand::rngs::StdRng,
+}
+
+#[wasm_bindgen]
+impl MultiAssetEngine {
+    #[wasm_bindgen(constructor)]
+    pub fn new(inputs_json: &str) -> Result<MultiAssetEngine, JsValue> {
+        use rand::SeedableRng;
+        let seed = rand::rngs::StdRng::from_entropy().gen::<u64>() & JS_SAFE_SEED_MASK;
+        
+        MultiAssetEngine::new_seeded(inputs_json, seed)
+    }
+    
+    /// Engine whose RNG stream is fixed by `seed`
+    #[wasm_bindgen]
+    pub fn new_seeded(inputs_json: &str, seed: u64) -> Result<MultiAssetEngine, JsValue> {
+        utils::set_panic_hook();
+        
+        let inputs: MultiAssetInputs = serde_json::from_str(inputs_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse inputs: {}", e)))?;
+        
+        MultiAssetEngine::from_inputs(inputs, seed).map_err(|e| JsValue::from_str(&e))
+    }
+    
+    /// Probability that both legs of `target_json` settle YES, with each leg's marginal
+    #[wasm_bindgen]
+    pub fn run_joint(&mut self, target_json: &str, n_paths: u32, request_id: Option<String>) -> Result<String, JsValue> {
+        let target: JointTarget = serde_json::from_str(target_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse target: {}", e)))?;
+        let result = self.simulate_joint(target, n_paths).map_err(|e| JsValue::from_str(&e))?;
+        
+        to_json_tagged(&result, request_id.as_deref())
+    }
+}
+
+impl MultiAssetEngine {
+    fn from_inputs(inputs: MultiAssetInputs, seed: u64) -> Result<MultiAssetEngine, String> {
+        use rand::SeedableRng;
+        
+        if !(-1.0..=1.0).contains(&inputs.rho_assets) {
+            return Err(format!("rho_assets must be in [-1, 1], got {}", inputs.rho_assets));
+        }
+        // The shocks are correlated normals drawn here, so neither asset may substitute its own
+        for (name, asset) in [("first", &inputs.first), ("second", &inputs.second)] {
+            if asset.basket.is_some() || asset.qmc || asset.innovation != "normal" {
+                return Err(format!("The {} asset cannot use basket mode, qmc or non-normal innovations", name));
+            }
+        }
+        
+        let first = Model::new(inputs.first).map_err(|e| format!("Invalid first asset: {}", e))?;
+        let second = Model::new(inputs.second).map_err(|e| format!("Invalid second asset: {}", e))?;
+        if first.step_sizes() != second.step_sizes() {
+            return Err("Both assets must use the same time steps".to_string());
+        }
+        
+        Ok(MultiAssetEngine {
+            first,
+            second,
+            rho_assets: inputs.rho_assets,
+            rng: rand::rngs::StdRng::seed_from_u64(seed),
+        })
+    }
+    
+    /// Path `index` of both assets in the run seeded by `replay_seed`
+    ///
+    /// Each asset draws from its own per-path RNG, so regimes, variance and jumps are
+    /// independent; a third stream supplies the unit shocks, correlated before being handed over.
+    fn simulate_pair(
+        &self,
+        replay_seed: u64,
+        index: u32,
+        target: &JointTarget,
+        traces: Option<(&mut Vec<PathPoint>, &mut Vec<PathPoint>)>,
+    ) -> (PathOutcome, PathOutcome) {
+        let n_steps = self.first.step_sizes().len();
+        let mut shock_rng = path_rng(utils::variant_seed(replay_seed, "asset shocks"), index);
+        let independent: Vec<f64> = (0..n_steps)
+            .map(|_| shock_rng.sample(rand_distr::StandardNormal))
+            .collect();
+        let other: Vec<f64> = (0..n_steps)
+            .map(|_| shock_rng.sample(rand_distr::StandardNormal))
+            .collect();
+        
+        // Cholesky factor of the 2x2 correlation, written out so rho = +-1 stays valid
+        let orthogonal = (1.0 - self.rho_assets * self.rho_assets).max(0.0).sqrt();
+        let correlated: Vec<f64> = independent
+            .iter()
+            .zip(&other)
+            .map(|(z1, z2)| self.rho_assets * z1 + orthogonal * z2)
+            .collect();
+        
+        let (first_trace, second_trace) = match traces {
+            Some((first, second)) => (Some(first), Some(second)),
+            None => (None, None),
+        };
+        let first = self.first.simulate_shocked_path(
+            &mut path_rng(replay_seed, index),
+            first_trace,
+            Some(&target.first),
+            &independent,
+        );
+        let second = self.second.simulate_shocked_path(
+            &mut path_rng(utils::variant_seed(replay_seed, "second asset"), index),
+            second_trace,
+            Some(&target.second),
+            &correlated,
+        );
+        
+        (first, second)
+    }
+    
+    fn simulate_joint(&mut self, target: JointTarget, n_paths: u32) -> Result<JointResult, String> {
+        if n_paths == 0 {
+            return Err("n_paths must be positive".to_string());
+        }
+        
+        let replay_seed = self.rng.gen::<u64>() & JS_SAFE_SEED_MASK;
+        let (mut hits, mut hits_first, mut hits_second) = (0u32, 0u32, 0u32);
+        for i in 0..n_paths {
+            let (first, second) = self.simulate_pair(replay_seed, i, &target, None);
+            let hit_first = target_hit(&target.first, &first)?;
+            let hit_second = target_hit(&target.second, &second)?;
+            
+            hits_first += hit_first as u32;
+            hits_second += hit_second as u32;
+            hits += (hit_first && hit_second) as u32;
+        }
+        
+        let n = n_paths as f64;
+        let p = hits as f64 / n;
+        Ok(JointResult {
+            target,
+            p,
+            ci: utils::binomial_ci(hits, n_paths, 0.95),
+            fair: p * 100.0,
+            p_first: hits_first as f64 / n,
+            p_second: hits_second as f64 / n,
+            hits,
+            n: n_paths,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn asset_json(s0: f64, theta: f64) -> String {
+        format!(r#"{{
+            "s0": {s0},
+            "t": 24.0,
+            "dt": 1.0,
+            "regimes": [{{ "mu": 0.0, "heston": {{ "kappa": 2.0, "theta": {theta}, "xi": 0.0, "rho": 0.0, "var_floor": {theta} }} }}],
+            "hmm": {{ "p": [[1.0]], "pi0": [1.0] }},
+            "jumps": {{ "lambda": 0.0, "mu_j": 0.0, "sigma_j": 0.0, "kind": "merton" }}
+        }}"#)
+    }
+    
+    fn pair_engine(rho_assets: f64) -> MultiAssetEngine {
+        let json = format!(
+            r#"{{ "first": {}, "second": {}, "rho_assets": {} }}"#,
+            asset_json(100000.0, 0.0001),
+            asset_json(4000.0, 0.0004),
+            rho_assets,
+        );
+        MultiAssetEngine::from_inputs(serde_json::from_str(&json).unwrap(), 5).unwrap()
+    }
+    
+    fn above(k: f64) -> Target {
+        Target { kind: "above".to_string(), K: Some(k), ..Default::default() }
+    }
+    
+    #[test]
+    fn test_perfectly_correlated_assets_move_together() {
+        let engine = pair_engine(1.0);
+        let target = JointTarget { first: above(100000.0), second: above(4000.0) };
+        
+        let (mut first, mut second) = (Vec::new(), Vec::new());
+        engine.simulate_pair(11, 0, &target, Some((&mut first, &mut second)));
+        
+        // Log-returns scaled by each asset's step vol (sqrt(theta): QE with var_floor = theta holds it there)
+        let normalized = |points: &[PathPoint], vol: f64| -> Vec<f64> {
+            points.windows(2).map(|pair| (pair[1].price / pair[0].price).ln() / vol).collect()
+        };
+        let first = normalized(&first, 0.01);
+        let second = normalized(&second, 0.02);
+        assert_eq!(first.len(), 24);
+        for (a, b) in first.iter().zip(&second) {
+            assert!((a - b).abs() < 0.01, "{} vs {}", a, b);
+        }
+        
+        // Both legs then hit on the same paths
+        let result = pair_engine(1.0).simulate_joint(target.clone(), 2000).unwrap();
+        assert!((result.p - result.p_first).abs() < 0.01);
+        assert!((result.p - result.p_second).abs() < 0.01);
+        
+        let independent = pair_engine(0.0).simulate_joint(target, 4000).unwrap();
+        assert!((independent.p - independent.p_first * independent.p_second).abs() < 0.03);
+    }
+}
@@ -0,0 +1,181 @@
+/// Degree, inner polynomial coefficients and initial direction numbers for Sobol dimensions
+/// 2 and up (Joe & Kuo, new-joe-kuo-6.21201); dimension 1 is the van der Corput sequence
+const DIRECTION_TABLE: [(u32, u32, &[u32]); 20] = [
+    (1, 0, &[1]),
+    (2, 1, &[1, 3]),
+    (3, 1, &[1, 3, 1]),
+    (3, 2, &[1, 1, 1]),
+    (4, 1, &[1, 1, 3, 3]),
+    (4, 4, &[1, 3, 5, 13]),
+    (5, 2, &[1, 1, 5, 5, 17]),
+    (5, 4, &[1, 1, 5, 5, 5]),
+    (5, 7, &[1, 1, 7, 11, 19]),
+    (5, 11, &[1, 1, 5, 1, 1]),
+    (5, 13, &[1, 1, 1, 3, 11]),
+    (5, 14, &[1, 3, 5, 5, 31]),
+    (6, 1, &[1, 3, 3, 9, 7, 49]),
+    (6, 13, &[1, 1, 1, 15, 21, 21]),
+    (6, 16, &[1, 3, 1, 13, 27, 49]),
+    (6, 19, &[1, 1, 1, 15, 7, 5]),
+    (6, 22, &[1, 3, 1, 15, 13, 25]),
+    (6, 25, &[1, 1, 5, 5, 19, 61]),
+    (7, 1, &[1, 3, 7, 11, 23, 15, 103]),
+    (7, 4, &[1, 3, 7, 13, 13, 15, 69]),
+];
+
+/// Most Sobol dimensions available; further coordinates of a point are left to the RNG
+pub const MAX_SOBOL_DIMS: usize = DIRECTION_TABLE.len() + 1;
+
+const BITS: usize = 32;
+
+/// Sobol points in [0, 1)^dims, generated by index (gray-code order) so any point is independent
+/// of the others and a run can hand point i to path i
+pub struct Sobol {
+    directions: Vec<[u32; BITS]>,
+}
+
+impl Sobol {
+    pub fn new(dims: usize) -> Result<Sobol, String> {
+        if dims == 0 || dims > MAX_SOBOL_DIMS {
+            return Err(format!("Sobol dimensions must be in 1..={}, got {}", MAX_SOBOL_DIMS, dims));
+        }
+        
+        let mut directions = Vec::with_capacity(dims);
+        directions.push(std::array::from_fn(|k| 1u32 << (BITS - 1 - k)));
+        
+        for &(degree, coefficients, initial) in &DIRECTION_TABLE[..dims - 1] {
+            let s = degree as usize;
+            let mut v = [0u32; BITS];
+            for (k, (v, m)) in v.iter_mut().zip(initial).enumerate() {
+                *v = m << (BITS - 1 - k);
+            }
+            for k in s..BITS {
+                v[k] = v[k - s] ^ (v[k - s] >> s);
+                for j in 1..s {
+                    if (coefficients >> (s - 1 - j)) & 1 == 1 {
+                        v[k] ^= v[k - j];
+                    }
+                }
+            }
+            directions.push(v);
+        }
+        
+        Ok(Sobol { directions })
+    }
+    
+    pub fn dims(&self) -> usize {
+        self.directions.len()
+    }
+    
+    /// Point `index`, XORed with a per-dimension digital `shift` and centred in its 2^-32 cell so
+    /// no coordinate is exactly 0; a uniformly random shift makes each coordinate exactly U(0, 1)
+    pub fn point(&self, index: u32, shift: &[u32], out: &mut [f64]) {
+        let gray = index ^ (index >> 1);
+        
+        for ((v, shift), out) in self.directions.iter().zip(shift).zip(out.iter_mut()) {
+            let mut x = 0u32;
+            for (k, direction) in v.iter().enumerate() {
+                if (gray >> k) & 1 == 1 {
+                    x ^= direction;
+                }
+            }
+            *out = ((x ^ shift) as f64 + 0.5) / (1u64 << BITS) as f64;
+        }
+    }
+}
+
+/// Builds unit-variance Brownian increments over `n` steps from independent normals, spending
+/// the first normal on the endpoint and the next ones on successive midpoints
+pub struct BrownianBridge {
+    n: usize,
+    nodes: Vec<BridgeNode>,
+}
+
+struct BridgeNode {
+    left: usize,
+    mid: usize,
+    right: usize,
+    left_weight: f64,
+    right_weight: f64,
+    sd: f64,
+}
+
+impl BrownianBridge {
+    pub fn new(n: usize) -> BrownianBridge {
+        let mut nodes = Vec::with_capacity(n.saturating_sub(1));
+        let mut intervals = std::collections::VecDeque::from([(0, n)]);
+        
+        // Breadth first, so earlier normals fix coarser time scales
+        while let Some((left, right)) = intervals.pop_front() {
+            if right - left < 2 {
+                continue;
+            }
+            let mid = (left + right) / 2;
+            let span = (right - left) as f64;
+            nodes.push(BridgeNode {
+                left,
+                mid,
+                right,
+                left_weight: (right - mid) as f64 / span,
+                right_weight: (mid - left) as f64 / span,
+                sd: ((mid - left) as f64 * (right - mid) as f64 / span).sqrt(),
+            });
+            intervals.push_back((left, mid));
+            intervals.push_back((mid, right));
+        }
+        
+        BrownianBridge { n, nodes }
+    }
+    
+    /// Increments W(k+1) - W(k), each N(0, 1), from `normals.len() == n` independent normals
+    pub fn increments(&self, normals: &[f64]) -> Vec<f64> {
+        if self.n == 0 {
+            return Vec::new();
+        }
+        
+        let mut w = vec![0.0; self.n + 1];
+        w[self.n] = (self.n as f64).sqrt() * normals[0];
+        for (node, z) in self.nodes.iter().zip(&normals[1..]) {
+            w[node.mid] = node.left_weight * w[node.left] + node.right_weight * w[node.right] + node.sd * z;
+        }
+        
+        w.windows(2).map(|pair| pair[1] - pair[0]).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_sobol_is_stratified() {
+        let sobol = Sobol::new(MAX_SOBOL_DIMS).unwrap();
+        let shift = vec![0; MAX_SOBOL_DIMS];
+        let mut point = vec![0.0; MAX_SOBOL_DIMS];
+        
+        // Every dimension puts exactly one of the first 2^m points in each cell of width 2^-m
+        let m = 8;
+        let mut cells = vec![vec![0u32; 1 << m]; MAX_SOBOL_DIMS];
+        for i in 0..(1u32 << m) {
+            sobol.point(i, &shift, &mut point);
+            for (dim, x) in point.iter().enumerate() {
+                cells[dim][(x * (1 << m) as f64) as usize] += 1;
+            }
+        }
+        assert!(cells.iter().all(|dim| dim.iter().all(|&count| count == 1)));
+        assert!(Sobol::new(MAX_SOBOL_DIMS + 1).is_err());
+    }
+    
+    #[test]
+    fn test_brownian_bridge_sums_to_endpoint() {
+        for n in [1, 2, 7, 24] {
+            let bridge = BrownianBridge::new(n);
+            let normals: Vec<f64> = (0..n).map(|i| (i as f64 * 0.7).sin()).collect();
+            let increments = bridge.increments(&normals);
+            
+            assert_eq!(increments.len(), n);
+            let total: f64 = increments.iter().sum();
+            assert!((total - (n as f64).sqrt() * normals[0]).abs() < 1e-12);
+        }
+    }
+}
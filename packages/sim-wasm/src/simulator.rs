@@ -0,0 +1,1193 @@
+use rand::prelude::*;
+use crate::models;
+use crate::qmc::{BrownianBridge, Sobol, MAX_SOBOL_DIMS};
+use crate::types::*;
+use crate::utils;
+
+/// Reusable record of the random words one path consumed, replayed to its antithetic twin
+///
+/// The twin makes exactly the same draws as the recorded path (only the sign of the diffusive
+/// price shocks differs, see `Model::simulate_mirrored_path`), so replaying the words gives it
+/// identical regime, variance and jump shocks without regenerating them. The buffer is cleared
+/// for every pair but keeps its allocation, so a run allocates it once.
+#[derive(Default)]
+pub struct ShockBuffer {
+    words: Vec<u64>,
+}
+
+impl ShockBuffer {
+    pub fn new() -> Self {
+        ShockBuffer::default()
+    }
+    
+    /// Start a new path: clear the buffer and record every word drawn from `inner`
+    pub fn record<'a, R: RngCore + ?Sized>(&'a mut self, inner: &'a mut R) -> RecordingRng<'a, R> {
+        self.words.clear();
+        RecordingRng { inner, words: &mut self.words }
+    }
+    
+    /// Play the recorded words back, in order
+    pub fn replay(&self) -> ReplayRng<'_> {
+        ReplayRng { words: &self.words, cursor: 0 }
+    }
+    
+    #[cfg(test)]
+    pub fn capacity(&self) -> usize {
+        self.words.capacity()
+    }
+}
+
+pub struct RecordingRng<'a, R: RngCore + ?Sized> {
+    inner: &'a mut R,
+    words: &'a mut Vec<u64>,
+}
+
+impl<R: RngCore + ?Sized> RngCore for RecordingRng<'_, R> {
+    fn next_u32(&mut self) -> u32 {
+        let word = self.inner.next_u32();
+        self.words.push(word as u64);
+        word
+    }
+    
+    fn next_u64(&mut self) -> u64 {
+        let word = self.inner.next_u64();
+        self.words.push(word);
+        word
+    }
+    
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.inner.fill_bytes(dest);
+        for chunk in dest.chunks(8) {
+            let mut bytes = [0u8; 8];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            self.words.push(u64::from_le_bytes(bytes));
+        }
+    }
+    
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+pub struct ReplayRng<'a> {
+    words: &'a [u64],
+    cursor: usize,
+}
+
+impl ReplayRng<'_> {
+    fn next_word(&mut self) -> u64 {
+        let word = *self.words.get(self.cursor).expect("antithetic twin drew more words than its recorded path");
+        self.cursor += 1;
+        word
+    }
+}
+
+impl RngCore for ReplayRng<'_> {
+    fn next_u32(&mut self) -> u32 {
+        self.next_word() as u32
+    }
+    
+    fn next_u64(&mut self) -> u64 {
+        self.next_word()
+    }
+    
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let len = chunk.len();
+            chunk.copy_from_slice(&self.next_word().to_le_bytes()[..len]);
+        }
+    }
+    
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// How many variance steps took each QE branch
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QeCounts {
+    pub low_psi: u64,
+    pub high_psi: u64,
+    pub high_psi_zero: u64,
+}
+
+impl QeCounts {
+    fn record(&mut self, branch: models::QeBranch) {
+        match branch {
+            models::QeBranch::LowPsi => self.low_psi += 1,
+            models::QeBranch::HighPsi => self.high_psi += 1,
+            models::QeBranch::HighPsiZero => self.high_psi_zero += 1,
+        }
+    }
+    
+    pub fn add(&mut self, other: &QeCounts) {
+        self.low_psi += other.low_psi;
+        self.high_psi += other.high_psi;
+        self.high_psi_zero += other.high_psi_zero;
+    }
+    
+    pub fn total(&self) -> u64 {
+        self.low_psi + self.high_psi + self.high_psi_zero
+    }
+}
+
+/// Which of a first_touch target's two levels a path reached first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FirstTouch {
+    #[default]
+    NotMonitored,  // simulated without levels (plain `simulate_path`)
+    Neither,
+    Upper,
+    Lower,
+}
+
+/// Most substeps one step is split into for an intense jump process
+const MAX_JUMP_SUBSTEPS: usize = 1000;
+
+/// Levels [L, U] a target needs watched on every step, if any; a one-sided touch barrier leaves
+/// the other side unbounded
+pub fn touch_levels(target: &Target) -> Option<[f64; 2]> {
+    match (target.kind.as_str(), target.L, target.U, target.K) {
+        ("first_touch", Some(lower), Some(upper), _) => Some([lower, upper]),
+        ("touch", _, _, Some(k)) => match target.direction.as_deref().unwrap_or("up") {
+            "up" => Some([f64::NEG_INFINITY, k]),
+            "down" => Some([k, f64::INFINITY]),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// First level reached, and when, monitored at t = 0 and each step end (and in between with
+/// `bridge_correction`)
+struct TouchMonitor {
+    levels: Option<[f64; 2]>,
+    state: FirstTouch,
+    time: Option<f64>,
+}
+
+impl TouchMonitor {
+    fn new(levels: Option<[f64; 2]>) -> TouchMonitor {
+        let state = if levels.is_some() { FirstTouch::Neither } else { FirstTouch::NotMonitored };
+        TouchMonitor { levels, state, time: None }
+    }
+    
+    fn update(&mut self, s: f64, time: f64) {
+        if let (FirstTouch::Neither, Some([lower, upper])) = (self.state, self.levels) {
+            if s >= upper {
+                self.state = FirstTouch::Upper;
+            } else if s <= lower {
+                self.state = FirstTouch::Lower;
+            } else {
+                return;
+            }
+            self.time = Some(time);
+        }
+    }
+    
+    /// `update` that also lets the path cross between `from` and `to`: given both ends, the log
+    /// price is a Brownian bridge with `variance` over the step, which crosses a level b it ends
+    /// short of with probability exp(-2 ln(b / from) ln(b / to) / variance). `u` is a uniform
+    /// decided by the caller; a bridged crossing is stamped at the step end
+    fn update_bridged(&mut self, from: f64, to: f64, variance: f64, time: f64, u: f64) {
+        self.update(to, time);
+        
+        if let (FirstTouch::Neither, Some([lower, upper])) = (self.state, self.levels) {
+            if variance <= 0.0 {
+                return;
+            }
+            let crossed = |level: f64| (-2.0 * (level / from).ln() * (level / to).ln() / variance).exp();
+            let p_upper = if upper.is_finite() { crossed(upper) } else { 0.0 };
+            let p_lower = if lower > 0.0 && lower.is_finite() { crossed(lower) } else { 0.0 };
+            
+            // Opposite ends of u, so each level keeps its own crossing probability
+            if u < p_upper {
+                self.state = FirstTouch::Upper;
+            } else if 1.0 - u < p_lower {
+                self.state = FirstTouch::Lower;
+            } else {
+                return;
+            }
+            self.time = Some(time);
+        }
+    }
+}
+
+/// Per-path summary returned by `simulate_path`
+#[derive(Default)]
+pub struct PathOutcome {
+    pub initial_price: f64,  // s0, or the index value at t = 0 in basket mode
+    pub final_price: f64,
+    pub regime_switches: u32,
+    pub min_price: f64,      // running minimum over the horizon, t = 0 included
+    pub max_price: f64,      // running maximum, likewise
+    pub realized_vol: f64,   // annualized standard deviation of the step log-returns per sqrt(hour)
+    pub qe: QeCounts,        // variance steps by QE branch, warm-up included
+    pub first_touch: FirstTouch,
+    pub touch_time: Option<f64>,  // elapsed hours when first_touch was decided, if it was
+    pub terminal_shock: f64,  // signed unit price shocks z1 weighted by sqrt(dt / horizon): the path's N(0, 1) terminal Brownian value; zero in basket mode
+    pub regime_time: Vec<f64>,  // hours spent in each regime over the horizon
+}
+
+/// Running sums of per-step log-returns, each divided by sqrt(dt) so steps of every size
+/// estimate the same hourly variance, for the realized volatility
+#[derive(Default)]
+struct LogReturnStats {
+    n: u32,
+    sum: f64,
+    sum_sq: f64,
+}
+
+impl LogReturnStats {
+    fn push(&mut self, from: f64, to: f64, dt: f64) {
+        let r = (to / from).ln() / dt.sqrt();
+        self.n += 1;
+        self.sum += r;
+        self.sum_sq += r * r;
+    }
+    
+    fn annualized_vol(&self) -> f64 {
+        if self.n < 2 {
+            return 0.0;
+        }
+        
+        let n = self.n as f64;
+        let variance = ((self.sum_sq - self.sum * self.sum / n) / (n - 1.0)).max(0.0);
+        (variance * utils::HOURS_PER_YEAR).sqrt()
+    }
+}
+
+/// Validated simulation inputs plus anything derived from them once up front
+///
+/// Immutable once built, so native workers can share one by reference while each owns its RNG.
+pub struct Model {
+    pub sim_inputs: SimInputs,
+    basket_factor: Option<Vec<Vec<f64>>>,  // Cholesky factor of the basket correlation
+    stationary: Option<Vec<f64>>,          // stationary distribution of hmm.p, when it exists
+    qmc: Option<QmcShocks>,                // when sim_inputs.qmc is set
+    steps: Vec<f64>,                       // size of every step of the horizon, see `step_sizes`
+    student_t: Option<(rand_distr::StudentT<f64>, f64)>,  // t distribution and its unit-variance scale, for student_t innovations
+    price_tilt: f64,                       // mean of the terminal shock, see `with_price_tilt`
+}
+
+/// The regime, HMM and jump parameters a step reads, from the base inputs or a scenario switch
+struct ActiveParams<'a> {
+    regimes: &'a RegimeSet,
+    hmm: &'a HMM,
+    jumps: &'a JumpParams,
+}
+
+/// Sobol-driven price shocks: the leading bridge normals come from the Sobol point, any beyond
+/// `MAX_SOBOL_DIMS` from the RNG
+struct QmcShocks {
+    sobol: Sobol,
+    bridge: BrownianBridge,
+    n_steps: usize,
+}
+
+/// Step sizes covering the horizon: ceil(t / dt) steps of `dt`, or with a `dt_schedule` as many
+/// equal steps per segment as its dt needs, so each threshold falls on a step boundary
+fn step_sizes(inputs: &SimInputs) -> Vec<f64> {
+    if inputs.dt_schedule.is_empty() {
+        return vec![inputs.dt; (inputs.t / inputs.dt).ceil() as usize];
+    }
+    
+    let mut steps = Vec::new();
+    for (i, &(start, dt)) in inputs.dt_schedule.iter().enumerate() {
+        let end = inputs.dt_schedule.get(i + 1).map_or(inputs.t, |next| next.0);
+        // The tolerance keeps a segment that is a whole number of steps from gaining one to rounding
+        let n = ((end - start) / dt - 1e-9).ceil().max(1.0);
+        steps.extend(std::iter::repeat_n((end - start) / n, n as usize));
+    }
+    steps
+}
+
+fn validate_jumps(scope: &str, jumps: &JumpParams) -> Result<(), String> {
+    match jumps.kind.as_str() {
+        "merton" | "skew_normal" => Ok(()),
+        "kou" => {
+            if !(jumps.p_up >= 0.0 && jumps.p_up <= 1.0) {
+                return Err(format!("{}kou p_up must be in [0, 1], got {}", scope, jumps.p_up));
+            }
+            // E[e^J] is infinite unless up moves decay faster than e^(-J)
+            if !(jumps.eta_up > 1.0 && jumps.eta_up.is_finite()) {
+                return Err(format!("{}kou eta_up must be greater than 1, got {}", scope, jumps.eta_up));
+            }
+            if !(jumps.eta_down > 0.0 && jumps.eta_down.is_finite()) {
+                return Err(format!("{}kou eta_down must be positive, got {}", scope, jumps.eta_down));
+            }
+            Ok(())
+        },
+        other => Err(format!("{}jump kind must be \"merton\", \"kou\" or \"skew_normal\", got \"{}\"", scope, other)),
+    }?;
+    
+    if !(jumps.alpha >= 0.0 && jumps.alpha.is_finite()) {
+        return Err(format!("{}hawkes alpha must be non-negative, got {}", scope, jumps.alpha));
+    }
+    // Each jump triggers alpha / beta more on average; at 1 or above the intensity explodes
+    if jumps.alpha > 0.0 && !(jumps.beta > jumps.alpha && jumps.beta.is_finite()) {
+        return Err(format!("{}hawkes beta must exceed alpha = {}, got {}", scope, jumps.alpha, jumps.beta));
+    }
+    Ok(())
+}
+
+/// A drift term structure must start at t = 0, so it covers the whole horizon, with strictly
+/// increasing start times inside it
+fn validate_mu_schedule(name: &str, schedule: &[(f64, f64)], t: f64) -> Result<(), String> {
+    let Some(&(first, _)) = schedule.first() else {
+        return Ok(());
+    };
+    if first != 0.0 {
+        return Err(format!("{} mu_schedule must start at hour 0 to cover the horizon, got {}", name, first));
+    }
+    for pair in schedule.windows(2) {
+        if pair[1].0.is_nan() || pair[1].0 <= pair[0].0 {
+            return Err(format!("{} mu_schedule hours must be strictly increasing, got {} after {}", name, pair[1].0, pair[0].0));
+        }
+    }
+    if let Some(&(from_hour, mu)) = schedule.iter().find(|&&(from_hour, mu)| from_hour >= t || !mu.is_finite()) {
+        return Err(format!("{} mu_schedule piece at hour {} (mu = {}) must start before t = {} with a finite mu", name, from_hour, mu, t));
+    }
+    Ok(())
+}
+
+/// Reject inputs the simulation loop cannot handle
+fn validate_inputs(inputs: &SimInputs) -> Result<(), String> {
+    let n_regimes = inputs.regimes.len();
+    if n_regimes == 0 {
+        return Err("At least one regime is required".to_string());
+    }
+    
+    let mut regime_sets = vec![("", &inputs.regimes)];
+    if let Some(basket) = &inputs.basket {
+        regime_sets.extend(basket.assets.iter().map(|asset| ("basket ", &asset.regimes)));
+    }
+    for (scope, regimes) in regime_sets {
+        if regimes.len() != n_regimes {
+            return Err(format!("{}regimes define {} states, expected {}", scope, regimes.len(), n_regimes));
+        }
+        for (regime, params) in regimes.iter().enumerate() {
+            if !(params.jump_vol_spike >= 0.0 && params.jump_vol_spike.is_finite()) {
+                return Err(format!("{}{} jump_vol_spike must be non-negative, got {}", scope, regime_name(regime), params.jump_vol_spike));
+            }
+            validate_mu_schedule(&format!("{}{}", scope, regime_name(regime)), &params.mu_schedule, inputs.t)?;
+            for heston in std::iter::once(&params.heston).chain(params.heston2.as_ref()) {
+                let floor = heston.var_floor;
+                if !(floor > 0.0 && floor.is_finite()) {
+                    return Err(format!("{}{} var_floor must be positive, got {}", scope, regime_name(regime), floor));
+                }
+            }
+        }
+    }
+    
+    validate_jumps("", &inputs.jumps)?;
+    for asset in inputs.basket.iter().flat_map(|basket| &basket.assets) {
+        validate_jumps("basket ", &asset.jumps)?;
+    }
+    
+    let hmm = &inputs.hmm;
+    for p in std::iter::once(&hmm.p).chain(hmm.schedule.iter().map(|entry| &entry.p)) {
+        if p.len() != n_regimes || p.iter().any(|row| row.len() != n_regimes) {
+            return Err(format!("HMM transition matrix must be {}x{}, one row and column per regime", n_regimes, n_regimes));
+        }
+        if p.iter().flatten().any(|x| !(*x >= 0.0 && x.is_finite())) {
+            return Err("HMM transition probabilities must be non-negative".to_string());
+        }
+        // Per-step stochastic matrix (see models::update_regime_with), not a rate matrix
+        if let Some(row) = p.iter().find(|row| (row.iter().sum::<f64>() - 1.0).abs() > 1e-9) {
+            return Err(format!("HMM transition matrix rows must sum to 1, got {:?}", row));
+        }
+    }
+    if hmm.pi0.len() != n_regimes {
+        return Err(format!("HMM pi0 has {} entries, expected {}", hmm.pi0.len(), n_regimes));
+    }
+    
+    if !(inputs.warmup_hours >= 0.0 && inputs.warmup_hours.is_finite()) {
+        return Err(format!("warmup_hours must be non-negative, got {}", inputs.warmup_hours));
+    }
+    
+    let schedule = &inputs.hmm.schedule;
+    if schedule.iter().any(|entry| !entry.from_hour.is_finite()) {
+        return Err("HMM schedule from_hour must be finite".to_string());
+    }
+    if schedule.windows(2).any(|pair| pair[1].from_hour < pair[0].from_hour) {
+        return Err("HMM schedule must be sorted by from_hour".to_string());
+    }
+    
+    if let Some(ou) = &inputs.drift_ou {
+        if !(ou.speed >= 0.0 && ou.vol >= 0.0) {
+            return Err("drift_ou speed and vol must be non-negative".to_string());
+        }
+    }
+    
+    if let Some(carry) = &inputs.carry {
+        if !(carry.initial.is_finite() && carry.mean.is_finite()) {
+            return Err("carry initial and mean must be finite".to_string());
+        }
+        if !(carry.speed >= 0.0 && carry.vol >= 0.0 && carry.speed.is_finite() && carry.vol.is_finite()) {
+            return Err("carry speed and vol must be non-negative".to_string());
+        }
+    }
+    
+    for shock in &inputs.vol_shocks {
+        if !(shock.vol_multiplier >= 0.0 && shock.vol_multiplier.is_finite()) {
+            return Err(format!("Vol shock multiplier must be non-negative, got {}", shock.vol_multiplier));
+        }
+        if shock.end < shock.start || shock.start.is_nan() || shock.end.is_nan() {
+            return Err(format!("Vol shock window [{}, {}) ends before it starts", shock.start, shock.end));
+        }
+    }
+    
+    if !matches!(inputs.variance_scheme.as_str(), "qe" | "full_truncation") {
+        return Err(format!("variance_scheme must be \"qe\" or \"full_truncation\", got \"{}\"", inputs.variance_scheme));
+    }
+    
+    match inputs.innovation.as_str() {
+        "normal" => {},
+        "student_t" if !(inputs.df > 2.0 && inputs.df.is_finite()) => {
+            return Err(format!("student_t innovations need df > 2 for a finite variance, got {}", inputs.df));
+        },
+        // Sobol points map to shocks through the normal quantile
+        "student_t" if inputs.qmc => return Err("student_t innovations are not supported with qmc".to_string()),
+        "student_t" => {},
+        other => return Err(format!("innovation must be \"normal\" or \"student_t\", got \"{}\"", other)),
+    }
+    
+    if !matches!(inputs.pricing_measure.as_str(), "real_world" | "risk_neutral") {
+        return Err(format!("pricing_measure must be \"real_world\" or \"risk_neutral\", got \"{}\"", inputs.pricing_measure));
+    }
+    if !inputs.r.is_finite() {
+        return Err(format!("r must be finite, got {}", inputs.r));
+    }
+    
+    if let Some(&(start, _)) = inputs.dt_schedule.first() {
+        if start != 0.0 {
+            return Err(format!("dt_schedule must start at time 0, got {}", start));
+        }
+    }
+    for (i, &(threshold, dt)) in inputs.dt_schedule.iter().enumerate() {
+        if !(dt > 0.0 && dt.is_finite()) {
+            return Err(format!("dt_schedule step sizes must be positive, got {} at time {}", dt, threshold));
+        }
+        if threshold.is_nan() || threshold >= inputs.t {
+            return Err(format!("dt_schedule thresholds must lie in [0, t), got {}", threshold));
+        }
+        if i > 0 && threshold <= inputs.dt_schedule[i - 1].0 {
+            return Err(format!("dt_schedule thresholds must be strictly increasing, got {} after {}", threshold, inputs.dt_schedule[i - 1].0));
+        }
+    }
+    
+    if let Some(threshold) = inputs.jump_substep_threshold {
+        if !(threshold > 0.0 && threshold.is_finite()) {
+            return Err(format!("jump_substep_threshold must be positive, got {}", threshold));
+        }
+    }
+    
+    if inputs.qmc && inputs.basket.is_some() {
+        return Err("qmc is not supported in basket mode".to_string());
+    }
+    if inputs.bridge_correction && inputs.basket.is_some() {
+        return Err("bridge_correction is not supported in basket mode".to_string());
+    }
+    
+    if let Some(switch) = &inputs.scenario_switch {
+        if !(switch.at_hour >= 0.0 && switch.at_hour.is_finite()) {
+            return Err(format!("Scenario switch at_hour must be non-negative, got {}", switch.at_hour));
+        }
+        if switch.inputs.regimes.len() != n_regimes {
+            return Err("Scenario switch inputs must keep the number of regimes".to_string());
+        }
+        if inputs.basket.is_some() {
+            return Err("Scenario switching is not supported in basket mode".to_string());
+        }
+        // The switched parameter sets must hold up in place of the base ones
+        let switched = SimInputs {
+            regimes: switch.inputs.regimes.clone(),
+            hmm: switch.inputs.hmm.clone(),
+            jumps: switch.inputs.jumps.clone(),
+            scenario_switch: None,
+            ..inputs.clone()
+        };
+        validate_inputs(&switched).map_err(|e| format!("Scenario switch: {}", e))?;
+    }
+    
+    if let Some(basket) = &inputs.basket {
+        let n = basket.assets.len();
+        if n == 0 {
+            return Err("Basket must contain at least one asset".to_string());
+        }
+        if basket.correlation.len() != n || basket.correlation.iter().any(|row| row.len() != n) {
+            return Err(format!("Basket correlation must be a {}x{} matrix", n, n));
+        }
+        for i in 0..n {
+            if (basket.correlation[i][i] - 1.0).abs() > 1e-9 {
+                return Err("Basket correlation diagonal must be 1".to_string());
+            }
+            for j in 0..i {
+                if (basket.correlation[i][j] - basket.correlation[j][i]).abs() > 1e-9 {
+                    return Err("Basket correlation must be symmetric".to_string());
+                }
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+impl Model {
+    pub fn new(sim_inputs: SimInputs) -> Result<Model, String> {
+        validate_inputs(&sim_inputs)?;
+        
+        let basket_factor = match &sim_inputs.basket {
+            Some(basket) => Some(
+                utils::cholesky(&basket.correlation)
+                    .map_err(|e| format!("Invalid basket correlation: {}", e))?,
+            ),
+            None => None,
+        };
+        
+        let stationary = models::stationary_distribution(&sim_inputs.hmm.p);
+        if sim_inputs.use_stationary_pi0 && stationary.is_none() {
+            return Err("use_stationary_pi0 needs a transition matrix with a unique stationary distribution".to_string());
+        }
+        
+        let steps = step_sizes(&sim_inputs);
+        let qmc = if sim_inputs.qmc {
+            let n_steps = steps.len();
+            Some(QmcShocks {
+                sobol: Sobol::new(n_steps.clamp(1, MAX_SOBOL_DIMS))?,
+                bridge: BrownianBridge::new(n_steps),
+                n_steps,
+            })
+        } else {
+            None
+        };
+        
+        // Var[t_df] = df / (df - 2)
+        let student_t = match sim_inputs.innovation.as_str() {
+            "student_t" => Some((
+                rand_distr::StudentT::new(sim_inputs.df).map_err(|e| format!("Invalid student_t df: {}", e))?,
+                ((sim_inputs.df - 2.0) / sim_inputs.df).sqrt(),
+            )),
+            _ => None,
+        };
+        
+        Ok(Model { sim_inputs, basket_factor, stationary, qmc, steps, student_t, price_tilt: 0.0 })
+    }
+    
+    /// The same model with every step's unit price shock shifted by `tilt * sqrt(dt / horizon)`, so
+    /// the terminal shock is N(tilt, 1) rather than N(0, 1). Paths drawn this way are reweighted
+    /// to the model's own measure by exp(-tilt * terminal_shock + tilt^2 / 2)
+    pub fn with_price_tilt(self, tilt: f64) -> Model {
+        Model { price_tilt: tilt, ..self }
+    }
+    
+    /// Size of every step from t = 0, in hours
+    pub fn step_sizes(&self) -> &[f64] {
+        &self.steps
+    }
+    
+    /// Time actually simulated: t rounded up to a whole number of steps
+    pub fn horizon(&self) -> f64 {
+        self.steps.iter().sum()
+    }
+    
+    /// Unit price shocks for every step of path `index` of the run seeded by `replay_seed`, or None
+    /// unless `qmc` is set. The whole run shares one random digital shift, so each path is still an
+    /// unbiased draw while the run as a whole stays stratified
+    pub fn qmc_price_shocks(&self, replay_seed: u64, index: u32) -> Option<Vec<f64>> {
+        let qmc = self.qmc.as_ref()?;
+        
+        let mut shift_rng = StdRng::seed_from_u64(utils::variant_seed(replay_seed, "sobol shift"));
+        let shift: Vec<u32> = (0..qmc.sobol.dims()).map(|_| shift_rng.gen()).collect();
+        
+        let mut normals = vec![0.0; qmc.n_steps.max(qmc.sobol.dims())];
+        qmc.sobol.point(index, &shift, &mut normals);
+        for u in &mut normals[..qmc.sobol.dims()] {
+            *u = utils::normal_quantile(*u);
+        }
+        
+        let mut pad_rng = StdRng::seed_from_u64(utils::path_seed(utils::variant_seed(replay_seed, "sobol pad"), index as u64));
+        for z in &mut normals[qmc.sobol.dims()..] {
+            *z = pad_rng.sample(rand_distr::StandardNormal);
+        }
+        
+        Some(qmc.bridge.increments(&normals))
+    }
+    
+    pub fn stationary_distribution(&self) -> Option<Vec<f64>> {
+        self.stationary.clone()
+    }
+    
+    /// Initial regime distribution actually used by the paths
+    pub fn pi0(&self) -> &[f64] {
+        match &self.stationary {
+            Some(pi) if self.sim_inputs.use_stationary_pi0 => pi,
+            _ => &self.sim_inputs.hmm.pi0,
+        }
+    }
+    
+    /// Draw the regime at the start of a path from `pi0` (one uniform; any shortfall goes to the last regime)
+    fn initial_regime<R: Rng + ?Sized>(&self, rng: &mut R) -> Regime {
+        let u: f64 = rng.gen();
+        let pi0 = self.pi0();
+        
+        let mut cumulative = 0.0;
+        for (regime, weight) in pi0.iter().enumerate() {
+            cumulative += weight;
+            if u < cumulative {
+                return regime;
+            }
+        }
+        pi0.len() - 1
+    }
+    
+    fn regime_params<'a>(&self, regimes: &'a RegimeSet, regime: Regime) -> &'a RegimeParams {
+        &regimes[regime]
+    }
+    
+    /// Starting level of each variance factor: the long-run theta (second factor from whichever
+    /// regime defines one first, 0 when none does)
+    fn initial_variance(&self, regimes: &RegimeSet) -> [f64; 2] {
+        let second = regimes.iter().find_map(|params| params.heston2.as_ref());
+        [regimes[0].heston.theta, second.map_or(0.0, |heston| heston.theta)]
+    }
+    
+    /// Advance the variance factors one step under `params`, returning the total variance
+    ///
+    /// A regime without `heston2` is single-factor: its second factor is frozen and unused.
+    fn step_variance<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        factors: &mut [f64; 2],
+        params: &RegimeParams,
+        dt: f64,
+        qe: &mut QeCounts,
+    ) -> f64 {
+        let full_truncation = self.sim_inputs.variance_scheme == "full_truncation";
+        let mut step = |v: f64, heston: &HestonParams| {
+            if full_truncation {
+                models::simulate_heston_variance_full_truncation(rng, v, heston, dt)
+            } else {
+                let (v_next, branch) = models::simulate_heston_variance_branch(rng, v, heston, dt);
+                qe.record(branch);
+                v_next
+            }
+        };
+        
+        factors[0] = step(factors[0], &params.heston);
+        match &params.heston2 {
+            Some(heston2) => {
+                factors[1] = step(factors[1], heston2);
+                factors[0] + factors[1]
+            },
+            None => factors[0],
+        }
+    }
+    
+    /// Feed a step's jumps back into the variance: the first factor rises by the regime's spike
+    /// times theta for each of them, and then mean-reverts as usual. The step's own price update
+    /// has already used its variance, so the spike shows from the next step on
+    pub fn apply_jump_vol_spike(&self, factors: &mut [f64; 2], params: &RegimeParams, n_jumps: u64) {
+        factors[0] += n_jumps as f64 * params.jump_vol_spike * params.heston.theta;
+    }
+    
+    /// Hour the scenario switch takes over, or None when there is none or no step starts at or after it
+    pub fn scenario_switch_hour(&self) -> Option<f64> {
+        let at_hour = self.sim_inputs.scenario_switch.as_ref()?.at_hour;
+        
+        // Step start times accumulate as in the path loop, so this agrees with `active_inputs`
+        let mut time = 0.0;
+        self.steps
+            .iter()
+            .any(|dt| {
+                let fires = time >= at_hour;
+                time += dt;
+                fires
+            })
+            .then_some(at_hour)
+    }
+    
+    /// Parameter sets in force for the step starting at `time`: the scenario's second set after its switch
+    fn active_inputs(&self, time: f64) -> ActiveParams<'_> {
+        match &self.sim_inputs.scenario_switch {
+            Some(switch) if time >= switch.at_hour => ActiveParams {
+                regimes: &switch.inputs.regimes,
+                hmm: &switch.inputs.hmm,
+                jumps: &switch.inputs.jumps,
+            },
+            _ => ActiveParams {
+                regimes: &self.sim_inputs.regimes,
+                hmm: &self.sim_inputs.hmm,
+                jumps: &self.sim_inputs.jumps,
+            },
+        }
+    }
+    
+    /// Number of variance burn-in steps simulated before t = 0
+    fn warmup_steps(&self) -> usize {
+        (self.sim_inputs.warmup_hours / self.sim_inputs.dt).ceil() as usize
+    }
+    
+    /// Variance multiplier for the step starting at `time`; overlapping windows compound
+    fn variance_multiplier(&self, time: f64) -> f64 {
+        self.sim_inputs
+            .vol_shocks
+            .iter()
+            .filter(|shock| time >= shock.start && time < shock.end)
+            .map(|shock| shock.vol_multiplier * shock.vol_multiplier)
+            .product()
+    }
+    
+    /// Substeps for a step of `dt` at jump rate `intensity`: 1, or enough to bring the expected jump
+    /// count per substep down to `jump_substep_threshold` once intensity * dt exceeds it
+    fn jump_substeps(&self, intensity: f64, dt: f64) -> usize {
+        match self.sim_inputs.jump_substep_threshold {
+            Some(threshold) if intensity * dt > threshold => {
+                ((intensity * dt / threshold).ceil() as usize).min(MAX_JUMP_SUBSTEPS)
+            },
+            _ => 1,
+        }
+    }
+    
+    /// Unit-variance diffusive shock: a standard normal, or a Student-t scaled down to variance 1
+    fn innovation<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        match &self.student_t {
+            Some((t, scale)) => scale * rng.sample(t),
+            None => rng.sample(rand_distr::StandardNormal),
+        }
+    }
+    
+    /// Drift before carry: the real-world `mu` as given, or the risk-free `r` under the pricing
+    /// measure (the OU drift is still stepped, so the draws are the same either way)
+    fn measure_drift(&self, mu: f64) -> f64 {
+        match self.sim_inputs.pricing_measure.as_str() {
+            "risk_neutral" => self.sim_inputs.r,
+            _ => mu,
+        }
+    }
+    
+    /// Advance the carry rate one step (an OU/Vasicek process); zero when no carry is modelled
+    fn step_carry<R: Rng + ?Sized>(&self, rng: &mut R, carry_t: &mut f64, dt: f64) -> f64 {
+        if let Some(carry) = &self.sim_inputs.carry {
+            *carry_t = models::simulate_ou(rng, *carry_t, carry.mean, carry.speed, carry.vol, dt);
+        }
+        *carry_t
+    }
+    
+    /// Deterministic path with every shock at its expected value, for checking drift signs
+    ///
+    /// Each step grows the price by exp((mu - compensator) * dt): the diffusive factor
+    /// exp(sqrt(v dt) z - v dt / 2) is replaced by its mean of 1 and no jump fires, while the jump
+    /// compensator still applies. Drift terms are averaged over the regime distribution pi0
+    /// carried forward through the transition matrices (warm-up and schedule included), and the
+    /// OU drift and carry follow their conditional means.
+    pub fn expected_path(&self) -> Result<Vec<PathPoint>, String> {
+        if self.sim_inputs.basket.is_some() {
+            return Err("The expected path is not supported in basket mode".to_string());
+        }
+        
+        let propagate = |dist: &[f64], p: &[Vec<f64>]| -> Vec<f64> {
+            (0..dist.len()).map(|j| dist.iter().zip(p).map(|(w, row)| w * row[j]).sum()).collect()
+        };
+        let mean_mu = |dist: &[f64], regimes: &RegimeSet, time: f64| -> f64 {
+            dist.iter().zip(regimes.iter()).map(|(w, params)| w * models::mu_at(params, time)).sum()
+        };
+        
+        let mut dist = self.pi0().to_vec();
+        for _ in 0..self.warmup_steps() {
+            dist = propagate(&dist, &self.sim_inputs.hmm.p);
+        }
+        
+        let mut s = self.sim_inputs.s0;
+        let mut time = 0.0;
+        let mut mu_t = mean_mu(&dist, &self.sim_inputs.regimes, 0.0);
+        let mut carry_t = self.sim_inputs.carry.as_ref().map_or(0.0, |carry| carry.initial);
+        let mut points = vec![PathPoint { t: time, price: s }];
+        
+        for &dt in &self.steps {
+            let active = self.active_inputs(time);
+            dist = propagate(&dist, models::transition_matrix_at(active.hmm, time));
+            
+            let regime_mu = mean_mu(&dist, active.regimes, time);
+            if let Some(ou) = &self.sim_inputs.drift_ou {
+                mu_t = regime_mu + (mu_t - regime_mu) * (-ou.speed * dt).exp();
+            }
+            if let Some(carry) = &self.sim_inputs.carry {
+                carry_t = carry.mean + (carry_t - carry.mean) * (-carry.speed * dt).exp();
+            }
+            let mu = self.measure_drift(if self.sim_inputs.drift_ou.is_some() { mu_t } else { regime_mu }) - carry_t;
+            
+            s *= ((mu - models::jump_compensator(active.jumps)) * dt).exp();
+            time += dt;
+            points.push(PathPoint { t: time, price: s });
+        }
+        
+        Ok(points)
+    }
+    
+    /// Only the regime chain of one path: the regime at t = 0 and during each step
+    ///
+    /// Regimes evolve independently of price and variance, so this draws far fewer numbers than
+    /// a full path while following the same transition law (warm-up and schedule included).
+    pub fn simulate_regimes<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<Regime> {
+        let mut regime = self.initial_regime(rng);
+        
+        for _ in 0..self.warmup_steps() {
+            regime = models::update_regime(rng, regime, &self.sim_inputs.hmm);
+        }
+        
+        let mut regimes = Vec::with_capacity(self.steps.len() + 1);
+        regimes.push(regime);
+        let mut time = 0.0;
+        for dt in &self.steps {
+            let p = models::transition_matrix_at(self.active_inputs(time).hmm, time);
+            regime = models::update_regime_with(rng, regime, p);
+            regimes.push(regime);
+            time += dt;
+        }
+        
+        regimes
+    }
+    
+    /// Generic over the RNG so production code stays monomorphized over `StdRng`, while tests and
+    /// callers with their own generator can pass a `&mut dyn RngCore`
+    pub fn simulate_path<R: Rng + ?Sized>(&self, rng: &mut R, trace: Option<&mut Vec<PathPoint>>) -> PathOutcome {
+        self.simulate_signed_path(rng, trace, 1.0, None, None)
+    }
+    
+    /// `simulate_path` that also watches any levels `target` is scored on (touch, first_touch), with the same draws
+    pub fn simulate_target_path<R: Rng + ?Sized>(&self, rng: &mut R, trace: Option<&mut Vec<PathPoint>>, target: &Target) -> PathOutcome {
+        self.simulate_signed_path(rng, trace, 1.0, touch_levels(target), None)
+    }
+    
+    /// `simulate_path` (or `simulate_target_path`) with the diffusive price shocks taken from
+    /// `price_shocks`, one per step; the RNG draws are unchanged, so everything else matches
+    pub fn simulate_shocked_path<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        trace: Option<&mut Vec<PathPoint>>,
+        target: Option<&Target>,
+        price_shocks: &[f64],
+    ) -> PathOutcome {
+        self.simulate_signed_path(rng, trace, 1.0, target.and_then(touch_levels), Some(price_shocks))
+    }
+    
+    /// Antithetic twin of `simulate_target_path` (or of `simulate_path` without a target): fed an
+    /// identical RNG stream, it makes the same draws but negates the diffusive price shocks,
+    /// `price_shocks` (the QMC ones) included. Regime switches, variance and jumps are shared.
+    pub fn simulate_mirrored_path<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        trace: Option<&mut Vec<PathPoint>>,
+        target: Option<&Target>,
+        price_shocks: Option<&[f64]>,
+    ) -> PathOutcome {
+        self.simulate_signed_path(rng, trace, -1.0, target.and_then(touch_levels), price_shocks)
+    }
+    
+    fn simulate_signed_path<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        trace: Option<&mut Vec<PathPoint>>,
+        shock_sign: f64,
+        touch: Option<[f64; 2]>,
+        price_shocks: Option<&[f64]>,
+    ) -> PathOutcome {
+        if self.sim_inputs.basket.is_some() {
+            return self.simulate_basket_path(rng, trace, shock_sign, touch);
+        }
+        
+        self.simulate_single_path(rng, trace, None, shock_sign, touch, price_shocks)
+    }
+    
+    /// `simulate_path` recording the full state after every step, for debugging a single path
+    pub fn simulate_path_steps<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        steps: &mut Vec<PathStep>,
+        price_shocks: Option<&[f64]>,
+    ) -> Result<PathOutcome, String> {
+        if self.sim_inputs.basket.is_some() {
+            return Err("Step-by-step replay is not supported in basket mode".to_string());
+        }
+        
+        Ok(self.simulate_single_path(rng, None, Some(steps), 1.0, None, price_shocks))
+    }
+    
+    fn simulate_single_path<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        mut trace: Option<&mut Vec<PathPoint>>,
+        mut steps: Option<&mut Vec<PathStep>>,
+        shock_sign: f64,
+        touch: Option<[f64; 2]>,
+        price_shocks: Option<&[f64]>,
+    ) -> PathOutcome {
+        let dt = self.sim_inputs.dt;
+        
+        // Initialize state
+        let mut s = self.sim_inputs.s0;
+        let mut factors = self.initial_variance(&self.sim_inputs.regimes); // Start with long-term vol
+        let mut regime = self.initial_regime(rng);
+        
+        let mut qe = QeCounts::default();
+        
+        // Warm-up: let regime and variance relax before the horizon starts, price held at s0.
+        // The HMM schedule is keyed to horizon time, so warm-up uses the base matrix
+        for _ in 0..self.warmup_steps() {
+            regime = models::update_regime(rng, regime, &self.sim_inputs.hmm);
+            let params = self.regime_params(&self.sim_inputs.regimes, regime);
+            self.step_variance(rng, &mut factors, params, dt, &mut qe);
+        }
+        
+        let mut regime_switches = 0u32;
+        let mut log_returns = LogReturnStats::default();
+        let mut min_price = s;
+        let mut max_price = s;
+        let mut time = 0.0;
+        let mut mu_t = models::mu_at(self.regime_params(&self.sim_inputs.regimes, regime), time);
+        let mut carry_t = self.sim_inputs.carry.as_ref().map_or(0.0, |carry| carry.initial);
+        let mut first_touch = TouchMonitor::new(touch);
+        first_touch.update(s, time);
+        let mut terminal_shock = 0.0;
+        let horizon = self.horizon();
+        let mut regime_time = vec![0.0; self.sim_inputs.hmm.pi0.len()];
+        let mut excitation = 0.0;
+        
+        if let Some(points) = trace.as_deref_mut() {
+            points.push(PathPoint { t: time, price: s });
+        }
+        
+        // Simulate path
+        for (step, &dt) in self.steps.iter().enumerate() {
+            let active = self.active_inputs(time);
+            
+            // Update regime
+            let p = models::transition_matrix_at(active.hmm, time);
+            let next_regime = models::update_regime_with(rng, regime, p);
+            if next_regime != regime {
+                regime_switches += 1;
+            }
+            regime = next_regime;
+            regime_time[regime] += dt;
+            
+            // Get current parameters
+            let params = self.regime_params(active.regimes, regime);
+            
+            // Simulate variance (Heston, optionally double-Heston)
+            let v = self.step_variance(rng, &mut factors, params, dt, &mut qe);
+            
+            // Drift is either the regime's mu (from its term structure, if any) or an OU process
+            // around it, or r when pricing
+            let regime_mu = models::mu_at(params, time);
+            let mu = self.measure_drift(match &self.sim_inputs.drift_ou {
+                Some(ou) => {
+                    mu_t = models::simulate_ou(rng, mu_t, regime_mu, ou.speed, ou.vol, dt);
+                    mu_t
+                },
+                None => regime_mu,
+            }) - self.step_carry(rng, &mut carry_t, dt);
+            
+            // Same draws as models::simulate_price_with_jumps; only z1 drives the price. The
+            // antithetic twin negates z1 alone: z2 (unused), the QE variance uniforms/normals,
+            // regime uniforms and jump draws are replayed unchanged. QMC shocks replace z1 after
+            // it is drawn, keeping the stream aligned, and any price tilt shifts it. Student-t
+            // innovations replace both normals
+            let z1 = self.innovation(rng);
+            let z1 = price_shocks.map_or(z1, |shocks| shocks[step]) + self.price_tilt * (dt / horizon).sqrt();
+            let _z2 = self.innovation(rng);
+            terminal_shock += shock_sign * z1 * (dt / horizon).sqrt();
+            
+            // Simulate price with jumps, under any scheduled vol shock. An intense jump process
+            // splits the step into substeps whose diffusive shocks are bridged to sum to z1, so
+            // antithetic and QMC shocks keep their meaning; one substep is the plain step. Under
+            // Hawkes clustering the intensity is the base lambda plus the excitation of past jumps
+            let v_step = v * self.variance_multiplier(time);
+            let n_sub = self.jump_substeps(active.jumps.lambda + excitation, dt);
+            let sub_dt = dt / n_sub as f64;
+            let mut remaining = (n_sub as f64).sqrt() * shock_sign * z1;
+            let mut step_jumps = 0;
+            let step_start = s;
+            for k in 0..n_sub {
+                let left = (n_sub - k) as f64;
+                let w = if left > 1.0 {
+                    remaining / left + ((left - 1.0) / left).sqrt() * rng.sample::<f64, _>(rand_distr::StandardNormal)
+                } else {
+                    remaining
+                };
+                remaining -= w;
+                
+                let intensity = active.jumps.lambda + excitation;
+                let (new_s, n_jumps) = models::simulate_price_with_shock(rng, s, v_step, mu, active.jumps, intensity, sub_dt, w);
+                excitation = models::hawkes_excitation(active.jumps, excitation, n_jumps, sub_dt);
+                step_jumps += n_jumps;
+                min_price = min_price.min(new_s);
+                max_price = max_price.max(new_s);
+                // The bridge uniform is drawn on every substep, crossed or not, to keep the stream aligned
+                let substep_end = time + dt * (k + 1) as f64 / n_sub as f64;
+                if self.sim_inputs.bridge_correction {
+                    let u: f64 = rng.gen();
+                    first_touch.update_bridged(s, new_s, v_step * sub_dt, substep_end, u);
+                } else {
+                    first_touch.update(new_s, substep_end);
+                }
+                s = new_s;
+            }
+            self.apply_jump_vol_spike(&mut factors, params, step_jumps);
+            let jump_occurred = step_jumps > 0;
+            
+            log_returns.push(step_start, s, dt);
+            time += dt;
+            
+            if let Some(points) = trace.as_deref_mut() {
+                points.push(PathPoint { t: time, price: s });
+            }
+            if let Some(steps) = steps.as_deref_mut() {
+                steps.push(PathStep { t: time, s, v, regime, jump_occurred });
+            }
+        }
+        
+        PathOutcome {
+            initial_price: self.sim_inputs.s0,
+            final_price: s,
+            min_price,
+            max_price,
+            regime_switches,
+            realized_vol: log_returns.annualized_vol(),
+            qe,
+            first_touch: first_touch.state,
+            touch_time: first_touch.time,
+            terminal_shock,
+            regime_time,
+        }
+    }
+    
+    /// Simulate every basket constituent under a shared regime and return the index path
+    fn simulate_basket_path<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        mut trace: Option<&mut Vec<PathPoint>>,
+        shock_sign: f64,
+        touch: Option<[f64; 2]>,
+    ) -> PathOutcome {
+        let basket = self.sim_inputs.basket.as_ref().expect("basket mode");
+        let factor = self.basket_factor.as_ref().expect("basket factor");
+        let dt = self.sim_inputs.dt;
+        let n_assets = basket.assets.len();
+        
+        let index_value = |prices: &[f64]| -> f64 {
+            basket.assets.iter().zip(prices).map(|(asset, s)| asset.weight * s).sum()
+        };
+        
+        // Initialize state
+        let mut prices: Vec<f64> = basket.assets.iter().map(|asset| asset.s0).collect();
+        let initial_price = index_value(&prices);
+        let mut variances: Vec<[f64; 2]> = basket.assets.iter().map(|asset| self.initial_variance(&asset.regimes)).collect();
+        let mut regime = self.initial_regime(rng);
+        
+        let mut qe = QeCounts::default();
+        
+        for _ in 0..self.warmup_steps() {
+            regime = models::update_regime(rng, regime, &self.sim_inputs.hmm);
+            for (asset, factors) in basket.assets.iter().zip(variances.iter_mut()) {
+                let params = self.regime_params(&asset.regimes, regime);
+                self.step_variance(rng, factors, params, dt, &mut qe);
+            }
+        }
+        
+        let mut regime_switches = 0u32;
+        let mut log_returns = LogReturnStats::default();
+        let mut index = initial_price;
+        let mut min_price = index;
+        let mut max_price = index;
+        let mut time = 0.0;
+        let mut independent = vec![0.0; n_assets];
+        let mut drifts: Vec<f64> = basket.assets.iter().map(|asset| models::mu_at(self.regime_params(&asset.regimes, regime), time)).collect();
+        let mut carry_t = self.sim_inputs.carry.as_ref().map_or(0.0, |carry| carry.initial);
+        let mut first_touch = TouchMonitor::new(touch);
+        first_touch.update(index, time);
+        let mut regime_time = vec![0.0; self.sim_inputs.hmm.pi0.len()];
+        let mut excitations = vec![0.0; n_assets];
+        
+        if let Some(points) = trace.as_deref_mut() {
+            points.push(PathPoint { t: time, price: index_value(&prices) });
+        }
+        
+        for &dt in &self.steps {
+            let p = models::transition_matrix_at(&self.sim_inputs.hmm, time);
+            let next_regime = models::update_regime_with(rng, regime, p);
+            if next_regime != regime {
+                regime_switches += 1;
+            }
+            regime = next_regime;
+            regime_time[regime] += dt;
+            
+            // Correlate the price shocks through the Cholesky factor
+            for z in independent.iter_mut() {
+                *z = shock_sign * self.innovation(rng);
+            }
+            
+            let shock_multiplier = self.variance_multiplier(time);
+            // One funding rate for the whole index
+            let carry = self.step_carry(rng, &mut carry_t, dt);
+            
+            for (i, asset) in basket.assets.iter().enumerate() {
+                let params = self.regime_params(&asset.regimes, regime);
+                
+                let shock: f64 = (0..=i).map(|k| factor[i][k] * independent[k]).sum();
+                
+                let v = self.step_variance(rng, &mut variances[i], params, dt, &mut qe);
+                let regime_mu = models::mu_at(params, time);
+                let mu = self.measure_drift(match &self.sim_inputs.drift_ou {
+                    Some(ou) => {
+                        drifts[i] = models::simulate_ou(rng, drifts[i], regime_mu, ou.speed, ou.vol, dt);
+                        drifts[i]
+                    },
+                    None => regime_mu,
+                }) - carry;
+                let (new_s, n_jumps) = models::simulate_price_with_shock(
+                    rng,
+                    prices[i],
+                    v * shock_multiplier,
+                    mu,
+                    &asset.jumps,
+                    asset.jumps.lambda + excitations[i],
+                    dt,
+                    shock,
+                );
+                excitations[i] = models::hawkes_excitation(&asset.jumps, excitations[i], n_jumps, dt);
+                self.apply_jump_vol_spike(&mut variances[i], params, n_jumps);
+                prices[i] = new_s;
+            }
+            
+            let next_index = index_value(&prices);
+            log_returns.push(index, next_index, dt);
+            min_price = min_price.min(next_index);
+            max_price = max_price.max(next_index);
+            first_touch.update(next_index, time + dt);
+            index = next_index;
+            time += dt;
+            
+            if let Some(points) = trace.as_deref_mut() {
+                points.push(PathPoint { t: time, price: index });
+            }
+        }
+        
+        PathOutcome {
+            initial_price,
+            final_price: index,
+            min_price,
+            max_price,
+            regime_switches,
+            realized_vol: log_returns.annualized_vol(),
+            qe,
+            first_touch: first_touch.state,
+            touch_time: first_touch.time,
+            terminal_shock: 0.0,
+            regime_time,
+        }
+    }
+}
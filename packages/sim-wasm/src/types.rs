@@ -1,17 +1,33 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub enum Regime {
-    Bull,
-    Bear,
+/// Index of an HMM state into `RegimeSet`, `HMM.p` and `HMM.pi0`
+pub type Regime = usize;
+
+/// Two-regime inputs (and the legacy {"BULL", "BEAR"} JSON) put the bull state first
+pub const BULL: Regime = 0;
+pub const BEAR: Regime = 1;
+
+/// Display name of a regime in messages: BULL and BEAR for the first two, then "regime k"
+pub fn regime_name(regime: Regime) -> String {
+    match regime {
+        BULL => "BULL".to_string(),
+        BEAR => "BEAR".to_string(),
+        k => format!("regime {}", k),
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct HestonParams {
     pub kappa: f64,  // mean reversion speed
     pub theta: f64,  // long-term variance
     pub xi: f64,     // vol of vol
     pub rho: f64,    // correlation
+    #[serde(default = "default_var_floor")]
+    pub var_floor: f64,  // lower bound applied to simulated variance
+}
+
+fn default_var_floor() -> f64 {
+    1e-8
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,25 +35,84 @@ pub struct JumpParams {
     pub lambda: f64,   // jump intensity
     pub mu_j: f64,     // mean log jump size
     pub sigma_j: f64,  // std dev of log jump size
-    pub kind: String,  // "merton" or "kou"
+    pub kind: String,  // "merton", "kou" or "skew_normal"
+    #[serde(default)]
+    pub skew: f64,     // skew-normal shape (alpha), negative = heavier crash tail
+    #[serde(default)]
+    pub p_up: f64,     // kou: probability a jump is upward
+    #[serde(default)]
+    pub eta_up: f64,   // kou: rate of the upward exponential (mean up move 1 / eta_up), must exceed 1
+    #[serde(default)]
+    pub eta_down: f64, // kou: rate of the downward exponential
+    #[serde(default)]
+    pub alpha: f64,    // hawkes: intensity each jump adds, decaying at beta; 0 = constant-lambda Poisson
+    #[serde(default)]
+    pub beta: f64,     // hawkes: decay rate (per hour) of that excitation, must exceed alpha
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RegimeParams {
     pub mu: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mu_schedule: Vec<(f64, f64)>,  // piecewise-constant (from_hour, mu) from t = 0, replacing mu when set
     pub heston: HestonParams,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heston2: Option<HestonParams>,  // second variance factor (double Heston); total variance is the sum
+    #[serde(default)]
+    pub jump_vol_spike: f64,  // each jump in this regime adds jump_vol_spike * theta to the variance from the next step (crash then high vol)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HMM {
-    pub p: [[f64; 2]; 2],  // transition matrix
-    pub pi0: [f64; 2],     // initial probabilities
+    pub p: Vec<Vec<f64>>,  // N x N per-step transition matrix (rows sum to 1), N = number of regimes
+    pub pi0: Vec<f64>,     // initial probabilities
+    #[serde(default)]
+    pub schedule: Vec<HmmScheduleEntry>,  // time-varying overrides of p, sorted by from_hour
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RegimeSet {
-    pub BULL: RegimeParams,
-    pub BEAR: RegimeParams,
+pub struct HmmScheduleEntry {
+    pub from_hour: f64,      // hours from t = 0 at which this matrix takes over
+    pub p: Vec<Vec<f64>>,
+}
+
+/// Dynamics of each regime, indexed by `Regime`
+///
+/// Serializes as a list. The older {"BULL": .., "BEAR": ..} object still deserializes, as [BULL, BEAR].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "RegimeSetRepr")]
+pub struct RegimeSet(pub Vec<RegimeParams>);
+
+// Only lives for the length of a parse, so the legacy variant's size does not matter
+#[derive(Deserialize)]
+#[serde(untagged)]
+#[allow(non_snake_case, clippy::large_enum_variant)]
+enum RegimeSetRepr {
+    List(Vec<RegimeParams>),
+    Legacy { BULL: RegimeParams, BEAR: RegimeParams },
+}
+
+impl From<RegimeSetRepr> for RegimeSet {
+    fn from(repr: RegimeSetRepr) -> Self {
+        match repr {
+            RegimeSetRepr::List(regimes) => RegimeSet(regimes),
+            RegimeSetRepr::Legacy { BULL: bull, BEAR: bear } => RegimeSet(vec![bull, bear]),
+        }
+    }
+}
+
+impl std::ops::Deref for RegimeSet {
+    type Target = Vec<RegimeParams>;
+    
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for RegimeSet {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,24 +120,258 @@ pub struct SimInputs {
     pub s0: f64,         // current price
     pub t: f64,          // time to maturity (hours)
     pub dt: f64,         // time step
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dt_schedule: Vec<(f64, f64)>,  // (time_threshold, dt): dt applies from the threshold to the next; replaces dt over [0, t], warm-up keeps dt
     pub regimes: RegimeSet,
     pub hmm: HMM,
     pub jumps: JumpParams,
+    #[serde(default)]
+    pub use_stationary_pi0: bool,  // start regimes from the HMM's stationary distribution instead of pi0
+    #[serde(default)]
+    pub warmup_hours: f64,   // variance burn-in before t = 0, not counted toward t
+    #[serde(default)]
+    pub drift_ou: Option<DriftOu>,  // stochastic drift reverting to the regime's mu
+    #[serde(default)]
+    pub basket: Option<Basket>,  // when set, targets are evaluated on the weighted index
+    #[serde(default)]
+    pub vol_shocks: Vec<VolShock>,  // scheduled diffusive vol spikes, e.g. around an announcement
+    #[serde(default)]
+    pub carry: Option<CarryOu>,  // stochastic funding rate subtracted from the drift
+    #[serde(default)]
+    pub scenario_switch: Option<ScenarioSwitch>,  // deterministic structural break within each path
+    #[serde(default = "default_variance_scheme")]
+    pub variance_scheme: String,  // "qe" (default) or "full_truncation" Euler for every variance factor
+    #[serde(default = "default_pricing_measure")]
+    pub pricing_measure: String,  // "real_world" (default): regime mu; "risk_neutral": r replaces mu, compensator kept
+    #[serde(default)]
+    pub r: f64,  // risk-free rate (per hour), used under the risk-neutral measure
+    #[serde(default)]
+    pub jump_substep_threshold: Option<f64>,  // split single-asset steps whose lambda * dt exceeds this into finer diffusion + jump substeps
+    #[serde(default)]
+    pub qmc: bool,  // diffusive price shocks from a shifted Sobol sequence via a Brownian bridge; regimes, variance and jumps still use the RNG
+    #[serde(default)]
+    pub bridge_correction: bool,  // touch levels may also be crossed between steps, with the Brownian-bridge crossing probability
+    #[serde(default = "default_innovation")]
+    pub innovation: String,  // diffusive price shocks: "normal" (default) or "student_t", rescaled to unit variance
+    #[serde(default)]
+    pub df: f64,  // student_t degrees of freedom, above 2 so the variance is finite
+}
+
+fn default_innovation() -> String {
+    "normal".to_string()
+}
+
+fn default_variance_scheme() -> String {
+    "qe".to_string()
+}
+
+fn default_pricing_measure() -> String {
+    "real_world".to_string()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioSwitch {
+    pub at_hour: f64,  // steps starting at or after this time use `inputs`
+    pub inputs: ScenarioInputs,  // the path keeps its state and every other input
+}
+
+/// The parameter sets a scenario switch replaces; each must fit the base inputs' regime count
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioInputs {
+    pub regimes: RegimeSet,
+    pub hmm: HMM,
+    pub jumps: JumpParams,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CarryOu {
+    pub initial: f64,  // current funding/carry rate (per hour)
+    pub mean: f64,     // level it reverts to (per hour)
+    pub speed: f64,    // mean reversion speed (per hour)
+    pub vol: f64,      // volatility of the carry rate
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolShock {
+    pub start: f64,           // hours from t = 0
+    pub end: f64,             // exclusive; a step is shocked when its start time falls in [start, end)
+    pub vol_multiplier: f64,  // scales volatility, so variance is scaled by its square
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthCheck {
+    pub version: String,       // crate version
+    pub target: String,        // "wasm32" or "native"
+    pub panic_hook: bool,      // built with the console_error_panic_hook feature
+    pub parallel: bool,        // run_simulation_parallel is compiled in (native only)
+    pub rng: String,
+    pub variance_reduction: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExperimentVariant {
+    pub name: String,
+    pub inputs: SimInputs,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariantRun {
+    pub name: String,
+    pub seed: u64,  // derived from (base_seed, name); rerun alone with import_config
+    pub result: SimResult,
+}
+
+/// Everything a bug report needs to see what the engine was doing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DebugSnapshot {
+    pub build: HealthCheck,
+    pub config: EngineConfig,  // inputs, seed and options
+    pub rolling_window: Option<RollingWindowState>,
+    pub last_diagnostics: Option<SimDiagnostics>,  // from the most recent full run
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollingWindowState {
+    pub target: Option<Target>,
+    pub estimate: RollingEstimate,
+}
+
+/// Where an engine's RNG stream stands, to checkpoint a long run and resume it bit-identically
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RngState {
+    pub seed: u64,         // the engine seed the stream was started from
+    pub word_pos: String,  // 32-bit words consumed so far, in decimal: a u128 does not fit a JS number
+}
+
+/// Everything needed to rebuild an engine exactly as it was constructed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineConfig {
+    pub version: u32,  // config format version
+    pub seed: u64,
+    pub inputs: SimInputs,
+    #[serde(default)]
+    pub rolling_window: Option<u32>,  // size of the rolling estimate window, if enabled
+    #[serde(default)]
+    pub output: OutputOptions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriftOu {
+    pub speed: f64,  // mean reversion speed of mu_t towards the regime mu (per hour)
+    pub vol: f64,    // volatility of mu_t
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BasketAsset {
+    pub weight: f64,        // index weight w_i
+    pub s0: f64,            // current price of the constituent
+    pub regimes: RegimeSet, // per-asset dynamics, driven by the shared HMM regime
+    pub jumps: JumpParams,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Basket {
+    pub assets: Vec<BasketAsset>,
+    pub correlation: Vec<Vec<f64>>,  // correlation of the constituents' price shocks
+}
+
+/// Two assets, each a full single-asset model, whose diffusive price shocks are correlated
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiAssetInputs {
+    pub first: SimInputs,   // e.g. BTC
+    pub second: SimInputs,  // e.g. ETH; must share the first asset's step grid
+    pub rho_assets: f64,    // correlation of the two assets' Brownian increments
+}
+
+/// Both legs must settle YES, e.g. "ETH above X and BTC above Y"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JointTarget {
+    pub first: Target,
+    pub second: Target,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[allow(non_snake_case)]
 pub struct Target {
-    pub kind: String,    // "above" or "range"
-    pub K: Option<f64>,  // strike for above/below
-    pub L: Option<f64>,  // lower bound for range
-    pub U: Option<f64>,  // upper bound for range
+    pub kind: String,    // "above", "below", "range", "return_above", "realized_vol_above", "stayed_above", "touch" or "first_touch"
+    pub K: Option<f64>,  // strike for above/below, floor for stayed_above, barrier for touch
+    pub L: Option<f64>,  // lower bound for range, lower level for first_touch
+    pub U: Option<f64>,  // upper bound for range, upper level for first_touch (hit = U reached before L)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub l_inclusive: Option<bool>,  // range includes L (default true)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub u_inclusive: Option<bool>,  // range includes U (default true)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pct: Option<f64>,  // return threshold for return_above (0.03 = +3%, negative for downside)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vol: Option<f64>,  // annualized realized vol threshold for realized_vol_above (0.6 = 60%)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direction: Option<String>,  // touch: "up" (default, price reaches K from below) or "down"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payoff: Option<String>,  // "binary" (default), or "linear" / "capped" for above/below: max(S_T - K, 0) or max(K - S_T, 0)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cap: Option<f64>,    // capped: most the payoff pays
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub floor: Option<f64>,  // capped: least the payoff pays (default 0)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SimDiagnostics {
     pub stderr: f64,
+    pub relative_stderr: Option<f64>,  // stderr / p, None when p == 0
     pub n: u32,
-    pub convergence: Option<Vec<f64>>,
+    pub saturated: bool,  // every path hit or every path missed
+    pub convergence: Option<Vec<f64>>,  // running p after each twentieth of the paths
+    pub regime_switches: Option<RegimeSwitchStats>,
+    pub annualized_vol: AnnualizedVol,
+    pub terminal_variance: Option<TerminalVarianceCheck>,  // only for single-regime, jump-free Heston
+    pub qe_branches: Option<QeBranchStats>,  // None under the full_truncation scheme
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replay_seed: Option<u64>,  // base of the per-path seeds: replay_path(replay_seed, index)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scenario_switch_hour: Option<f64>,  // when the scenario's second parameter set took over
+    #[serde(default)]
+    pub feller_violations: bool,  // some variance factor has 2 kappa theta < xi^2, see check_feller
+    #[serde(default)]
+    pub regime_occupancy: Vec<f64>,  // share of simulated time spent in each regime, over all paths
+}
+
+/// Feller condition 2 kappa theta >= xi^2 for one variance factor of one regime
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FellerCheck {
+    pub regime: String,
+    pub factor: u32,          // 1 for heston, 2 for heston2
+    pub satisfied: bool,      // when false the variance reaches zero and the QE scheme degrades
+    pub ratio: Option<f64>,   // 2 kappa theta / xi^2; None when xi = 0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QeBranchStats {
+    pub steps: u64,           // variance steps across all paths, warm-up included
+    pub low_psi: f64,         // fraction taking the chi-square branch
+    pub high_psi: f64,        // fraction taking the exponential branch
+    pub high_psi_zero: f64,   // fraction landing on the zero-variance point mass
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminalVarianceCheck {
+    pub empirical: f64,    // sample variance of ln(S_T / s0)
+    pub theoretical: f64,  // expected integrated variance over the horizon
+    pub ratio: f64,        // empirical / theoretical; far from 1 points at a scheme bug
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnualizedVol {
+    pub by_regime: Vec<f64>,  // sqrt(theta + theta2) annualized, in regime order
+    pub blended: f64,         // sqrt of the pi0-weighted long-run variance, annualized
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegimeSwitchStats {
+    pub mean: f64,
+    pub variance: f64,
+    pub max: u32,
+    pub counts: Vec<u32>,  // counts[k] = number of paths with exactly k switches
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -71,6 +380,49 @@ pub struct PathPoint {
     pub price: f64,
 }
 
+/// Path with every shock at its expectation: the diffusive factor at its mean of 1 and no jumps
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpectedPath {
+    pub final_price: f64,
+    pub points: Vec<PathPoint>,
+}
+
+/// Full state after one step of a replayed path
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathStep {
+    pub t: f64,
+    pub s: f64,
+    pub v: f64,  // total variance used for the step (both factors, before any vol shock)
+    pub regime: Regime,
+    pub jump_occurred: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepresentativePath {
+    pub label: String,  // "lowest_hit", "median_hit", "highest_hit" or "median_miss"
+    pub id: u32,        // path index within the run
+    pub hit: bool,
+    pub final_price: f64,
+    pub points: Vec<PathPoint>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegimeOccupancy {
+    pub times: Vec<f64>,           // t = 0, then the end of each step
+    pub occupancy: Vec<Vec<f64>>,  // share of paths in each regime at each time
+    pub stationary: Option<Vec<f64>>,  // where the mix drifts to
+    pub n: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Candle {
+    pub hour: u32,  // bucket covers (hour, hour + 1]
+    pub open: f64,  // previous hour's close
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PathSample {
     pub id: u32,
@@ -93,14 +445,170 @@ pub struct DistributionSummary {
     pub samples: Vec<PathSample>,
 }
 
+/// Percentiles of the terminal price over every path of a run (unweighted)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminalStats {
+    pub p5: f64,
+    pub p25: f64,
+    pub p50: f64,
+    pub p75: f64,
+    pub p95: f64,
+}
+
+/// Percentiles of the first-passage time (hours from t = 0) over the paths that touched the barrier
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FirstPassageStats {
+    pub p5: f64,
+    pub p25: f64,
+    pub p50: f64,
+    pub p75: f64,
+    pub p95: f64,
+    pub touched: u32,  // paths the times are taken over; the rest of SimResult.n never touched
+}
+
+/// Equal-width histogram of terminal prices over `[min, max]`; `counts` sum to the path count
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceHistogram {
+    pub bin_edges: Vec<f64>,  // n_bins + 1 ascending edges
+    pub counts: Vec<u32>,
+}
+
+/// Raw and control-variate estimates from the same paths; the control is the digital of a driftless
+/// GBM at the blended long-run vol, driven by each path's own price shocks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlVariateEstimate {
+    pub p_raw: f64,
+    pub stderr_raw: f64,
+    pub p: f64,          // p_raw - beta * (control mean - analytic mean)
+    pub stderr: f64,
+    pub beta: f64,       // estimated optimal coefficient Cov(hit, control) / Var(control)
+    pub control_mean: f64,  // analytic GBM probability the control is centred on
+}
+
+/// Monte Carlo expectation of a non-binary target payoff over the terminal price
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayoffEstimate {
+    pub payoff: String,  // "linear" or "capped"
+    pub expected: f64,   // in price units, like K
+    pub stderr: f64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SimResult {
     pub target: Target,
     pub p: f64,
     pub ci: [f64; 2],
     pub fair: f64,
+    #[serde(default)]
+    pub hits: u32,  // raw count of paths hitting the target (unweighted)
+    #[serde(default)]
+    pub n: u32,     // paths simulated; sum hits and n across independent runs to pool them exactly
     pub diagnostics: SimDiagnostics,
     pub distribution: Option<DistributionSummary>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terminal_stats: Option<TerminalStats>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_passage: Option<FirstPassageStats>,  // touch and first_touch targets, once any path touched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_histogram: Option<PriceHistogram>,  // when OutputOptions.histogram_bins is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub risk: Option<RiskMetrics>,  // when OutputOptions.risk_level is set, over this run's terminal prices
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub control_variate: Option<ControlVariateEstimate>,  // run_simulation_control_variate only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payoff: Option<PayoffEstimate>,  // targets with a linear or capped payoff; p stays the hit rate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixed_point: Option<FixedPointValues>,  // display-ready integers, when enabled
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ci_levels: Vec<LevelCi>,  // one interval per OutputOptions.ci_levels entry, from the same counts
+    #[serde(default)]
+    pub incomplete: bool,  // run stopped early (budget or cancel); diagnostics.n paths were used
+}
+
+/// Engine-wide presentation settings applied to every `SimResult`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OutputOptions {
+    #[serde(default)]
+    pub fixed_point_decimals: Option<u32>,  // emit FixedPointValues with this many decimals
+    #[serde(default)]
+    pub ci_levels: Vec<f64>,  // extra confidence levels reported in SimResult.ci_levels
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub histogram_bins: Option<u32>,  // emit SimResult.price_histogram with this many bins
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub risk_level: Option<f64>,  // emit SimResult.risk at this tail probability
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelCi {
+    pub level: f64,
+    pub ci: [f64; 2],
+}
+
+/// Values scaled by 10^decimals and rounded half away from zero
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixedPointValues {
+    pub decimals: u32,
+    pub fair: i64,       // cents
+    pub ci: [i64; 2],    // cents
+    pub price_min: Option<i64>,
+    pub price_mean: Option<i64>,
+    pub price_max: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DtStudyRow {
+    pub dt: f64,
+    pub p: f64,
+    pub ci: [f64; 2],
+    pub stderr: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DtStudy {
+    pub rows: Vec<DtStudyRow>,  // in the order the dt values were given
+    pub n: u32,                 // paths per dt
+    pub seed: u64,              // common seed shared by every dt
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DualMeasureResult {
+    pub real_world: SimResult,    // regime mu drift, for forecasting
+    pub risk_neutral: SimResult,  // risk-free r drift, for pricing
+    pub seed: u64,                // common seed, so both runs share their paths
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThetaDecayPoint {
+    pub t: f64,  // remaining hours to expiry
+    pub fair: f64,
+    pub p: f64,
+    pub ci: [f64; 2],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThetaDecayCurve {
+    pub points: Vec<ThetaDecayPoint>,  // in the order the time points were given
+    pub n: u32,                        // paths per time point
+    pub seed: u64,                     // common seed shared by every time point
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmartRunResult {
+    pub strategy: String,  // "antithetic", "importance" or "plain"
+    pub reason: String,    // why the pilot led to that choice
+    pub pilot_p: f64,
+    pub pilot_n: u32,
+    pub result: SimResult,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollingEstimate {
+    pub p: f64,
+    pub ci: [f64; 2],
+    pub fair: f64,
+    pub window_size: u32,   // configured capacity
+    pub effective_n: u32,   // paths currently in the window
+    pub total_paths: u64,   // paths simulated since the window was (re)started
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -112,8 +620,170 @@ pub struct IntermediateResult {
     pub fair: f64,
 }
 
-// Ensure types are Send + Sync for WASM
-unsafe impl Send for SimInputs {}
-unsafe impl Sync for SimInputs {}
-unsafe impl Send for SimResult {}
-unsafe impl Sync for SimResult {}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultDiff {
+    pub dp: f64,            // b.p - a.p
+    pub dfair: f64,         // b.fair - a.fair (cents)
+    pub dci: [f64; 2],      // change in each CI bound
+    pub z: f64,             // dp over the combined standard error
+    pub overlap: bool,      // do the two CIs overlap?
+    pub significant: bool,  // CIs are disjoint
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConditionalResult {
+    pub target: Target,
+    pub min_vol: f64,             // condition: annualized realized vol above this
+    pub p: f64,                   // P(target | condition), NaN-free: 0 when no path qualifies
+    pub ci: [f64; 2],
+    pub condition_rate: f64,      // fraction of paths meeting the condition
+    pub condition_ci: [f64; 2],
+    pub n_conditioned: u32,       // paths meeting the condition
+    pub n: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JointResult {
+    pub target: JointTarget,
+    pub p: f64,         // P(both legs hit)
+    pub ci: [f64; 2],   // 95% interval for p
+    pub fair: f64,      // p in cents
+    pub p_first: f64,   // marginal P(first leg hits), on the same paths
+    pub p_second: f64,  // marginal P(second leg hits)
+    pub hits: u32,
+    pub n: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpreadResult {
+    pub lower: f64,
+    pub upper: f64,
+    pub p_lower: f64,             // P(above lower)
+    pub p_upper: f64,             // P(above upper)
+    pub p: f64,                   // p_lower - p_upper = P(lower < S_T <= upper)
+    pub ci: [f64; 2],
+    pub stderr: f64,              // paired: both legs share the same paths
+    pub independent_stderr: f64,  // what two separate runs of this size would give
+    pub n: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StrikeLadderResult {
+    pub strikes: Vec<f64>,
+    pub p: Vec<f64>,                  // raw P(above K) per strike, all from the same paths
+    pub ci: Vec<[f64; 2]>,
+    pub smoothed: Option<Vec<f64>>,   // logistic-in-log-strike or isotonic fit, monotone in K; None without smoothing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub covariance: Option<Vec<Vec<f64>>>,  // covariance of the p estimates, from the shared paths
+    pub n: u32,
+}
+
+/// Three-way outcome of a first_touch race; the probabilities sum to 1
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FirstTouchRace {
+    pub target: Target,
+    pub p_upper: f64,    // U reached before L (the first_touch target's p)
+    pub p_lower: f64,    // L reached before U
+    pub p_neither: f64,  // neither level reached by the horizon
+    pub ci_upper: [f64; 2],
+    pub ci_lower: [f64; 2],
+    pub ci_neither: [f64; 2],
+    pub n: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeInTheMoney {
+    pub target: Target,
+    pub fraction: f64,  // expected share of steps ending above K
+    pub stderr: f64,
+    pub hours: f64,     // fraction * simulated horizon
+    pub n: u32,
+}
+
+/// Tail risk of the terminal log-return ln(S_T / s0); both figures are returns, so a loss is negative
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RiskMetrics {
+    pub level: f64,  // tail probability, e.g. 0.05
+    pub var: f64,    // `level` quantile of the log-return, interpolated between order statistics
+    pub cvar: f64,   // expected shortfall: mean log-return over the tail at or below `var`
+    pub n: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminalQuantiles {
+    pub probs: Vec<f64>,
+    pub quantiles: Vec<f64>,  // terminal price at each probability, interpolated between order statistics
+    pub n: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StrikeForFair {
+    pub target_cents: f64,
+    pub strike: f64,      // K with P(above K) * 100 == target_cents
+    pub ci: [f64; 2],     // 95% order-statistic interval for K
+    pub n: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImpliedVol {
+    pub target: Target,
+    pub market_price_cents: f64,
+    pub implied_vol: f64,  // flat annualized vol whose Black-Scholes digital matches the market
+    pub model_vol: f64,    // the model's blended annualized vol, for comparison
+    pub vol_ratio: f64,    // implied_vol / model_vol
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JumpSensitivity {
+    pub target: Target,
+    pub lambda: f64,         // base jump intensity (per hour)
+    pub lambda_down: f64,    // lambda - bump, floored at lambda itself when that would go negative
+    pub lambda_up: f64,
+    pub fair: f64,
+    pub dfair_dlambda: f64,  // cents per unit of hourly intensity
+    pub stderr: f64,         // from the per-path differences, which share random numbers
+    pub n: u32,
+}
+
+/// Common multiplier on every regime's theta at which the model's p matches a market probability
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolScaleFit {
+    pub target: Target,
+    pub market_prob: f64,
+    pub factor: f64,      // theta (and var_floor) scale; the vol scales by its square root
+    pub p: f64,           // model p at `factor`, on the same paths as every bisection step
+    pub stderr: f64,
+    pub iterations: u32,  // bisection steps taken
+    pub n: u32,
+}
+
+/// Central-difference Greeks of the fair value, every leg priced on the same per-path seeds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Greeks {
+    pub target: Target,
+    pub fair: f64,
+    pub ds: f64,
+    pub dvol: f64,           // bump to every regime's annualized long-run vol sqrt(theta * hours per year)
+    pub delta: f64,          // cents per unit of s0
+    pub delta_stderr: f64,
+    pub vega: f64,           // cents per unit of annualized vol
+    pub vega_stderr: f64,
+    pub n: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelWarning {
+    pub code: String,     // stable identifier, e.g. "negligible_vol_of_vol"
+    pub message: String,  // human-readable explanation
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpectedProfit {
+    pub side: String,                 // "buy" or "sell" YES
+    pub market_price_cents: f64,
+    pub n_contracts: u32,
+    pub p: f64,                       // model probability used
+    pub fee_cents: f64,               // Kalshi fee for the whole position
+    pub expected_profit_cents: f64,   // expected P&L of the position, net of fees
+    pub ci: [f64; 2],                 // expected P&L at the bounds of the probability CI
+}
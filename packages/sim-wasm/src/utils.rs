@@ -1,5 +1,8 @@
 use wasm_bindgen::prelude::*;
 
+/// The model's time unit is the hour (`SimInputs.t`, `dt` and all rates are per hour)
+pub const HOURS_PER_YEAR: f64 = 24.0 * 365.0;
+
 pub fn set_panic_hook() {
     // When the `console_error_panic_hook` feature is enabled, we can call the
     // `set_panic_hook` function at least once during initialization, and then
@@ -11,20 +14,37 @@ pub fn set_panic_hook() {
     console_error_panic_hook::set_once();
 }
 
+/// Two-sided critical value z with P(|Z| <= z) = confidence; levels outside (0, 1) fall back to 95%
+pub fn two_sided_z(confidence: f64) -> f64 {
+    let confidence = if confidence > 0.0 && confidence < 1.0 { confidence } else { 0.95 };
+    normal_quantile(0.5 + confidence / 2.0)
+}
+
+/// One-sided critical value z with P(Z <= z) = confidence; levels outside (0, 1) fall back to 95%
+pub fn one_sided_z(confidence: f64) -> f64 {
+    let confidence = if confidence > 0.0 && confidence < 1.0 { confidence } else { 0.95 };
+    normal_quantile(confidence)
+}
+
 /// Calculate Wilson score confidence interval for binomial proportion
 pub fn wilson_ci(successes: u32, n: u32, confidence: f64) -> [f64; 2] {
+    wilson_bounds(successes, n, two_sided_z(confidence))
+}
+
+/// A single Wilson bound at the one-sided level `confidence`: the upper confidence limit when
+/// `upper`, else the lower one (95% one-sided uses z = 1.645 rather than 1.96)
+pub fn wilson_ci_one_sided(successes: u32, n: u32, confidence: f64, upper: bool) -> f64 {
+    let [lower, upper_bound] = wilson_bounds(successes, n, one_sided_z(confidence));
+    if upper { upper_bound } else { lower }
+}
+
+/// Wilson score bounds center -/+ margin for critical value `z`
+fn wilson_bounds(successes: u32, n: u32, z: f64) -> [f64; 2] {
     if n == 0 {
         return [0.0, 1.0];
     }
     
     let p = successes as f64 / n as f64;
-    let z = if confidence == 0.95 {
-        1.96
-    } else if confidence == 0.99 {
-        2.576
-    } else {
-        1.96 // Default to 95%
-    };
     
     let z_sq = z * z;
     let n_f = n as f64;
@@ -33,10 +53,43 @@ pub fn wilson_ci(successes: u32, n: u32, confidence: f64) -> [f64; 2] {
     let center = (p + z_sq / (2.0 * n_f)) / denominator;
     let margin = (z * (p * (1.0 - p) / n_f + z_sq / (4.0 * n_f * n_f)).sqrt()) / denominator;
     
-    [
-        (center - margin).max(0.0),
-        (center + margin).min(1.0)
-    ]
+    // Pin the bounds at the edges exactly; the closed form only reaches them up to rounding
+    let lower = if successes == 0 { 0.0 } else { (center - margin).max(0.0) };
+    let upper = if successes == n { 1.0 } else { (center + margin).min(1.0) };
+    
+    [lower, upper]
+}
+
+/// Binomial confidence interval that stays honest when every path hit or every path missed
+///
+/// Wilson collapses towards a point at p == 0 or p == 1, so saturated counts fall back to a
+/// rule-of-three style bound instead (upper bound ~3/n at 95% when there are zero hits).
+pub fn binomial_ci(successes: u32, n: u32, confidence: f64) -> [f64; 2] {
+    if n == 0 {
+        return [0.0, 1.0];
+    }
+    
+    if successes == 0 {
+        [0.0, rule_of_three(n, confidence)]
+    } else if successes == n {
+        [1.0 - rule_of_three(n, confidence), 1.0]
+    } else {
+        wilson_ci(successes, n, confidence)
+    }
+}
+
+/// Upper bound on p after observing zero events in n trials: -ln(1 - confidence) / n
+pub fn rule_of_three(n: u32, confidence: f64) -> f64 {
+    (-(1.0 - confidence).ln() / n as f64).min(1.0)
+}
+
+/// Kalshi trading fee in cents: roundup(0.07 * C * P * (1 - P)) to the next cent, P in dollars
+pub fn kalshi_fee_cents(price_cents: f64, n_contracts: u32) -> f64 {
+    let p = price_cents / 100.0;
+    let fee_cents = 7.0 * n_contracts as f64 * p * (1.0 - p);
+    
+    // Shave float noise so an exact cent amount isn't rounded up a further cent
+    (fee_cents - 1e-9).ceil().max(0.0)
 }
 
 /// Calculate standard error for binomial proportion
@@ -44,6 +97,216 @@ pub fn binomial_stderr(p: f64, n: u32) -> f64 {
     (p * (1.0 - p) / n as f64).sqrt()
 }
 
+/// Binomial stderr of `hits` in `n` paths; a saturated count (0 or n) has zero sample variance,
+/// so its stderr is taken at the rule-of-three bound instead to reflect the sample size
+pub fn hit_stderr(hits: u32, n: u32) -> f64 {
+    if hits == 0 || hits == n {
+        binomial_stderr(rule_of_three(n, 0.95), n)
+    } else {
+        binomial_stderr(hits as f64 / n as f64, n)
+    }
+}
+
+/// Weighted least-squares fit of logit(p) = a + b * x, returning (a, b)
+///
+/// Weights are the binomial information n * p * (1 - p); estimates are clamped half a count
+/// away from 0 and 1 so saturated points stay finite. With non-increasing `p` over increasing
+/// `x` the fitted slope is never positive, so the fitted curve stays monotone.
+pub fn logistic_fit(x: &[f64], p: &[f64], n: u32) -> Option<(f64, f64)> {
+    if x.len() != p.len() || x.len() < 2 || n == 0 {
+        return None;
+    }
+    
+    let eps = 0.5 / n as f64;
+    let mut sw = 0.0;
+    let mut sx = 0.0;
+    let mut sy = 0.0;
+    let mut sxx = 0.0;
+    let mut sxy = 0.0;
+    
+    for (&xi, &pi) in x.iter().zip(p) {
+        let q = pi.clamp(eps, 1.0 - eps);
+        let w = n as f64 * q * (1.0 - q);
+        let y = (q / (1.0 - q)).ln();
+        sw += w;
+        sx += w * xi;
+        sy += w * y;
+        sxx += w * xi * xi;
+        sxy += w * xi * y;
+    }
+    
+    let denom = sw * sxx - sx * sx;
+    if denom.abs() < f64::EPSILON {
+        return None;
+    }
+    
+    let b = (sw * sxy - sx * sy) / denom;
+    let a = (sy - b * sx) / sw;
+    Some((a, b))
+}
+
+/// Pool-adjacent-violators fit of a non-increasing sequence to `y` with weights `w`: the weighted
+/// least-squares fit among non-increasing sequences, pooling every run that rises into its mean
+pub fn isotonic_decreasing(y: &[f64], w: &[f64]) -> Vec<f64> {
+    // (mean, weight, length) of each pooled block
+    let mut blocks: Vec<(f64, f64, usize)> = Vec::with_capacity(y.len());
+    for (&yi, &wi) in y.iter().zip(w) {
+        let mut block = (yi, wi, 1);
+        while let Some(&(mean, weight, len)) = blocks.last() {
+            if mean >= block.0 {
+                break;
+            }
+            blocks.pop();
+            let total = weight + block.1;
+            block = ((mean * weight + block.0 * block.1) / total, total, len + block.2);
+        }
+        blocks.push(block);
+    }
+    
+    blocks.iter().flat_map(|&(mean, _, len)| std::iter::repeat_n(mean, len)).collect()
+}
+
+/// Logistic function 1 / (1 + e^-x)
+pub fn logistic(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// Sample quantile of ascending-sorted data with linear interpolation between order statistics
+pub fn quantile_sorted(sorted: &[f64], prob: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+    
+    let pos = prob.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lo = pos.floor() as usize;
+    let hi = pos.ceil() as usize;
+    let frac = pos - lo as f64;
+    
+    sorted[lo] + (sorted[hi] - sorted[lo]) * frac
+}
+
+/// Lower-triangular Cholesky factor L of a symmetric positive-definite matrix (A = L L^T)
+pub fn cholesky(matrix: &[Vec<f64>]) -> Result<Vec<Vec<f64>>, String> {
+    let n = matrix.len();
+    if matrix.iter().any(|row| row.len() != n) {
+        return Err("Matrix must be square".to_string());
+    }
+    
+    let mut l = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in 0..=i {
+            let sum: f64 = (0..j).map(|k| l[i][k] * l[j][k]).sum();
+            if i == j {
+                let d = matrix[i][i] - sum;
+                if d <= 0.0 {
+                    return Err("Matrix is not positive definite".to_string());
+                }
+                l[i][j] = d.sqrt();
+            } else {
+                l[i][j] = (matrix[i][j] - sum) / l[j][j];
+            }
+        }
+    }
+    
+    Ok(l)
+}
+
+/// SplitMix64 finalizer: a cheap, well-mixed bijection on u64
+pub fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Independent per-path seed, so any single path can be regenerated from (base, index)
+pub fn path_seed(base: u64, index: u64) -> u64 {
+    splitmix64(base ^ splitmix64(index))
+}
+
+/// 64-bit FNV-1a: a stable string hash (unlike std's, which is randomized per process)
+pub fn fnv1a64(text: &str) -> u64 {
+    text.bytes().fold(0xCBF2_9CE4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3))
+}
+
+/// Seed for a named experiment variant, deterministic in (base_seed, name)
+pub fn variant_seed(base_seed: u64, name: &str) -> u64 {
+    splitmix64(base_seed ^ fnv1a64(name))
+}
+
+/// Wall-clock milliseconds, for run budgets (`std::time` is unavailable on wasm32-unknown-unknown)
+#[cfg(target_arch = "wasm32")]
+pub fn now_ms() -> f64 {
+    js_sys::Date::now()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now_ms() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0.0, |elapsed| elapsed.as_secs_f64() * 1000.0)
+}
+
+/// Seconds since the Unix epoch of an RFC 3339 timestamp such as "2025-03-01T16:00:00Z" or
+/// "2025-03-01T12:00:00.5-04:00" (fractional seconds optional, an offset or Z required)
+pub fn parse_rfc3339(text: &str) -> Result<f64, String> {
+    let invalid = || format!("Invalid timestamp \"{}\", expected e.g. 2025-03-01T16:00:00Z", text);
+    let bytes = text.as_bytes();
+    if bytes.len() < 20 || !bytes.is_ascii() || bytes[4] != b'-' || bytes[7] != b'-' || bytes[13] != b':' || bytes[16] != b':' {
+        return Err(invalid());
+    }
+    if !matches!(bytes[10], b'T' | b't' | b' ') {
+        return Err(invalid());
+    }
+    let field = |range: std::ops::Range<usize>| -> Result<i64, String> {
+        let digits = &text[range];
+        if digits.bytes().all(|b| b.is_ascii_digit()) { digits.parse().map_err(|_| invalid()) } else { Err(invalid()) }
+    };
+    
+    let (year, month, day) = (field(0..4)?, field(5..7)?, field(8..10)?);
+    let (hour, minute, second) = (field(11..13)?, field(14..16)?, field(17..19)?);
+    let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let month_days = [31, if leap { 29 } else { 28 }, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+    if !(1..=12).contains(&month) || day < 1 || day > month_days[month as usize - 1] || hour > 23 || minute > 59 || second > 59 {
+        return Err(invalid());
+    }
+    
+    // Optional fraction, then Z or +-HH:MM
+    let mut rest = &text[19..];
+    let mut fraction = 0.0;
+    if let Some(after_dot) = rest.strip_prefix('.') {
+        let n_digits = after_dot.bytes().take_while(|b| b.is_ascii_digit()).count();
+        if n_digits == 0 {
+            return Err(invalid());
+        }
+        fraction = format!("0.{}", &after_dot[..n_digits]).parse().map_err(|_| invalid())?;
+        rest = &after_dot[n_digits..];
+    }
+    let offset_minutes = match rest.as_bytes() {
+        [b'Z' | b'z'] => 0,
+        [sign @ (b'+' | b'-'), _, _, b':', _, _] => {
+            let (hours, minutes) = (field(text.len() - 5..text.len() - 3)?, field(text.len() - 2..text.len())?);
+            if hours > 23 || minutes > 59 {
+                return Err(invalid());
+            }
+            let magnitude = hours * 60 + minutes;
+            if *sign == b'-' { -magnitude } else { magnitude }
+        },
+        _ => return Err(invalid()),
+    };
+    
+    // Days from 1970-01-01 to the civil date (proleptic Gregorian)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    
+    let seconds = days * 86_400 + hour * 3_600 + minute * 60 + second - offset_minutes * 60;
+    Ok(seconds as f64 + fraction)
+}
+
 /// Normal CDF approximation
 pub fn normal_cdf(x: f64) -> f64 {
     use statrs::distribution::{Normal, ContinuousCDF};
@@ -95,6 +358,27 @@ impl ProgressReporter {
 mod tests {
     use super::*;
     
+    #[test]
+    fn test_path_seeds_are_distinct() {
+        // Reference value of the SplitMix64 output for state 0
+        assert_eq!(splitmix64(0), 0xE220_A839_7B1D_CDAF);
+        
+        let seeds: std::collections::HashSet<u64> = (0..1000).map(|i| path_seed(42, i)).collect();
+        assert_eq!(seeds.len(), 1000);
+        assert_ne!(path_seed(42, 0), path_seed(43, 0));
+    }
+    
+    #[test]
+    fn test_variant_seeds_are_stable() {
+        // Published FNV-1a test vectors
+        assert_eq!(fnv1a64(""), 0xCBF2_9CE4_8422_2325);
+        assert_eq!(fnv1a64("a"), 0xAF63_DC4C_8601_EC8C);
+        
+        assert_eq!(variant_seed(7, "control"), variant_seed(7, "control"));
+        assert_ne!(variant_seed(7, "control"), variant_seed(7, "treatment"));
+        assert_ne!(variant_seed(7, "control"), variant_seed(8, "control"));
+    }
+    
     #[test]
     fn test_wilson_ci() {
         // Test edge cases
@@ -110,5 +394,120 @@ mod tests {
         let ci = wilson_ci(50, 100, 0.95);
         assert!(ci[0] > 0.4);
         assert!(ci[1] < 0.6);
+        
+        // Any level works, and higher confidence means a wider interval
+        assert!((two_sided_z(0.95) - 1.959964).abs() < 1e-6);
+        assert!((two_sided_z(0.99) - 2.575829).abs() < 1e-6);
+        let narrow = wilson_ci(50, 100, 0.5);
+        let wide = wilson_ci(50, 100, 0.99);
+        assert!(wide[0] < ci[0] && ci[0] < narrow[0]);
+        assert!(narrow[1] < ci[1] && ci[1] < wide[1]);
+    }
+    
+    #[test]
+    fn test_wilson_ci_other_levels() {
+        let ci_90 = wilson_ci(30, 200, 0.90);
+        let ci_95 = wilson_ci(30, 200, 0.95);
+        assert!(ci_95[0] < ci_90[0] && ci_90[1] < ci_95[1]);
+        
+        // 95% agrees with the old hard-coded z = 1.96 to three decimals
+        let (p, n, z) = (0.15f64, 200.0, 1.96);
+        let denominator = 1.0 + z * z / n;
+        let center = (p + z * z / (2.0 * n)) / denominator;
+        let margin = z * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt() / denominator;
+        assert!((ci_95[0] - (center - margin)).abs() < 5e-4);
+        assert!((ci_95[1] - (center + margin)).abs() < 5e-4);
+    }
+    
+    #[test]
+    fn test_wilson_one_sided_bounds() {
+        assert!((one_sided_z(0.95) - 1.644854).abs() < 1e-6);
+        
+        // A one-sided 95% limit spends all 5% on one tail, so it sits inside the two-sided 95% interval
+        for (successes, n) in [(3, 1000), (50, 100), (0, 500)] {
+            let two_sided = wilson_ci(successes, n, 0.95);
+            let upper = wilson_ci_one_sided(successes, n, 0.95, true);
+            let lower = wilson_ci_one_sided(successes, n, 0.95, false);
+            assert!(upper < two_sided[1]);
+            assert!(lower >= two_sided[0]);
+            assert!(lower <= successes as f64 / n as f64 && successes as f64 / n as f64 <= upper);
+        }
+        
+        // One-sided 97.5% is the upper end of the two-sided 95% interval
+        assert!((wilson_ci_one_sided(50, 100, 0.975, true) - wilson_ci(50, 100, 0.95)[1]).abs() < 1e-12);
+    }
+    
+    #[test]
+    fn test_binomial_ci_saturated() {
+        // Zero hits: bounded by sample size rather than collapsing to zero
+        let ci = binomial_ci(0, 1000, 0.95);
+        assert_eq!(ci[0], 0.0);
+        assert!((ci[1] - 3.0 / 1000.0).abs() < 1e-4);
+        
+        let ci = binomial_ci(1000, 1000, 0.95);
+        assert!((ci[0] - (1.0 - 3.0 / 1000.0)).abs() < 1e-4);
+        assert_eq!(ci[1], 1.0);
+        
+        // Interior counts are plain Wilson
+        assert_eq!(binomial_ci(50, 100, 0.95), wilson_ci(50, 100, 0.95));
+    }
+    
+    #[test]
+    fn test_isotonic_decreasing_pools_violators() {
+        let fit = isotonic_decreasing(&[0.9, 0.5, 0.7, 0.2, 0.3, 0.1], &[1.0; 6]);
+        let expected = [0.9, 0.6, 0.6, 0.25, 0.25, 0.1];
+        assert!(fit.iter().zip(&expected).all(|(a, b)| (a - b).abs() < 1e-12), "{:?}", fit);
+        
+        // A heavier point pulls its pool toward itself
+        let fit = isotonic_decreasing(&[0.4, 0.6], &[3.0, 1.0]);
+        assert!(fit.iter().all(|v| (v - 0.45).abs() < 1e-12));
+        
+        assert_eq!(isotonic_decreasing(&[0.8, 0.5, 0.5], &[1.0; 3]), vec![0.8, 0.5, 0.5]);
+    }
+    
+    #[test]
+    fn test_logistic_fit_recovers_curve() {
+        let x: Vec<f64> = (0..9).map(|i| i as f64 - 4.0).collect();
+        let p: Vec<f64> = x.iter().map(|&xi| logistic(0.5 - 1.5 * xi)).collect();
+        
+        let (a, b) = logistic_fit(&x, &p, 10_000).unwrap();
+        assert!((a - 0.5).abs() < 1e-3);
+        assert!((b + 1.5).abs() < 1e-3);
+        
+        assert!(logistic_fit(&x[..1], &p[..1], 10_000).is_none());
+    }
+    
+    #[test]
+    fn test_quantile_sorted() {
+        let data = [1.0, 2.0, 3.0, 4.0, 5.0];
+        assert_eq!(quantile_sorted(&data, 0.0), 1.0);
+        assert_eq!(quantile_sorted(&data, 0.5), 3.0);
+        assert_eq!(quantile_sorted(&data, 1.0), 5.0);
+        assert!((quantile_sorted(&data, 0.1) - 1.4).abs() < 1e-12);
+    }
+    
+    #[test]
+    fn test_parse_rfc3339() {
+        assert_eq!(parse_rfc3339("1970-01-01T00:00:00Z").unwrap(), 0.0);
+        assert_eq!(parse_rfc3339("2000-03-01T00:00:00Z").unwrap(), 951_868_800.0);
+        assert_eq!(parse_rfc3339("2024-02-29T12:30:00.25Z").unwrap(), 1_709_209_800.25);
+        
+        // The same instant written in two zones
+        assert_eq!(parse_rfc3339("2025-03-01T12:00:00-04:00").unwrap(), parse_rfc3339("2025-03-01T16:00:00Z").unwrap());
+        
+        for bad in ["", "2025-03-01", "2025-03-01T16:00:00", "2025-02-29T00:00:00Z", "2025-13-01T00:00:00Z",
+                    "2025-03-01T24:00:00Z", "2025-03-01T16:00:00.Z", "2025-03-01T16:00:00+0400", "2025-03-0xT16:00:00Z"] {
+            assert!(parse_rfc3339(bad).is_err(), "{}", bad);
+        }
+    }
+    
+    #[test]
+    fn test_cholesky() {
+        let a = vec![vec![1.0, 0.6], vec![0.6, 1.0]];
+        let l = cholesky(&a).unwrap();
+        assert!((l[1][0] - 0.6).abs() < 1e-12);
+        assert!((l[1][1] - 0.8).abs() < 1e-12);
+        
+        assert!(cholesky(&[vec![1.0, 2.0], vec![2.0, 1.0]]).is_err());
     }
 }